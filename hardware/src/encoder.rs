@@ -1,4 +1,5 @@
 use core::prelude::rust_2024::*;
+use core::sync::atomic::{AtomicI32, Ordering};
 use either::Either;
 use embedded_hal::digital::v2::InputPin;
 
@@ -7,6 +8,14 @@ pub struct Encoder<PinA: InputPin, PinB: InputPin> {
     dt: PinB,
     state: u8,
     pulses: i8,
+    resolution: Resolution,
+    invert_a: bool,
+    invert_b: bool,
+    acceleration: Option<Acceleration>,
+    // when the last detent was read, for acceleration
+    last_detent: Option<u32>,
+    // steps taken since the last call to `take_steps`, positive clockwise
+    steps: i32,
     dir: Dir,
 }
 
@@ -17,108 +26,293 @@ pub enum Dir {
     Same,
 }
 
+/// Number of pulses, quadrature transitions, between two detents of an encoder.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Resolution {
+    One = 1,
+    Two = 2,
+    Four = 4,
+}
+
+/// Takes more than one step when detents are read in quick succession.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Acceleration {
+    /// A detent read less than this many ms after the previous one is fast.
+    pub threshold: u32,
+    /// Number of steps a fast detent takes.
+    pub multiplier: u8,
+}
+
 impl<PinA: InputPin, PinB: InputPin> Encoder<PinA, PinB> {
     const LOOKUP_TABLE: [i8; 16] = [0, -1, 1, 0, 1, 0, 0, -1, -1, 0, 0, 1, 0, 1, -1, 0];
 
-    pub const fn new(pin_a: PinA, pin_b: PinB) -> Self {
+    pub const fn new(pin_a: PinA, pin_b: PinB, resolution: Resolution) -> Self {
         Self {
             clk: pin_a,
             dt: pin_b,
             state: 0,
             pulses: 0,
+            resolution,
+            invert_a: false,
+            invert_b: false,
+            acceleration: None,
+            last_detent: None,
+            steps: 0,
             dir: Dir::Same,
         }
     }
 
+    /// Inverts the level read from either pin, inverting one of them reverses the direction.
+    #[must_use]
+    pub const fn invert(mut self, invert_a: bool, invert_b: bool) -> Self {
+        self.invert_a = invert_a;
+        self.invert_b = invert_b;
+        self
+    }
+
+    /// Accelerates fast spins, only applies when updated with [`update_at`](Self::update_at).
+    #[must_use]
+    pub const fn accelerate(mut self, acceleration: Acceleration) -> Self {
+        self.acceleration = Some(acceleration);
+        self
+    }
+
     /// Returns the update of this [`Encoder<PinA, PinB>`].
     ///
     /// # Errors
     ///
     /// This function will return an error if either input pins return errors.
     pub fn update(&mut self) -> Result<(), Either<PinA::Error, PinB::Error>> {
+        self.read(None)
+    }
+
+    /// Updates the encoder at `now` ms, accelerating fast spins.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if either input pins return errors.
+    pub fn update_at(&mut self, now: u32) -> Result<(), Either<PinA::Error, PinB::Error>> {
+        self.read(Some(now))
+    }
+
+    fn read(&mut self, now: Option<u32>) -> Result<(), Either<PinA::Error, PinB::Error>> {
         #[rustfmt::skip]
-        let clk: u8 = u8::from(self.clk.is_high().map_err(Either::Left)?);
-        let dt: u8 = u8::from(self.dt.is_high().map_err(Either::Right)?);
+        let clk: u8 = u8::from(self.clk.is_high().map_err(Either::Left)? != self.invert_a);
+        let dt: u8 = u8::from(self.dt.is_high().map_err(Either::Right)? != self.invert_b);
 
         let new_state: u8 = clk << 1 | dt;
 
-        #[allow(clippy::if_not_else)]
+        self.dir = Dir::Same;
+
         if self.state & 0b0011 != new_state {
             self.state <<= 2;
             self.state |= new_state;
 
             self.pulses += Self::LOOKUP_TABLE[self.state as usize & 0b1111];
-            if self.pulses == 4 {
-                self.dir = Dir::Cw;
-            } else if self.pulses == -4 {
-                self.dir = Dir::Cww;
-            } else {
-                self.dir = Dir::Same;
+
+            let resolution = self.resolution as i8;
+            let detents = i32::from(self.pulses / resolution);
+            self.pulses %= resolution;
+
+            if detents != 0 {
+                let steps = detents * i32::from(self.multiplier(now));
+                self.steps = self.steps.saturating_add(steps);
+                self.dir = if steps > 0 { Dir::Cw } else { Dir::Cww };
             }
-            self.pulses %= 4;
-        } else {
-            self.dir = Dir::Same;
         }
         Ok(())
     }
 
+    // returns the steps a detent read at `now` takes
+    const fn multiplier(&mut self, now: Option<u32>) -> u8 {
+        let (Some(acceleration), Some(now)) = (self.acceleration, now) else {
+            return 1;
+        };
+
+        let last_detent = self.last_detent.replace(now);
+        match last_detent {
+            Some(last_detent) if now.wrapping_sub(last_detent) < acceleration.threshold => {
+                acceleration.multiplier
+            }
+            _ => 1,
+        }
+    }
+
+    /// Returns the pins, for clearing interrupts on them.
+    pub const fn pins_mut(&mut self) -> (&mut PinA, &mut PinB) {
+        (&mut self.clk, &mut self.dt)
+    }
+
+    /// Returns the direction of the detents read by the last update.
     pub const fn direction(&self) -> Dir {
         self.dir
     }
+
+    /// Returns the steps taken since the last call, positive clockwise.
+    pub const fn take_steps(&mut self) -> i32 {
+        let steps = self.steps;
+        self.steps = 0;
+        steps
+    }
+}
+
+/// Steps of an encoder read in an interrupt handler, shared with the main loop.
+///
+/// Only uses atomic loads and stores, so it works on chips without atomic read-modify-write
+/// like the RP2040. There must be a single writer, the interrupt handler.
+pub struct StepCounter {
+    // every step ever added, wrapping
+    total: AtomicI32,
+}
+
+impl StepCounter {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            total: AtomicI32::new(0),
+        }
+    }
+
+    /// Adds steps, only call this from the one interrupt handler reading the encoder.
+    pub fn add(&self, steps: i32) {
+        let total = self.total.load(Ordering::Relaxed);
+        self.total.store(total.wrapping_add(steps), Ordering::Release);
+    }
+
+    /// Returns every step added so far, wrapping, the steps since the last read are the
+    /// wrapping difference with the previous total.
+    pub fn total(&self) -> i32 {
+        self.total.load(Ordering::Acquire)
+    }
+}
+
+impl Default for StepCounter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-// mod test {
-//     use embedded_hal::{digital::v2::InputPin, digital::v2::{ErrorType, Error}};
-//
-//     use crate::encoder::Dir;
-//
-//     use super::Encoder;
-//
-//     struct FakeInputPin {
-//         state: bool
-//     }
-//
-//     impl FakeInputPin {
-//         fn new(state: bool) -> Self {
-//             Self {
-//                 state,
-//             }
-//         }
-//
-//         fn set_state(&mut self, state: bool) {
-//             self.state = state;
-//         }
-//     }
-//
-//     #[derive(Debug)]
-//     struct CantError;
-//
-//     impl Error for CantError {
-//         fn kind(&self) -> embedded_hal::digital::ErrorKind {
-//             embedded_hal::digital::ErrorKind::Other
-//         }
-//     }
-//
-//     impl ErrorType for FakeInputPin {
-//         type Error = CantError;
-//     }
-//
-//     impl InputPin for FakeInputPin {
-//         fn is_high(&mut self) -> Result<bool, Self::Error> {
-//             Ok(self.state == true)
-//         }
-//
-//         fn is_low(&mut self) -> Result<bool, Self::Error> {
-//             Ok(self.state == false)
-//         }
-//     }
-//
-//     fn test() {
-//         let pin_a = FakeInputPin::new(false);
-//         let pin_b = FakeInputPin::new(false);
-//
-//         let encoder = Encoder::new(pin_a, pin_b);
-//
-//         assert_eq!(encoder.direction(), Dir::Same);
-//     }
-// }
+#[cfg(test)]
+mod test {
+    use core::cell::Cell;
+    use core::convert::Infallible;
+
+    use embedded_hal::digital::v2::InputPin;
+
+    use super::{Acceleration, Dir, Encoder, Resolution, StepCounter};
+
+    struct FakeInputPin<'a> {
+        state: &'a Cell<bool>,
+    }
+
+    impl InputPin for FakeInputPin<'_> {
+        type Error = Infallible;
+
+        fn is_high(&self) -> Result<bool, Self::Error> {
+            Ok(self.state.get())
+        }
+
+        fn is_low(&self) -> Result<bool, Self::Error> {
+            Ok(!self.state.get())
+        }
+    }
+
+    // levels of the pins through one full clockwise cycle
+    const CW: [(bool, bool); 4] = [(true, false), (true, true), (false, true), (false, false)];
+
+    fn setup<'a>(
+        a: &'a Cell<bool>,
+        b: &'a Cell<bool>,
+        resolution: Resolution,
+    ) -> Encoder<FakeInputPin<'a>, FakeInputPin<'a>> {
+        Encoder::new(FakeInputPin { state: a }, FakeInputPin { state: b }, resolution)
+    }
+
+    // drives the pins through `levels`, updating the encoder at `now` after each change
+    fn turn(
+        encoder: &mut Encoder<FakeInputPin<'_>, FakeInputPin<'_>>,
+        a: &Cell<bool>,
+        b: &Cell<bool>,
+        levels: impl IntoIterator<Item = (bool, bool)>,
+        now: u32,
+    ) -> Vec<Dir> {
+        levels
+            .into_iter()
+            .map(|(level_a, level_b)| {
+                a.set(level_a);
+                b.set(level_b);
+                encoder.update_at(now).unwrap();
+                encoder.direction()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn starts_still() {
+        let (a, b) = (Cell::new(false), Cell::new(false));
+        let mut encoder = setup(&a, &b, Resolution::Four);
+
+        encoder.update().unwrap();
+        assert_eq!(encoder.direction(), Dir::Same);
+        assert_eq!(encoder.take_steps(), 0);
+    }
+
+    #[test]
+    fn resolution_sets_pulses_per_detent() {
+        for (resolution, steps) in [
+            (Resolution::Four, 1),
+            (Resolution::Two, 2),
+            (Resolution::One, 4),
+        ] {
+            let (a, b) = (Cell::new(false), Cell::new(false));
+            let mut encoder = setup(&a, &b, resolution);
+
+            let dirs = turn(&mut encoder, &a, &b, CW, 0);
+            assert_eq!(encoder.take_steps(), steps);
+            assert_eq!(dirs.last(), Some(&Dir::Cw));
+            assert_eq!(encoder.take_steps(), 0);
+        }
+    }
+
+    #[test]
+    fn counter_clockwise_and_inverted() {
+        let (a, b) = (Cell::new(false), Cell::new(false));
+        let mut encoder = setup(&a, &b, Resolution::Four);
+        turn(&mut encoder, &a, &b, CW.into_iter().rev().skip(1).chain([(false, false)]), 0);
+        assert_eq!(encoder.direction(), Dir::Cww);
+        assert_eq!(encoder.take_steps(), -1);
+
+        // inverting one pin reverses the direction
+        let (a, b) = (Cell::new(true), Cell::new(false));
+        let mut encoder = setup(&a, &b, Resolution::Four).invert(true, false);
+        turn(&mut encoder, &a, &b, CW.into_iter().chain([(true, false)]), 0);
+        assert_eq!(encoder.take_steps(), -1);
+    }
+
+    #[test]
+    fn fast_detents_accelerate() {
+        let (a, b) = (Cell::new(false), Cell::new(false));
+        let mut encoder = setup(&a, &b, Resolution::Four).accelerate(Acceleration {
+            threshold: 50,
+            multiplier: 3,
+        });
+
+        turn(&mut encoder, &a, &b, CW, 0);
+        assert_eq!(encoder.take_steps(), 1);
+        turn(&mut encoder, &a, &b, CW, 20);
+        assert_eq!(encoder.take_steps(), 3);
+        turn(&mut encoder, &a, &b, CW, 200);
+        assert_eq!(encoder.take_steps(), 1);
+    }
+
+    #[test]
+    fn counter_totals_added_steps() {
+        let counter = StepCounter::new();
+        counter.add(3);
+        counter.add(-1);
+        assert_eq!(counter.total(), 2);
+
+        counter.add(i32::MAX);
+        assert_eq!(counter.total().wrapping_sub(2), i32::MAX);
+    }
+}
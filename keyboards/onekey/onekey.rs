@@ -2,38 +2,35 @@
 #![no_main]
 #![feature(stmt_expr_attributes)] // enables skipping rustfmt
 
+use keyboard_rs::config::{Config, Debounce};
+use keyboard_rs::debounce::Algorithm;
 use keyboard_rs::hardware::Encoder;
-use keyboard_rs::keycode::{Keycode, Keycode::*};
-use keyboard_rs::{init, matrix_scaning, Board};
+use keyboard_rs::keycode::Keycode::*;
+use keyboard_rs::{init, layout, matrix_scaning};
 use rp2040_hal::gpio::{DynPinId, FunctionSio, Pin, PullDown, PullUp, SioInput, SioOutput};
 use rp2040_hal::Watchdog;
 
 use panic_halt as _;
-use rp2040_hal::timer::CountDown;
 use rp2040_hal::usb::UsbBus;
 use rp_pico::entry;
 
 #[entry]
 fn main() -> ! {
-    const NUMOFCOL: usize = 1;
-    const NUMOFROW: usize = 1;
-    const NUMOFLAYES: usize = 2;
     const NUMOFENCODERS: usize = 1;
 
-    #[rustfmt::skip]
-    const KEYS: &[&[&[Keycode]]] = &[
-        &[
-            &[KC_LAYER(1)]
-        ],
-        &[
-            &[KC_LAYER(0)]
-        ]
-    ];
+    layout! {
+        const KEYS: Layout<NUMOFCOL, NUMOFROW, NUMOFLAYES> = [
+            [
+                [KC_LAYER(1)]
+            ],
+            [
+                [KC_LAYER(0)]
+            ]
+        ];
+    }
 
     let (pins, board, timer) = init();
 
-    let (timer0, timer1) = Board::setup_timers(&timer, &timer);
-
     let col: &mut [Pin<DynPinId, FunctionSio<SioOutput>, PullDown>] =
         &mut [pins.gpio27.into_push_pull_output().into_dyn_pin()];
     let row: &mut [Pin<DynPinId, FunctionSio<SioInput>, PullDown>] =
@@ -57,8 +54,21 @@ fn main() -> ! {
         Pin<DynPinId, FunctionSio<SioInput>, PullUp>,
         Pin<DynPinId, FunctionSio<SioOutput>, PullDown>,
         Pin<DynPinId, FunctionSio<SioInput>, PullDown>,
-        CountDown,
         Watchdog,
         UsbBus,
-    >(board, col, row, KEYS, [encoder], timer0, timer1);
+    >(
+        board,
+        col,
+        row,
+        &KEYS,
+        [encoder],
+        Config {
+            debounce: Debounce {
+                algorithm: Algorithm::EagerPressDeferRelease,
+                time: 5,
+            },
+            ..Config::new()
+        },
+        &timer,
+    );
 }
@@ -0,0 +1,24 @@
+//! The time every part of a keyboard runs on.
+//!
+//! Scanning, debouncing, tap/hold, combos, macros, mouse keys, RGB and the display all read the
+//! milliseconds of one free-running [`Clock`], so a slow loop doesn't slow them down.
+//!
+//! ```ignore
+//! let (pins, board, timer) = init();
+//! let keyboard = Keyboard::new(&KEYS, cols, rows, Config::new(), &timer, &usb_bus);
+//! ```
+
+/// A free-running clock.
+pub trait Clock {
+    /// Returns the ms since the clock started, wrapping.
+    fn now(&self) -> u32;
+}
+
+#[cfg(feature = "rp2040")]
+impl Clock for rp2040_hal::Timer {
+    #[allow(clippy::cast_possible_truncation)]
+    fn now(&self) -> u32 {
+        // the counter is in us, the ms wrap after 49 days like every time in the keyboard
+        (self.get_counter().ticks() / 1000) as u32
+    }
+}
@@ -0,0 +1,438 @@
+use crate::debounce::Algorithm;
+use crate::keycode::Keycode;
+use crate::rgb::{Animation, Hsv};
+use crate::tap_dance::Action;
+
+/// Tap/hold behaviour of `KC_MT` and `KC_LT` keys.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TapHold {
+    /// How long in ms a key has to be held before it counts as held.
+    pub tapping_term: u32,
+    /// Holds if another key is pressed and released while the key is held, even within the tapping term.
+    pub permissive_hold: bool,
+    /// Holds as soon as another key is pressed while the key is held.
+    pub hold_on_other_key_press: bool,
+    /// Sends the tap key if the key is released after the tapping term without another key being pressed.
+    pub retro_tapping: bool,
+}
+
+impl TapHold {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            tapping_term: 200,
+            permissive_hold: false,
+            hold_on_other_key_press: false,
+            retro_tapping: false,
+        }
+    }
+}
+
+impl Default for TapHold {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How the speed of a held mouse key changes over time.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Acceleration {
+    /// Always moves by the move delta.
+    Constant,
+    /// Speeds up linearly from the move delta to `max_speed` times the move delta, like QMK's
+    /// default mouse keys.
+    Accelerated {
+        /// Multiple of the move delta reached after `time_to_max` moves.
+        max_speed: u8,
+        /// Number of moves it takes to reach `max_speed`.
+        time_to_max: u8,
+    },
+}
+
+/// Movement of the cursor or the wheel while a mouse key is held.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Motion {
+    /// Distance of the first move.
+    pub delta: u8,
+    /// How long in ms after the first move the key starts repeating.
+    pub delay: u32,
+    /// How long in ms between repeated moves.
+    pub interval: u32,
+    pub acceleration: Acceleration,
+}
+
+/// Behaviour of the `KC_MS_*` and `KC_WH_*` keys.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MouseKeys {
+    pub cursor: Motion,
+    pub wheel: Motion,
+}
+
+impl MouseKeys {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            cursor: Motion {
+                delta: 8,
+                delay: 10,
+                interval: 16,
+                acceleration: Acceleration::Accelerated {
+                    max_speed: 10,
+                    time_to_max: 30,
+                },
+            },
+            wheel: Motion {
+                delta: 1,
+                delay: 10,
+                interval: 80,
+                acceleration: Acceleration::Accelerated {
+                    max_speed: 8,
+                    time_to_max: 40,
+                },
+            },
+        }
+    }
+}
+
+impl Default for MouseKeys {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The direction of the diodes in the matrix.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DiodeDirection {
+    /// Anodes on the columns, cathodes on the rows.
+    Col2Row,
+    /// Anodes on the rows, cathodes on the columns.
+    Row2Col,
+}
+
+/// The level an output pin is driven to when it is scanned.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ActiveLevel {
+    /// Outputs are driven high and pulled down inputs read high when a key is pressed.
+    High,
+    /// Outputs are driven low and pulled up inputs read low when a key is pressed.
+    Low,
+}
+
+/// Wiring of the matrix.
+///
+/// The output pins are the anodes when the active level is high and the cathodes when it is low,
+/// so the default of `Col2Row` and `High` drives the columns and reads the rows.
+/// Layouts are always addressed as `[row][col]`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MatrixConfig {
+    pub diode_direction: DiodeDirection,
+    pub active_level: ActiveLevel,
+}
+
+impl MatrixConfig {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            diode_direction: DiodeDirection::Col2Row,
+            active_level: ActiveLevel::High,
+        }
+    }
+
+    /// Returns if the output pins are the columns, otherwise they are the rows.
+    #[must_use]
+    pub const fn outputs_are_cols(&self) -> bool {
+        matches!(
+            (self.diode_direction, self.active_level),
+            (DiodeDirection::Col2Row, ActiveLevel::High)
+                | (DiodeDirection::Row2Col, ActiveLevel::Low)
+        )
+    }
+}
+
+impl Default for MatrixConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Debouncing of every key in the matrix.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Debounce {
+    pub algorithm: Algorithm,
+    /// How long in ms a key has to be stable before a change is sent, or is ignored after one
+    /// with [`Algorithm::EagerBoth`].
+    pub time: u32,
+}
+
+impl Debounce {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            algorithm: Algorithm::SymmetricDefer,
+            time: 5,
+        }
+    }
+}
+
+impl Default for Debounce {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Behaviour of rotary encoders.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Encoders {
+    /// How long in ms the key of a detent is held down before it is released.
+    pub tap_duration: u32,
+}
+
+impl Encoders {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { tap_duration: 10 }
+    }
+}
+
+impl Default for Encoders {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// RGB underglow, changed at runtime with the `KC_RGB_*` keys.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Underglow {
+    /// Number of LEDs on the strip, up to [`MAX_LEDS`](crate::rgb::MAX_LEDS).
+    pub leds: usize,
+    /// Starts lit, `KC_RGB_TOG` turns it on and off.
+    pub enabled: bool,
+    pub animation: Animation,
+    pub color: Hsv,
+    /// Hue and saturation used instead of the colour's on each layer, layers without an entry
+    /// or with `None` use the colour.
+    pub layer_colors: &'static [Option<Hsv>],
+    /// How long in ms one cycle of the animation lasts.
+    pub period: u32,
+    /// How long in ms between frames.
+    pub frame_interval: u32,
+}
+
+impl Underglow {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            leds: 0,
+            enabled: true,
+            animation: Animation::Static,
+            color: Hsv::new(0, 255, 128),
+            layer_colors: &[],
+            period: 2000,
+            frame_interval: 16,
+        }
+    }
+}
+
+impl Default for Underglow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Keys pressed together to send another keycode, like `J` and `K` sending `KC_ESCAPE`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Combo {
+    /// Positions of the keys as `(row, col)`, up to 8 keys.
+    pub keys: &'static [(usize, usize)],
+    /// Sent instead of the keys, from the position of the first key pressed.
+    pub keycode: Keycode,
+    /// The only layer the combo works on, `None` for every layer.
+    pub layer: Option<usize>,
+    /// How long in ms the keys can take to be pressed, `None` for the term of [`Combos`].
+    pub term: Option<u32>,
+}
+
+impl Combo {
+    #[must_use]
+    pub const fn new(keys: &'static [(usize, usize)], keycode: Keycode) -> Self {
+        Self {
+            keys,
+            keycode,
+            layer: None,
+            term: None,
+        }
+    }
+}
+
+/// Combos of keys pressed together.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Combos {
+    pub combos: &'static [Combo],
+    /// How long in ms the keys of a combo can take to be pressed, unless the combo has its own.
+    pub term: u32,
+}
+
+impl Combos {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            combos: &[],
+            term: 50,
+        }
+    }
+}
+
+impl Default for Combos {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Keys typed after `KC_LEAD` to run an action, like `G` then `S` typing `git status`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Sequence {
+    /// The keycodes typed, up to 8 keys.
+    pub keys: &'static [Keycode],
+    pub action: Action,
+}
+
+impl Sequence {
+    #[must_use]
+    pub const fn new(keys: &'static [Keycode], action: Action) -> Self {
+        Self { keys, action }
+    }
+}
+
+/// Sequences of keys typed after a `KC_LEAD` key.
+///
+/// The keys typed are never sent, they are matched against the sequences until one matches, none
+/// can match anymore or no key is typed for the timeout.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Leader {
+    pub sequences: &'static [Sequence],
+    /// How long in ms to wait for the next key.
+    pub timeout: u32,
+}
+
+impl Leader {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            sequences: &[],
+            timeout: 300,
+        }
+    }
+}
+
+impl Default for Leader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A key sending another keycode while modifiers are held, like Shift and Backspace sending
+/// Delete.
+///
+/// The modifiers that trigger it aren't sent while the key is held. A left or right modifier
+/// means either of them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KeyOverride {
+    pub modifiers: &'static [Keycode],
+    pub key: Keycode,
+    /// Sent instead of the key and the modifiers.
+    pub replacement: Keycode,
+    /// The only layer the override works on, `None` for every layer.
+    pub layer: Option<usize>,
+}
+
+impl KeyOverride {
+    #[must_use]
+    pub const fn new(modifiers: &'static [Keycode], key: Keycode, replacement: Keycode) -> Self {
+        Self {
+            modifiers,
+            key,
+            replacement,
+            layer: None,
+        }
+    }
+}
+
+/// A status display, like an OLED, showing the layer, lock LEDs and WPM.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DisplayConfig {
+    /// Names shown for each layer, layers without a name show their number.
+    pub layer_names: &'static [&'static str],
+    /// The least time in ms between refreshes, a refresh only happens when the status changed.
+    pub refresh_interval: u32,
+}
+
+impl DisplayConfig {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            layer_names: &[],
+            refresh_interval: 100,
+        }
+    }
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What the keyboard does when scanning, reading an encoder or USB fails.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Recovery {
+    /// Drops the error, `periodic` returns `Ok`.
+    Ignore,
+    /// Counts the error and keeps going, `periodic` returns the error.
+    Count,
+    /// Counts the error and resets the USB device if it came from USB, forcing the host to
+    /// enumerate it again, `periodic` returns the error.
+    ResetUsb,
+    /// Counts the error, `periodic` returns the error and `matrix_scaning` reboots the board.
+    Reboot,
+}
+
+/// Behaviour settings for a keyboard.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Config {
+    pub tap_hold: TapHold,
+    pub mouse_keys: MouseKeys,
+    pub matrix: MatrixConfig,
+    pub debounce: Debounce,
+    pub recovery: Recovery,
+    pub encoders: Encoders,
+    pub underglow: Underglow,
+    pub display: DisplayConfig,
+    pub combos: Combos,
+    pub leader: Leader,
+    pub key_overrides: &'static [KeyOverride],
+}
+
+impl Config {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            tap_hold: TapHold::new(),
+            mouse_keys: MouseKeys::new(),
+            matrix: MatrixConfig::new(),
+            debounce: Debounce::new(),
+            recovery: Recovery::ResetUsb,
+            encoders: Encoders::new(),
+            underglow: Underglow::new(),
+            display: DisplayConfig::new(),
+            combos: Combos::new(),
+            leader: Leader::new(),
+            key_overrides: &[],
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
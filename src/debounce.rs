@@ -1,5 +1,3 @@
-use embedded_hal::timer::CountDown;
-
 pub fn debounce(input: impl Fn() -> bool, time: impl Fn() -> u32, ms: u32) -> bool {
     let mut last_change = time();
     let mut last_state =  input();
@@ -30,25 +28,166 @@ pub fn debounce_wait(input: impl Fn() -> bool, wait: impl Fn() -> bool) -> bool
     last_state
 }
 
-pub fn debounce_countdown<C: CountDown>(input: impl Fn() -> bool, wait: &mut C) -> bool 
-where C::Time: From<fugit::Duration<u32, 1, 1000000>> {
-    use fugit::ExtU32;
+/// The debounce algorithm used for every key in the matrix.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Algorithm {
+    /// Presses and releases are only reported once the key has been stable for the debounce time.
+    SymmetricDefer,
+    /// Presses are reported straight away, releases once the key has been stable for the debounce time.
+    EagerPressDeferRelease,
+    /// Every change is reported straight away, then the key is ignored for the debounce time.
+    EagerBoth,
+}
+
+#[derive(Clone, Copy)]
+struct KeyState {
+    debounced: bool,
+    raw: bool,
+    // last time the raw state changed
+    raw_changed_at: u32,
+    // last time the debounced state changed
+    debounced_changed_at: Option<u32>,
+}
+
+impl KeyState {
+    const fn new() -> Self {
+        Self {
+            debounced: false,
+            raw: false,
+            raw_changed_at: 0,
+            debounced_changed_at: None,
+        }
+    }
+}
+
+/// Non-blocking per key debouncer for a `ROWS` x `COLS` matrix.
+///
+/// Time is passed in by the caller in milliseconds, so the debouncer never waits on a timer.
+pub struct Debouncer<const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize> {
+    algorithm: Algorithm,
+    debounce_ms: u32,
+    keys: [[KeyState; NUM_OF_COLS]; NUM_OF_ROWS],
+}
+
+impl<const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize> Debouncer<NUM_OF_COLS, NUM_OF_ROWS> {
+    #[must_use]
+    pub const fn new(algorithm: Algorithm, debounce_ms: u32) -> Self {
+        Self {
+            algorithm,
+            debounce_ms,
+            keys: [[KeyState::new(); NUM_OF_COLS]; NUM_OF_ROWS],
+        }
+    }
+
+    /// Feeds the raw state of a key read at `now` (in ms) and returns its debounced state.
+    pub fn update(&mut self, row: usize, col: usize, raw: bool, now: u32) -> bool {
+        let key = &mut self.keys[row][col];
+
+        if key.raw != raw {
+            key.raw = raw;
+            key.raw_changed_at = now;
+        }
+
+        if key.raw != key.debounced {
+            let stable = now.wrapping_sub(key.raw_changed_at) >= self.debounce_ms;
+            let settled = key
+                .debounced_changed_at
+                .is_none_or(|changed_at| now.wrapping_sub(changed_at) >= self.debounce_ms);
+
+            let accept = match self.algorithm {
+                Algorithm::SymmetricDefer => stable,
+                Algorithm::EagerPressDeferRelease => key.raw || stable,
+                Algorithm::EagerBoth => settled,
+            };
+
+            if accept {
+                key.debounced = key.raw;
+                key.debounced_changed_at = Some(now);
+            }
+        }
+
+        key.debounced
+    }
+
+    /// Returns the debounced state of a key without updating it.
+    #[must_use]
+    pub const fn is_pressed(&self, row: usize, col: usize) -> bool {
+        self.keys[row][col].debounced
+    }
+}
+
+impl<const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize> Default
+    for Debouncer<NUM_OF_COLS, NUM_OF_ROWS>
+{
+    fn default() -> Self {
+        Self::new(Algorithm::SymmetricDefer, 5)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Algorithm, Debouncer};
+
+    // feeds `raw` once per ms from `start` to `end` and returns the debounced state of every call
+    fn feed(
+        debouncer: &mut Debouncer<1, 1>,
+        raw: impl Fn(u32) -> bool,
+        start: u32,
+        end: u32,
+    ) -> Vec<bool> {
+        (start..end)
+            .map(|now| debouncer.update(0, 0, raw(now), now))
+            .collect()
+    }
+
+    #[test]
+    fn symmetric_defer_waits_for_stable_press_and_release() {
+        let mut debouncer = Debouncer::<1, 1>::new(Algorithm::SymmetricDefer, 5);
+
+        let pressed = feed(&mut debouncer, |_| true, 0, 10);
+        assert_eq!(pressed.iter().position(|state| *state), Some(5));
 
-    let mut last_state = input();
+        let released = feed(&mut debouncer, |_| false, 10, 20);
+        assert_eq!(released.iter().position(|state| !*state), Some(5));
+    }
+
+    #[test]
+    fn symmetric_defer_ignores_chatter() {
+        let mut debouncer = Debouncer::<1, 1>::new(Algorithm::SymmetricDefer, 5);
+
+        let states = feed(&mut debouncer, |now| now % 2 == 0, 0, 20);
+        assert!(states.iter().all(|state| !*state));
+    }
+
+    #[test]
+    fn eager_press_defer_release() {
+        let mut debouncer = Debouncer::<1, 1>::new(Algorithm::EagerPressDeferRelease, 5);
 
-    wait.start(10.millis());
+        assert!(debouncer.update(0, 0, true, 0));
 
-    while wait.wait().is_ok() {}
+        // a bounce on the way down doesn't release the key
+        assert!(debouncer.update(0, 0, false, 1));
+        assert!(debouncer.update(0, 0, true, 2));
 
-    input()
+        let released = feed(&mut debouncer, |_| false, 3, 12);
+        assert_eq!(released.iter().position(|state| !*state), Some(5));
+    }
 
-    // while wait.wait().is_err() {
-    //     let current_state = input();
-    //
-    //     if last_state != current_state {
-    //         last_state = current_state;
-    //     }
-    // }
-    //
-    // last_state
+    #[test]
+    fn eager_both_locks_out_after_change() {
+        let mut debouncer = Debouncer::<1, 1>::new(Algorithm::EagerBoth, 5);
+
+        assert!(debouncer.update(0, 0, true, 0));
+        assert!(debouncer.update(0, 0, false, 1));
+        assert!(debouncer.update(0, 0, false, 4));
+        assert!(!debouncer.update(0, 0, false, 5));
+    }
+
+    #[test]
+    fn handles_clock_wrap() {
+        let mut debouncer = Debouncer::<1, 1>::new(Algorithm::SymmetricDefer, 5);
+
+        assert!(!debouncer.update(0, 0, true, u32::MAX - 1));
+        assert!(debouncer.update(0, 0, true, 3));
+    }
 }
@@ -0,0 +1,161 @@
+//! Status displays, like an SSD1306 OLED.
+//!
+//! [`Keyboard::periodic`](crate::Keyboard::periodic) shows the [`Status`] of the keyboard on a
+//! [`StatusDisplay`] whenever it changes, at most once per refresh interval of the
+//! [`DisplayConfig`](crate::config::DisplayConfig), then sends it a bit at a time with
+//! [`StatusDisplay::flush`] so the matrix keeps being scanned.
+//!
+//! With the `oled` feature, `Ssd1306` drives an I2C OLED and `Simulator` renders to memory for
+//! tests, both drawing the status with `draw_status` on `embedded-graphics`.
+//!
+//! ```ignore
+//! let mut oled = Ssd1306::new(i2c, Height::H32);
+//! oled.init().unwrap();
+//! let keyboard = Keyboard::new(/* .. */).with_display(&mut oled);
+//! ```
+
+use crate::indicator::Leds;
+
+#[cfg(feature = "oled")]
+mod simulator;
+#[cfg(feature = "oled")]
+mod ssd1306;
+
+#[cfg(feature = "oled")]
+pub use simulator::Simulator;
+#[cfg(feature = "oled")]
+pub use ssd1306::{Height, Ssd1306};
+
+/// What a status display shows.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Status {
+    /// The highest active layer.
+    pub layer: usize,
+    /// The name of the layer, empty when it has none.
+    pub layer_name: &'static str,
+    pub leds: Leds,
+    /// Words per minute typed over the last few seconds.
+    pub wpm: u16,
+}
+
+/// A display that failed to take what was shown, like an I2C display that isn't connected.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DisplayError;
+
+/// A display showing the status of the keyboard.
+pub trait StatusDisplay {
+    /// Shows `status`, errors are left to the display since the keyboard works without it.
+    ///
+    /// A display that takes a while to update should only draw it here and send it in
+    /// [`flush`](Self::flush).
+    fn show(&mut self, status: &Status);
+
+    /// Sends part of what was shown without blocking.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`nb::Error::WouldBlock`] while there is more to send, it is called again until
+    /// the rest is sent. A display that fails gives up on what was shown, it is shown again
+    /// after the refresh interval.
+    fn flush(&mut self) -> nb::Result<(), DisplayError> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "oled")]
+pub use graphics::draw_status;
+
+#[cfg(feature = "oled")]
+mod graphics {
+    use core::fmt::{self, Write};
+
+    use embedded_graphics::mono_font::ascii::FONT_6X10;
+    use embedded_graphics::mono_font::MonoTextStyle;
+    use embedded_graphics::pixelcolor::BinaryColor;
+    use embedded_graphics::prelude::{DrawTarget, Point};
+    use embedded_graphics::text::{Baseline, Text};
+    use embedded_graphics::Drawable;
+
+    use super::Status;
+
+    // height of a line of text in pixels
+    pub(super) const LINE_HEIGHT: i32 = 10;
+    // characters that fit on a 128 pixel wide line
+    const LINE_LEN: usize = 21;
+
+    // a line of text formatted without allocating, cut at the width of the display
+    struct Line {
+        bytes: [u8; LINE_LEN],
+        len: usize,
+    }
+
+    impl Line {
+        const fn new() -> Self {
+            Self {
+                bytes: [0; LINE_LEN],
+                len: 0,
+            }
+        }
+
+        fn as_str(&self) -> &str {
+            // only whole `str`s are written
+            core::str::from_utf8(&self.bytes[..self.len]).unwrap_or_default()
+        }
+    }
+
+    impl Write for Line {
+        // writes as much of `s` as there is room for, cut at a character boundary
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let mut end = s.len().min(LINE_LEN - self.len);
+            while !s.is_char_boundary(end) {
+                end -= 1;
+            }
+
+            self.bytes[self.len..self.len + end].copy_from_slice(&s.as_bytes()[..end]);
+            self.len += end;
+            Ok(())
+        }
+    }
+
+    /// Draws the layer, the lock LEDs that are on and the WPM on three lines of text.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the draw target.
+    pub fn draw_status<D: DrawTarget<Color = BinaryColor>>(
+        target: &mut D,
+        status: &Status,
+    ) -> Result<(), D::Error> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+
+        let mut layer = Line::new();
+        // lines too long for the display are cut
+        let _ = if status.layer_name.is_empty() {
+            write!(layer, "Layer {}", status.layer)
+        } else {
+            write!(layer, "{}", status.layer_name)
+        };
+
+        let mut locks = Line::new();
+        for (on, name) in [
+            (status.leds.caps_lock, "CAPS "),
+            (status.leds.num_lock, "NUM "),
+            (status.leds.scroll_lock, "SCRL"),
+        ] {
+            if on {
+                let _ = locks.write_str(name);
+            }
+        }
+
+        let mut wpm = Line::new();
+        let _ = write!(wpm, "WPM {}", status.wpm);
+
+        for (index, line) in [layer, locks, wpm].iter().enumerate() {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+            let position = Point::new(0, index as i32 * LINE_HEIGHT);
+            Text::with_baseline(line.as_str(), position, style, Baseline::Top).draw(target)?;
+        }
+
+        Ok(())
+    }
+}
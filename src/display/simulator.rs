@@ -0,0 +1,209 @@
+use core::convert::Infallible;
+
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::{DrawTarget, OriginDimensions, Pixel, Size};
+
+use super::{draw_status, Status, StatusDisplay};
+
+/// An in memory display, for previewing and snapshot testing what a display shows.
+pub struct Simulator<const WIDTH: usize, const HEIGHT: usize> {
+    pixels: [[bool; WIDTH]; HEIGHT],
+    // number of times a status was shown
+    shown: usize,
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> Simulator<WIDTH, HEIGHT> {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            pixels: [[false; WIDTH]; HEIGHT],
+            shown: 0,
+        }
+    }
+
+    #[must_use]
+    pub const fn is_on(&self, x: usize, y: usize) -> bool {
+        self.pixels[y][x]
+    }
+
+    /// Returns the number of times a status was shown.
+    #[must_use]
+    pub const fn shown(&self) -> usize {
+        self.shown
+    }
+
+    /// Writes the pixels as lines of `#` for on and `.` for off, for comparing to a snapshot.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the writer.
+    pub fn write_ascii(&self, out: &mut impl core::fmt::Write) -> core::fmt::Result {
+        for row in &self.pixels {
+            for pixel in row {
+                out.write_char(if *pixel { '#' } else { '.' })?;
+            }
+            out.write_char('\n')?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> Default for Simulator<WIDTH, HEIGHT> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> DrawTarget for Simulator<WIDTH, HEIGHT> {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            let (Ok(x), Ok(y)) = (usize::try_from(point.x), usize::try_from(point.y)) else {
+                continue;
+            };
+            if let Some(pixel) = self.pixels.get_mut(y).and_then(|row| row.get_mut(x)) {
+                *pixel = color.is_on();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> OriginDimensions for Simulator<WIDTH, HEIGHT> {
+    #[allow(clippy::cast_possible_truncation)]
+    fn size(&self) -> Size {
+        Size::new(WIDTH as u32, HEIGHT as u32)
+    }
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> StatusDisplay for Simulator<WIDTH, HEIGHT> {
+    fn show(&mut self, status: &Status) {
+        self.pixels = [[false; WIDTH]; HEIGHT];
+        let Ok(()) = draw_status(self, status);
+        self.shown += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Simulator;
+    use crate::display::graphics::LINE_HEIGHT;
+    use crate::display::{Status, StatusDisplay};
+    use crate::indicator::Leds;
+
+    const STATUS: Status = Status {
+        layer: 1,
+        layer_name: "Symbols",
+        leds: Leds {
+            num_lock: false,
+            caps_lock: false,
+            scroll_lock: false,
+            compose: false,
+            kana: false,
+        },
+        wpm: 42,
+    };
+
+    fn snapshot(status: &Status) -> String {
+        let mut display = Simulator::<128, 32>::new();
+        display.show(status);
+        let mut ascii = String::new();
+        display.write_ascii(&mut ascii).unwrap();
+        ascii
+    }
+
+    // returns the rows of the snapshot covered by `line` of text
+    fn line(snapshot: &str, line: usize) -> Vec<&str> {
+        let height = LINE_HEIGHT as usize;
+        snapshot.lines().skip(line * height).take(height).collect()
+    }
+
+    #[test]
+    fn status_is_drawn_on_three_lines() {
+        let plain = snapshot(&STATUS);
+        assert_eq!(plain, snapshot(&STATUS));
+        assert!(line(&plain, 0).iter().any(|row| row.contains('#')));
+        // no locks on
+        assert!(line(&plain, 1).iter().all(|row| !row.contains('#')));
+        assert!(line(&plain, 2).iter().any(|row| row.contains('#')));
+
+        let caps = snapshot(&Status {
+            leds: Leds::from_report(0b10),
+            ..STATUS
+        });
+        assert_eq!(line(&plain, 0), line(&caps, 0));
+        assert!(line(&caps, 1).iter().any(|row| row.contains('#')));
+        assert_eq!(line(&plain, 2), line(&caps, 2));
+
+        let faster = snapshot(&Status { wpm: 43, ..STATUS });
+        assert_eq!(line(&plain, 0), line(&faster, 0));
+        assert!(line(&plain, 2) != line(&faster, 2));
+    }
+
+    // the pixels of `STATUS`, rows and columns past these are off
+    #[rustfmt::skip]
+    const STATUS_PIXELS: &[&str] = &[
+        "..........................................",
+        ".###..............#............##.........",
+        "#...#.............#.............#.........",
+        "#.....#...#.##.#..#.##...###....#....###..",
+        ".###..#...#.#.#.#.##..#.#...#...#...#.....",
+        "....#.#..##.#.#.#.#...#.#...#...#....###..",
+        "#...#..##.#.#.#.#.##..#.#...#...#.......#.",
+        ".###......#.#...#.#.##...###...###..####..",
+        "......#...#...............................",
+        ".......###................................",
+        "..........................................",
+        "..........................................",
+        "..........................................",
+        "..........................................",
+        "..........................................",
+        "..........................................",
+        "..........................................",
+        "..........................................",
+        "..........................................",
+        "..........................................",
+        "..........................................",
+        "#...#.####..#...#..........#...###........",
+        "#...#.#...#.#...#.........##..#...#.......",
+        "#...#.#...#.##.##........#.#......#.......",
+        "#.#.#.####..#.#.#.......#..#....##........",
+        "#.#.#.#.....#...#.......#####..#..........",
+        "##.##.#.....#...#..........#..#...........",
+        "#...#.#.....#...#..........#..#####.......",
+    ];
+
+    #[test]
+    fn status_matches_its_pixels() {
+        let expected: String = (0..32)
+            .map(|row| {
+                let pixels = STATUS_PIXELS.get(row).copied().unwrap_or_default();
+                format!("{pixels:.<128}\n")
+            })
+            .collect();
+
+        assert_eq!(snapshot(&STATUS), expected);
+    }
+
+    #[test]
+    fn long_names_are_cut() {
+        let long = snapshot(&Status {
+            layer_name: "A layer with a very long name",
+            ..STATUS
+        });
+        let cut = snapshot(&Status {
+            layer_name: "A layer with a very l",
+            ..STATUS
+        });
+
+        assert!(line(&long, 0).iter().any(|row| row.contains('#')));
+        assert_eq!(line(&long, 0), line(&cut, 0));
+    }
+}
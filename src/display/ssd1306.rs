@@ -0,0 +1,252 @@
+use core::convert::Infallible;
+
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::{DrawTarget, OriginDimensions, Pixel, Size};
+use embedded_hal::blocking::i2c::Write;
+
+use super::{draw_status, DisplayError, Status, StatusDisplay};
+
+const ADDRESS: u8 = 0x3C;
+const WIDTH: usize = 128;
+// the most rows a display can have
+const MAX_HEIGHT: usize = 64;
+// control bytes starting a write
+const COMMAND: u8 = 0x00;
+const DATA: u8 = 0x40;
+// display bytes sent per I2C write
+const CHUNK: usize = 16;
+
+/// Rows of an SSD1306 display.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Height {
+    H32 = 32,
+    H64 = 64,
+}
+
+/// A 128 pixel wide SSD1306 OLED on I2C, drawn to a buffer sent a chunk at a time by
+/// [`flush`](Self::flush).
+pub struct Ssd1306<I2C: Write> {
+    i2c: I2C,
+    height: Height,
+    // one bit per pixel, each byte a column of 8 rows of a page
+    buffer: [u8; WIDTH * MAX_HEIGHT / 8],
+    // the next byte of the buffer to send, `None` when the display shows the whole buffer
+    next: Option<usize>,
+}
+
+impl<I2C: Write> Ssd1306<I2C> {
+    pub const fn new(i2c: I2C, height: Height) -> Self {
+        Self {
+            i2c,
+            height,
+            buffer: [0; WIDTH * MAX_HEIGHT / 8],
+            next: None,
+        }
+    }
+
+    const fn pages(&self) -> usize {
+        self.height as usize / 8
+    }
+
+    /// Sets up and turns on the display.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the I2C bus.
+    pub fn init(&mut self) -> Result<(), I2C::Error> {
+        #[allow(clippy::cast_possible_truncation)]
+        let (multiplex, com_pins) = match self.height {
+            Height::H32 => (31, 0x02),
+            Height::H64 => (63, 0x12),
+        };
+
+        #[rustfmt::skip]
+        let commands = [
+            COMMAND,
+            0xAE, // display off
+            0xD5, 0x80, // clock divide
+            0xA8, multiplex,
+            0xD3, 0x00, // no display offset
+            0x40, // start line 0
+            0x8D, 0x14, // charge pump on
+            0x20, 0x00, // horizontal addressing
+            0xA1, // columns right to left
+            0xC8, // rows bottom to top
+            0xDA, com_pins,
+            0x81, 0x8F, // contrast
+            0xD9, 0xF1, // precharge
+            0xDB, 0x40, // vcomh deselect level
+            0xA4, // show the buffer
+            0xA6, // not inverted
+            0xAF, // display on
+        ];
+        self.i2c.write(ADDRESS, &commands)
+    }
+
+    /// Sends the next chunk of the buffer drawn since the last flush.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`nb::Error::WouldBlock`] until the whole buffer is sent, or the error of the I2C
+    /// bus, after which nothing is sent until the buffer is drawn again.
+    pub fn flush(&mut self) -> nb::Result<(), I2C::Error> {
+        let Some(next) = self.next else {
+            return Ok(());
+        };
+
+        match self.send_chunk(next) {
+            Ok(end) if end == WIDTH * self.pages() => {
+                self.next = None;
+                Ok(())
+            }
+            Ok(end) => {
+                self.next = Some(end);
+                Err(nb::Error::WouldBlock)
+            }
+            Err(error) => {
+                self.next = None;
+                Err(nb::Error::Other(error))
+            }
+        }
+    }
+
+    // sends the chunk of the buffer starting at `start`, returning where the next one starts
+    #[allow(clippy::cast_possible_truncation)]
+    fn send_chunk(&mut self, start: usize) -> Result<usize, I2C::Error> {
+        let pages = self.pages();
+        if start == 0 {
+            let columns = [COMMAND, 0x21, 0, WIDTH as u8 - 1];
+            let rows = [COMMAND, 0x22, 0, pages as u8 - 1];
+            self.i2c.write(ADDRESS, &columns)?;
+            self.i2c.write(ADDRESS, &rows)?;
+        }
+
+        let end = (start + CHUNK).min(WIDTH * pages);
+        let bytes = &self.buffer[start..end];
+        let mut chunk = [DATA; CHUNK + 1];
+        chunk[1..=bytes.len()].copy_from_slice(bytes);
+        self.i2c.write(ADDRESS, &chunk[..=bytes.len()])?;
+
+        Ok(end)
+    }
+}
+
+impl<I2C: Write> DrawTarget for Ssd1306<I2C> {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let height = self.height as usize;
+        self.next = Some(0);
+
+        for Pixel(point, color) in pixels {
+            let (Ok(x), Ok(y)) = (usize::try_from(point.x), usize::try_from(point.y)) else {
+                continue;
+            };
+            if x >= WIDTH || y >= height {
+                continue;
+            }
+
+            let byte = &mut self.buffer[x + y / 8 * WIDTH];
+            if color.is_on() {
+                *byte |= 1 << (y % 8);
+            } else {
+                *byte &= !(1 << (y % 8));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<I2C: Write> OriginDimensions for Ssd1306<I2C> {
+    fn size(&self) -> Size {
+        Size::new(WIDTH as u32, self.height as u32)
+    }
+}
+
+impl<I2C: Write> StatusDisplay for Ssd1306<I2C> {
+    fn show(&mut self, status: &Status) {
+        self.buffer.fill(0);
+        let Ok(()) = draw_status(self, status);
+    }
+
+    fn flush(&mut self) -> nb::Result<(), DisplayError> {
+        Self::flush(self).map_err(|error| error.map(|_| DisplayError))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use embedded_hal::blocking::i2c::Write;
+
+    use super::{Height, Ssd1306};
+    use crate::display::{Status, StatusDisplay};
+    use crate::indicator::Leds;
+
+    // counts the writes, failing the ones listed
+    #[derive(Default)]
+    struct MockI2c {
+        writes: usize,
+        fail: Vec<usize>,
+    }
+
+    impl Write for MockI2c {
+        type Error = ();
+
+        fn write(&mut self, _address: u8, _bytes: &[u8]) -> Result<(), ()> {
+            self.writes += 1;
+            if self.fail.contains(&self.writes) {
+                return Err(());
+            }
+            Ok(())
+        }
+    }
+
+    const STATUS: Status = Status {
+        layer: 0,
+        layer_name: "",
+        leds: Leds::from_report(0),
+        wpm: 0,
+    };
+
+    // flushes until the whole buffer is sent or a write fails, returning the number of calls
+    fn flush_all(display: &mut Ssd1306<MockI2c>) -> usize {
+        let mut calls = 1;
+        while StatusDisplay::flush(display) == Err(nb::Error::WouldBlock) {
+            calls += 1;
+        }
+        calls
+    }
+
+    #[test]
+    fn buffer_is_sent_a_chunk_at_a_time() {
+        let mut display = Ssd1306::new(MockI2c::default(), Height::H32);
+        assert!(Ssd1306::flush(&mut display).is_ok());
+        assert_eq!(display.i2c.writes, 0);
+
+        display.show(&STATUS);
+        assert_eq!(display.i2c.writes, 0);
+        // 512 bytes in chunks of 16, after setting the address
+        assert_eq!(flush_all(&mut display), 32);
+        assert_eq!(display.i2c.writes, 34);
+    }
+
+    #[test]
+    fn failed_write_gives_up_until_the_next_show() {
+        let mut display = Ssd1306::new(MockI2c::default(), Height::H32);
+        display.i2c.fail = vec![10];
+
+        display.show(&STATUS);
+        assert_eq!(flush_all(&mut display), 8);
+        assert_eq!(StatusDisplay::flush(&mut display), Ok(()));
+        assert_eq!(display.i2c.writes, 10);
+
+        display.show(&STATUS);
+        assert_eq!(flush_all(&mut display), 32);
+        assert_eq!(display.i2c.writes, 10 + 34);
+    }
+}
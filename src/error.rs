@@ -0,0 +1,21 @@
+use core::convert::Infallible;
+
+use either::Either;
+use usbd_human_interface_device::UsbHidError;
+
+/// An error returned from [`Keyboard::periodic`](crate::Keyboard::periodic).
+///
+/// Generic over the error types of the matrix output pins, matrix input pins and encoder pins.
+#[derive(Debug)]
+pub enum KeyboardError<OutputError, InputError, EncoderError = Infallible> {
+    /// Driving a matrix output pin failed.
+    Output(OutputError),
+    /// Reading a matrix input pin failed, the key keeps its last state.
+    Input(InputError),
+    /// Reading pin A or pin B of an encoder failed.
+    Encoder(Either<EncoderError, EncoderError>),
+    /// Reading the pin of an encoder's push switch failed.
+    EncoderSwitch(EncoderError),
+    /// Polling the USB device or writing a report failed.
+    Usb(UsbHidError),
+}
@@ -1,3 +1,6 @@
 pub mod encoder;
+#[cfg(feature = "rp2040")]
+pub mod interrupt;
 
-pub use encoder::Encoder;
+pub use encoder::{Encoder, Switch};
+pub use hardware::encoder::{Acceleration, Resolution, StepCounter};
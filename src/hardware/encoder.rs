@@ -1,30 +1,289 @@
+use either::Either;
 use embedded_hal::digital::v2::InputPin;
-use hardware::encoder::{self, Dir};
+use hardware::encoder::{self, Dir, Resolution, StepCounter};
 
+use crate::error::KeyboardError;
 use crate::keycode::Keycode;
 
+/// Where the push switch of an [`Encoder`] is read from.
+pub enum Switch<Input: InputPin> {
+    /// The encoder has no switch.
+    None,
+    /// A pin pulled up and shorted to ground while the switch is pressed.
+    Pin(Input),
+    /// A position of the key matrix, its keycode in the layout should be `KC_NO`.
+    Matrix { row: usize, col: usize },
+}
+
+// a pin of an encoder that failed to read
+#[derive(Debug)]
+pub(crate) enum ReadError<Error> {
+    // pin A or pin B
+    Pins(Either<Error, Error>),
+    Switch(Error),
+}
+
+impl<OutputError, InputError, Error> From<ReadError<Error>>
+    for KeyboardError<OutputError, InputError, Error>
+{
+    fn from(error: ReadError<Error>) -> Self {
+        match error {
+            ReadError::Pins(error) => Self::Encoder(error),
+            ReadError::Switch(error) => Self::EncoderSwitch(error),
+        }
+    }
+}
+
+// where the steps of an encoder come from
+enum Source<Input: InputPin> {
+    // read every update
+    Polled(encoder::Encoder<Input, Input>),
+    // read by an interrupt handler, `last` is the total at the previous update
+    Interrupt {
+        counter: &'static StepCounter,
+        last: i32,
+    },
+}
+
 pub struct Encoder<Input: InputPin> {
-    encoder: encoder::Encoder<Input, Input>,
+    source: Source<Input>,
     pub(super) actions: &'static [[Keycode; 2]],
+    switch: Switch<Input>,
+    // actions per layer used while the switch is held
+    held_actions: &'static [[Keycode; 2]],
+    // actions per layer when the switch is pressed and released without rotating
+    click_actions: &'static [Keycode],
+    // steps taken by the last update, positive clockwise
+    steps: i32,
+    held: bool,
+    // rotated since the switch was pressed
+    rotated: bool,
+    // released without rotating during the last update
+    clicked: bool,
 }
 
 impl<Input: InputPin> Encoder<Input> {
     pub const fn new(pin_a: Input, pin_b: Input, actions: &'static [[Keycode; 2]]) -> Self {
+        Self::from_encoder(
+            encoder::Encoder::new(pin_a, pin_b, Resolution::Four),
+            actions,
+        )
+    }
+
+    /// Uses an encoder set up with its own resolution, inverted pins or acceleration.
+    pub const fn from_encoder(
+        encoder: encoder::Encoder<Input, Input>,
+        actions: &'static [[Keycode; 2]],
+    ) -> Self {
+        Self::from_source(Source::Polled(encoder), actions)
+    }
+
+    /// Uses the steps an interrupt handler adds to `counter` instead of polling pins.
+    pub const fn from_interrupt(
+        counter: &'static StepCounter,
+        actions: &'static [[Keycode; 2]],
+    ) -> Self {
+        Self::from_source(Source::Interrupt { counter, last: 0 }, actions)
+    }
+
+    const fn from_source(source: Source<Input>, actions: &'static [[Keycode; 2]]) -> Self {
         Self {
-            encoder: encoder::Encoder::new(pin_a, pin_b),
+            source,
             actions,
+            switch: Switch::None,
+            held_actions: &[],
+            click_actions: &[],
+            steps: 0,
+            held: false,
+            rotated: false,
+            clicked: false,
+        }
+    }
+
+    /// Adds a push switch, with `held_actions` used instead of the actions while it is held
+    /// and `click_actions` sent when it is pressed and released without rotating.
+    ///
+    /// Both are indexed by layer like the actions, layers without an entry fall back to the
+    /// actions when rotating and do nothing when clicked. A [`Switch::Matrix`] position outside
+    /// the matrix panics when the keyboard is made.
+    #[must_use]
+    pub fn with_switch(
+        mut self,
+        switch: Switch<Input>,
+        held_actions: &'static [[Keycode; 2]],
+        click_actions: &'static [Keycode],
+    ) -> Self {
+        self.switch = switch;
+        self.held_actions = held_actions;
+        self.click_actions = click_actions;
+        self
+    }
+
+    // the matrix position of the switch, if it's wired into the matrix
+    pub(crate) const fn matrix_position(&self) -> Option<(usize, usize)> {
+        match self.switch {
+            Switch::Matrix { row, col } => Some((row, col)),
+            Switch::None | Switch::Pin(_) => None,
+        }
+    }
+
+    pub(crate) const fn steps(&self) -> i32 {
+        self.steps
+    }
+
+    // returns the action for turning in `dir` on `layer`
+    pub(crate) fn turn_action(&self, layer: usize, dir: Dir) -> Keycode {
+        let actions = if self.held {
+            self.held_actions.get(layer).or_else(|| self.actions.get(layer))
+        } else {
+            self.actions.get(layer)
+        };
+
+        match (actions, dir) {
+            (Some(actions), Dir::Cw) => actions[1],
+            (Some(actions), Dir::Cww) => actions[0],
+            _ => Keycode::KC_NO,
         }
     }
 
-    pub(crate) const fn action(&self, layer: usize) -> Keycode {
-        match self.encoder.direction() {
-            Dir::Cw => self.actions[layer][1],
-            Dir::Cww => self.actions[layer][0],
-            Dir::Same => Keycode::KC_NO,
+    // returns the click action on `layer` if the switch was just clicked
+    pub(crate) fn click_action(&self, layer: usize) -> Keycode {
+        if self.clicked {
+            self.click_actions
+                .get(layer)
+                .copied()
+                .unwrap_or(Keycode::KC_NO)
+        } else {
+            Keycode::KC_NO
         }
     }
 
-    pub(crate) fn update(&mut self) {
-        let _ = self.encoder.update();
+    // reads the encoder and its switch at `now` ms, `is_pressed` returns the state of a matrix
+    // position
+    pub(crate) fn update(
+        &mut self,
+        now: u32,
+        is_pressed: impl Fn(usize, usize) -> bool,
+    ) -> Result<(), ReadError<Input::Error>> {
+        self.steps = match &mut self.source {
+            Source::Polled(encoder) => {
+                encoder.update_at(now).map_err(ReadError::Pins)?;
+                encoder.take_steps()
+            }
+            Source::Interrupt { counter, last } => {
+                let total = counter.total();
+                let steps = total.wrapping_sub(*last);
+                *last = total;
+                steps
+            }
+        };
+
+        let held = match &self.switch {
+            Switch::None => false,
+            Switch::Pin(pin) => pin.is_low().map_err(ReadError::Switch)?,
+            Switch::Matrix { row, col } => is_pressed(*row, *col),
+        };
+
+        self.clicked = self.held && !held && !self.rotated;
+
+        if held && !self.held {
+            self.rotated = false;
+        }
+        if held && self.steps != 0 {
+            self.rotated = true;
+        }
+        self.held = held;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::cell::Cell;
+
+    use hardware::encoder::StepCounter;
+
+    use super::{Dir, Encoder, ReadError, Switch};
+    use crate::keycode::Keycode::*;
+    use crate::testing::{MockMatrix, MockPin};
+
+    // turns the encoder one detent clockwise, returning the action of every update
+    fn turn_cw(
+        encoder: &mut Encoder<MockPin<'_>>,
+        a: &Cell<bool>,
+        b: &Cell<bool>,
+    ) -> Vec<crate::keycode::Keycode> {
+        [(true, false), (true, true), (false, true), (false, false)]
+            .into_iter()
+            .map(|(level_a, level_b)| {
+                a.set(level_a);
+                b.set(level_b);
+                encoder.update(0, |_, _| false).unwrap();
+                encoder.turn_action(0, if encoder.steps() > 0 { Dir::Cw } else { Dir::Same })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn switch_selects_held_actions_and_click() {
+        let (a, b, switch) = (Cell::new(false), Cell::new(false), Cell::new(true));
+        let mut encoder = Encoder::new(MockPin::new(&a), MockPin::new(&b), &[[KC_A, KC_B]])
+            .with_switch(Switch::Pin(MockPin::new(&switch)), &[[KC_C, KC_D]], &[KC_E]);
+
+        assert!(turn_cw(&mut encoder, &a, &b).last().unwrap() == &KC_B);
+
+        // pressed pulls the switch low
+        switch.set(false);
+        assert!(turn_cw(&mut encoder, &a, &b).last().unwrap() == &KC_D);
+
+        // released after rotating isn't a click
+        switch.set(true);
+        encoder.update(0, |_, _| false).unwrap();
+        assert!(encoder.click_action(0) == KC_NO);
+
+        switch.set(false);
+        encoder.update(0, |_, _| false).unwrap();
+        switch.set(true);
+        encoder.update(0, |_, _| false).unwrap();
+        assert!(encoder.click_action(0) == KC_E);
+
+        encoder.update(0, |_, _| false).unwrap();
+        assert!(encoder.click_action(0) == KC_NO);
+    }
+
+    #[test]
+    fn interrupt_steps_are_read_once() {
+        static COUNTER: StepCounter = StepCounter::new();
+        let mut encoder = Encoder::<MockPin<'_>>::from_interrupt(&COUNTER, &[[KC_A, KC_B]]);
+
+        COUNTER.add(2);
+        encoder.update(0, |_, _| false).unwrap();
+        assert_eq!(encoder.steps(), 2);
+
+        encoder.update(0, |_, _| false).unwrap();
+        assert_eq!(encoder.steps(), 0);
+
+        COUNTER.add(-1);
+        encoder.update(0, |_, _| false).unwrap();
+        assert_eq!(encoder.steps(), -1);
+    }
+
+    #[test]
+    fn switch_errors_are_not_pin_errors() {
+        static COUNTER: StepCounter = StepCounter::new();
+        let matrix = MockMatrix::<1, 1>::new();
+        let [switch] = matrix.input_pins();
+        let mut encoder = Encoder::from_interrupt(&COUNTER, &[[KC_A, KC_B]]).with_switch(
+            Switch::Pin(switch),
+            &[],
+            &[],
+        );
+
+        matrix.fail_inputs(true);
+        assert!(matches!(
+            encoder.update(0, |_, _| false),
+            Err(ReadError::Switch(_))
+        ));
     }
 }
@@ -0,0 +1,96 @@
+//! Encoders read on RP2040 GPIO edge interrupts.
+//!
+//! Polling an encoder once per [`Keyboard::periodic`](crate::Keyboard::periodic) misses
+//! transitions while the loop is busy, reading it on every edge of its pins doesn't.
+//!
+//! ```ignore
+//! static COUNTER: StepCounter = StepCounter::new();
+//! static ENCODER: Mutex<RefCell<Option<InterruptEncoder<PullUp>>>> =
+//!     Mutex::new(RefCell::new(None));
+//!
+//! // in main
+//! let encoder = InterruptEncoder::new(
+//!     pins.gpio9.into_pull_up_input().into_dyn_pin(),
+//!     pins.gpio8.into_pull_up_input().into_dyn_pin(),
+//!     Resolution::Four,
+//!     &COUNTER,
+//! );
+//! cortex_m::interrupt::free(|cs| ENCODER.borrow(cs).replace(Some(encoder)));
+//! unsafe { enable_interrupt() };
+//! let encoder = Encoder::from_interrupt(&COUNTER, &[[KC_VOLDOWN, KC_VOLUP]]);
+//!
+//! #[interrupt]
+//! fn IO_IRQ_BANK0() {
+//!     // now_ms returns the ms since boot, from the timer also used for `Keyboard::periodic`
+//!     cortex_m::interrupt::free(|cs| {
+//!         if let Some(encoder) = ENCODER.borrow(cs).borrow_mut().as_mut() {
+//!             encoder.on_interrupt(now_ms());
+//!         }
+//!     });
+//! }
+//! ```
+
+use cortex_m::peripheral::NVIC;
+use hardware::encoder::{self, Acceleration, Resolution, StepCounter};
+use rp2040_hal::gpio::{DynPinId, FunctionSioInput, Interrupt, Pin, PullType};
+use rp2040_hal::pac;
+
+type EncoderPin<Pull> = Pin<DynPinId, FunctionSioInput, Pull>;
+
+/// An encoder read from the `IO_IRQ_BANK0` interrupt handler, adding its steps to a
+/// [`StepCounter`] read by an [`Encoder`](super::Encoder) made with
+/// [`from_interrupt`](super::Encoder::from_interrupt).
+pub struct InterruptEncoder<Pull: PullType> {
+    encoder: encoder::Encoder<EncoderPin<Pull>, EncoderPin<Pull>>,
+    counter: &'static StepCounter,
+}
+
+impl<Pull: PullType> InterruptEncoder<Pull> {
+    /// Enables the edge interrupts of both pins.
+    pub fn new(
+        pin_a: EncoderPin<Pull>,
+        pin_b: EncoderPin<Pull>,
+        resolution: Resolution,
+        counter: &'static StepCounter,
+    ) -> Self {
+        for pin in [&pin_a, &pin_b] {
+            pin.set_interrupt_enabled(Interrupt::EdgeHigh, true);
+            pin.set_interrupt_enabled(Interrupt::EdgeLow, true);
+        }
+
+        Self {
+            encoder: encoder::Encoder::new(pin_a, pin_b, resolution),
+            counter,
+        }
+    }
+
+    /// Accelerates fast spins, using the times passed to [`on_interrupt`](Self::on_interrupt).
+    #[must_use]
+    pub fn accelerate(mut self, acceleration: Acceleration) -> Self {
+        self.encoder = self.encoder.accelerate(acceleration);
+        self
+    }
+
+    /// Reads the encoder at `now` ms, call this from the `IO_IRQ_BANK0` interrupt handler.
+    pub fn on_interrupt(&mut self, now: u32) {
+        let (pin_a, pin_b) = self.encoder.pins_mut();
+        for interrupt in [Interrupt::EdgeHigh, Interrupt::EdgeLow] {
+            pin_a.clear_interrupt(interrupt);
+            pin_b.clear_interrupt(interrupt);
+        }
+
+        // reading rp2040 pins can't fail
+        if self.encoder.update_at(now).is_ok() {
+            self.counter.add(self.encoder.take_steps());
+        }
+    }
+}
+
+/// Unmasks the `IO_IRQ_BANK0` interrupt.
+///
+/// # Safety
+///
+/// The interrupt handler must be ready to run, see [`NVIC::unmask`].
+pub unsafe fn enable_interrupt() {
+    NVIC::unmask(pac::Interrupt::IO_IRQ_BANK0);
+}
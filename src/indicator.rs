@@ -0,0 +1,188 @@
+//! Lock LEDs set by the host.
+//!
+//! The host sends the state of its Num, Caps, Scroll Lock, Compose and Kana LEDs to the
+//! keyboard, read with [`Keyboard::leds`](crate::Keyboard::leds) and shown with an
+//! [`Indicator`].
+//!
+//! ```ignore
+//! let mut caps_lock = PinIndicator::new(Led::CapsLock, pins.gpio25.into_push_pull_output());
+//!
+//! loop {
+//!     keyboard.periodic().ok();
+//!     caps_lock.show(keyboard.leds()).ok();
+//! }
+//! ```
+
+use embedded_hal::digital::v2::OutputPin;
+use embedded_hal::PwmPin;
+
+/// One of the LEDs the host sets.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Led {
+    NumLock,
+    CapsLock,
+    ScrollLock,
+    Compose,
+    Kana,
+}
+
+/// The state of every LED the host sets.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Leds {
+    pub num_lock: bool,
+    pub caps_lock: bool,
+    pub scroll_lock: bool,
+    pub compose: bool,
+    pub kana: bool,
+}
+
+impl Leds {
+    /// Returns the LEDs of a keyboard output report, one bit per LED from Num Lock up.
+    #[must_use]
+    pub const fn from_report(report: u8) -> Self {
+        Self {
+            num_lock: report & 1 != 0,
+            caps_lock: report & 1 << 1 != 0,
+            scroll_lock: report & 1 << 2 != 0,
+            compose: report & 1 << 3 != 0,
+            kana: report & 1 << 4 != 0,
+        }
+    }
+
+    #[must_use]
+    pub const fn is_on(&self, led: Led) -> bool {
+        match led {
+            Led::NumLock => self.num_lock,
+            Led::CapsLock => self.caps_lock,
+            Led::ScrollLock => self.scroll_lock,
+            Led::Compose => self.compose,
+            Led::Kana => self.kana,
+        }
+    }
+}
+
+/// Shows the state of the host LEDs.
+pub trait Indicator {
+    type Error;
+
+    /// Shows `leds`, call this every loop or whenever they change.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the pins driving the indicator.
+    fn show(&mut self, leds: Leds) -> Result<(), Self::Error>;
+}
+
+/// An LED driven by an output pin, high while it is on unless it is active low.
+pub struct PinIndicator<Pin: OutputPin> {
+    led: Led,
+    pin: Pin,
+    active_low: bool,
+}
+
+impl<Pin: OutputPin> PinIndicator<Pin> {
+    pub const fn new(led: Led, pin: Pin) -> Self {
+        Self {
+            led,
+            pin,
+            active_low: false,
+        }
+    }
+
+    /// Drives the pin low while the LED is on, for LEDs wired to the supply.
+    #[must_use]
+    pub const fn active_low(mut self) -> Self {
+        self.active_low = true;
+        self
+    }
+}
+
+impl<Pin: OutputPin> Indicator for PinIndicator<Pin> {
+    type Error = Pin::Error;
+
+    fn show(&mut self, leds: Leds) -> Result<(), Self::Error> {
+        if leds.is_on(self.led) == self.active_low {
+            self.pin.set_low()
+        } else {
+            self.pin.set_high()
+        }
+    }
+}
+
+/// An LED driven by a PWM channel, at `duty` while it is on and off otherwise.
+pub struct PwmIndicator<Pwm: PwmPin> {
+    led: Led,
+    pwm: Pwm,
+    duty: Pwm::Duty,
+}
+
+impl<Pwm: PwmPin> PwmIndicator<Pwm> {
+    /// Enables the channel.
+    pub fn new(led: Led, mut pwm: Pwm, duty: Pwm::Duty) -> Self {
+        pwm.enable();
+
+        Self { led, pwm, duty }
+    }
+}
+
+impl<Pwm: PwmPin> Indicator for PwmIndicator<Pwm>
+where
+    Pwm::Duty: Copy + Default,
+{
+    type Error = core::convert::Infallible;
+
+    fn show(&mut self, leds: Leds) -> Result<(), Self::Error> {
+        self.pwm.set_duty(if leds.is_on(self.led) {
+            self.duty
+        } else {
+            Pwm::Duty::default()
+        });
+
+        Ok(())
+    }
+}
+
+impl<I: Indicator, const N: usize> Indicator for [I; N] {
+    type Error = I::Error;
+
+    fn show(&mut self, leds: Leds) -> Result<(), Self::Error> {
+        self.iter_mut()
+            .map(|indicator| indicator.show(leds))
+            .fold(Ok(()), Result::and)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::cell::Cell;
+
+    use super::{Indicator, Led, Leds, PinIndicator};
+    use crate::testing::MockPin;
+
+    #[test]
+    fn report_bits_set_leds() {
+        let leds = Leds::from_report(0b0_0110);
+        assert!(!leds.is_on(Led::NumLock));
+        assert!(leds.is_on(Led::CapsLock));
+        assert!(leds.is_on(Led::ScrollLock));
+        assert!(!leds.is_on(Led::Kana));
+    }
+
+    #[test]
+    fn pin_follows_its_led() {
+        let (level, active_low) = (Cell::new(false), Cell::new(false));
+        let mut indicators = [
+            PinIndicator::new(Led::CapsLock, MockPin::new(&level)),
+            PinIndicator::new(Led::CapsLock, MockPin::new(&active_low)).active_low(),
+        ];
+
+        indicators.show(Leds::from_report(0b10)).unwrap();
+        assert!(level.get());
+        assert!(!active_low.get());
+
+        indicators.show(Leds::default()).unwrap();
+        assert!(!level.get());
+        assert!(active_low.get());
+    }
+}
@@ -1,22 +1,51 @@
+use core::convert::Infallible;
+
 use embedded_hal::digital::v2::{InputPin, OutputPin};
-use embedded_hal::timer::CountDown;
+use embedded_hal::serial::{Read, Write};
 use usb_device::class_prelude::{UsbBus as UsbBusTrait, UsbBusAllocator};
 
+use crate::clock::Clock;
+use crate::config::Config;
+use crate::debounce::Debouncer;
+use crate::display::StatusDisplay;
+use crate::error::KeyboardError;
+use crate::indicator::Leds;
 #[cfg(feature = "encoders")]
 use crate::hardware::encoder::Encoder;
 use crate::keycode::Keycode;
+use crate::rgb::LedDriver;
+use crate::split::Primary;
 
+mod base;
+mod combo;
 #[cfg(feature = "encoders")]
 mod encoder_controller;
-mod matrix;
+mod hid;
+mod key_override;
+mod leader;
+mod macro_player;
+pub mod matrix;
+mod mouse;
+mod screen;
+mod secondary;
 mod state;
+mod tap_hold;
+mod underglow;
 mod usb;
+mod wpm;
 
+use base::Base;
 #[cfg(feature = "encoders")]
 use encoder_controller::EncoderController;
-use matrix::Matrix;
+use matrix::{Matrix, Scanner};
+pub use secondary::Secondary;
 use state::State;
-use usb::Usb;
+
+// the result of updating a keyboard with this scanner
+type PeriodicResult<Scan, EncoderError = Infallible> = Result<
+    (),
+    KeyboardError<<Scan as Scanner>::OutputError, <Scan as Scanner>::InputError, EncoderError>,
+>;
 
 #[cfg(feature = "encoders")]
 pub struct Keyboard<
@@ -25,16 +54,12 @@ pub struct Keyboard<
     const NUM_OF_ROWS: usize,
     const NUM_OF_ENCODERS: usize,
     EncoderPin: InputPin,
-    Output: OutputPin,
-    Input: InputPin,
-    Timer: CountDown,
+    Scan: Scanner,
     UsbBus: UsbBusTrait,
 > where
     [(); NUM_OF_COLS * NUM_OF_ROWS + NUM_OF_ENCODERS]: Sized,
 {
-    state: State<'a, NUM_OF_COLS, NUM_OF_ROWS>,
-    matrix: Matrix<'a, NUM_OF_COLS, NUM_OF_ROWS, Output, Input, Timer>,
-    usb: Usb<'a, Timer, UsbBus>,
+    base: Base<'a, NUM_OF_COLS, NUM_OF_ROWS, Scan, UsbBus>,
     encoder_controller: EncoderController<NUM_OF_ENCODERS, EncoderPin>,
     buffer: [Keycode; NUM_OF_COLS * NUM_OF_ROWS + NUM_OF_ENCODERS],
 }
@@ -48,7 +73,6 @@ impl<
         EncoderPin: InputPin,
         Output: OutputPin,
         Input: InputPin,
-        Timer: CountDown,
         UsbBus: UsbBusTrait,
     >
     Keyboard<
@@ -57,29 +81,64 @@ impl<
         NUM_OF_ROWS,
         NUM_OF_ENCODERS,
         EncoderPin,
-        Output,
-        Input,
-        Timer,
+        Matrix<'a, NUM_OF_COLS, NUM_OF_ROWS, Output, Input>,
         UsbBus,
     >
 where
     [(); NUM_OF_COLS * NUM_OF_ROWS + NUM_OF_ENCODERS]: Sized,
 {
     pub fn new(
-        layout: &'a [&[&[Keycode]]],
+        layout: &'a [[[Keycode; NUM_OF_COLS]; NUM_OF_ROWS]],
         output_pins: &'a mut [Output],
         input_pins: &'a mut [Input],
         encoders: [Encoder<EncoderPin>; NUM_OF_ENCODERS],
-        timer0: &'a mut Timer,
-        timer1: &'a mut Timer,
-        timer2: &'a mut Timer,
+        config: Config,
+        clock: &'a dyn Clock,
         usb_bus: &'a UsbBusAllocator<UsbBus>,
     ) -> Self {
+        let debouncer = Debouncer::new(config.debounce.algorithm, config.debounce.time);
+        let matrix = Matrix::new(output_pins, input_pins, clock, debouncer, config.matrix);
+
+        Self::from_scanner(layout, matrix, encoders, config, usb_bus)
+    }
+}
+
+#[cfg(feature = "encoders")]
+impl<
+        'a,
+        const NUM_OF_COLS: usize,
+        const NUM_OF_ROWS: usize,
+        const NUM_OF_ENCODERS: usize,
+        EncoderPin: InputPin,
+        Scan: Scanner,
+        UsbBus: UsbBusTrait,
+    > Keyboard<'a, NUM_OF_COLS, NUM_OF_ROWS, NUM_OF_ENCODERS, EncoderPin, Scan, UsbBus>
+where
+    [(); NUM_OF_COLS * NUM_OF_ROWS + NUM_OF_ENCODERS]: Sized,
+{
+    /// Makes a keyboard scanning its keys with `scanner`, like a
+    /// [`DuplexMatrix`](matrix::DuplexMatrix).
+    ///
+    /// # Panics
+    ///
+    /// Panics if an encoder switch is wired to a position outside the matrix.
+    pub fn from_scanner(
+        layout: &'a [[[Keycode; NUM_OF_COLS]; NUM_OF_ROWS]],
+        scanner: Scan,
+        encoders: [Encoder<EncoderPin>; NUM_OF_ENCODERS],
+        config: Config,
+        usb_bus: &'a UsbBusAllocator<UsbBus>,
+    ) -> Self {
+        for (row, col) in encoders.iter().filter_map(Encoder::matrix_position) {
+            assert!(
+                row < NUM_OF_ROWS && col < NUM_OF_COLS,
+                "an encoder switch is wired to a position outside the matrix"
+            );
+        }
+
         Self {
-            state: State::new(layout),
-            matrix: Matrix::new(output_pins, input_pins, timer0, timer2),
-            usb: Usb::new(usb_bus, timer1),
-            encoder_controller: EncoderController::new(encoders),
+            base: Base::new(layout, scanner, &config, usb_bus),
+            encoder_controller: EncoderController::new(encoders, &config.encoders),
             buffer: [Keycode::KC_NO; NUM_OF_COLS * NUM_OF_ROWS + NUM_OF_ENCODERS],
         }
     }
@@ -89,34 +148,84 @@ where
     //     self.matrix = Some(Matrix::new(output_pins, input_pins, &timers[0]));
     // }
 
-    // update the keyboard
-    pub fn periodic(&mut self) where Timer::Time: From<fugit::Duration<u32, 1, 1000000>> {
-        if self.matrix.scan(&mut self.state) {
-            let flatten_state = self.matrix.state.iter().flatten();
-            let mut index = 0;
+    /// Updates the keyboard.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error of the update unless the recovery policy is
+    /// [`Recovery::Ignore`](crate::config::Recovery::Ignore), the rest of the update still runs.
+    pub fn periodic(&mut self) -> PeriodicResult<Scan, EncoderPin::Error> {
+        let (has_changed, scanned) = self.base.scan(&mut self.buffer);
 
-            for keycode in flatten_state {
-                self.buffer[index] = *keycode;
-                index += 1;
-            }
-
-            self.encoder_controller
-                .encoders_state
-                .iter()
-                .for_each(|keycode| {
-                    self.buffer[index] = *keycode;
-                    index += 1;
-                });
+        let matrix = &self.base.matrix;
+        let read = self
+            .encoder_controller
+            .periodic(self.base.state.layer(), matrix.now(), |row, col| {
+                matrix.is_pressed(row, col)
+            })
+            .map_err(KeyboardError::from);
 
-            self.usb.write_keyboard_report(&self.buffer);
-            self.usb.write_consumer_report(&self.buffer);
+        // the encoders come after the matrix in the buffer
+        let taps = self.encoder_controller.keys();
+        for (keycode, tap) in self.buffer[NUM_OF_COLS * NUM_OF_ROWS..].iter_mut().zip(taps) {
+            *keycode = tap;
         }
 
-        self.encoder_controller.periodic();
-        self.encoder_controller.encoders_state =
-            self.encoder_controller.actions(self.state.layer());
+        let send = has_changed || self.encoder_controller.has_pending();
+        let encoder_controller = &mut self.encoder_controller;
+        let updated = self
+            .base
+            .update(&self.buffer, send, |sent| encoder_controller.sent(sent));
+
+        self.base.recover(scanned.and(read).and(updated))
+    }
+
+    /// Returns the number of errors counted by the recovery policy.
+    pub const fn error_count(&self) -> u32 {
+        self.base.error_count()
+    }
+
+    /// Returns the LEDs last set by the host.
+    pub const fn leds(&self) -> Leds {
+        self.base.leds()
+    }
+
+    /// Returns the words per minute typed over the last few seconds.
+    pub fn wpm(&self) -> u16 {
+        self.base.wpm()
+    }
+
+    /// Calls `callback` whenever the host changes its LEDs and moves to the layer of the `KC_TO`,
+    /// `KC_DF` or `KC_LAYER` key it returns, like `KC_TO(1)` when Caps Lock turns on.
+    ///
+    /// Other keys do nothing, like `KC_NO` or `KC_MO` which no key would ever release, and so do
+    /// layers that aren't in the layout.
+    #[must_use]
+    pub fn with_led_callback(mut self, callback: fn(Leds) -> Keycode) -> Self {
+        self.base.set_led_callback(callback);
+        self
+    }
+
+    /// Drives RGB underglow with `driver`, set up by [`Underglow`](crate::config::Underglow).
+    #[must_use]
+    pub fn with_underglow(mut self, driver: &'a mut dyn LedDriver) -> Self {
+        self.base.set_led_driver(driver);
+        self
+    }
+
+    /// Shows the layer, lock LEDs and WPM on `display`, set up by
+    /// [`DisplayConfig`](crate::config::DisplayConfig).
+    #[must_use]
+    pub fn with_display(mut self, display: &'a mut dyn StatusDisplay) -> Self {
+        self.base.set_display(display);
+        self
+    }
 
-        self.usb.periodic();
+    /// Merges the keys of the secondary half into the layout and sends it the active layer.
+    ///
+    /// Call this every loop alongside [`periodic`](Self::periodic) on the primary half.
+    pub fn sync<Serial: Read<u8> + Write<u8>>(&mut self, primary: &mut Primary<Serial>) {
+        self.base.sync(primary);
     }
 }
 
@@ -125,16 +234,12 @@ pub struct Keyboard<
     'a,
     const NUM_OF_COLS: usize,
     const NUM_OF_ROWS: usize,
-    Output: OutputPin,
-    Input: InputPin,
-    Timer: CountDown,
+    Scan: Scanner,
     UsbBus: UsbBusTrait,
 > where
     [(); NUM_OF_COLS * NUM_OF_ROWS]: Sized,
 {
-    state: State<'a, NUM_OF_COLS, NUM_OF_ROWS>,
-    matrix: Matrix<'a, NUM_OF_COLS, NUM_OF_ROWS, Output, Input, Timer>,
-    usb: Usb<'a, Timer, UsbBus>,
+    base: Base<'a, NUM_OF_COLS, NUM_OF_ROWS, Scan, UsbBus>,
     buffer: [Keycode; NUM_OF_COLS * NUM_OF_ROWS],
 }
 
@@ -145,24 +250,54 @@ impl<
         const NUM_OF_ROWS: usize,
         Output: OutputPin,
         Input: InputPin,
-        Timer: CountDown,
         UsbBus: UsbBusTrait,
-    > Keyboard<'a, NUM_OF_COLS, NUM_OF_ROWS, Output, Input, Timer, UsbBus>
+    >
+    Keyboard<
+        'a,
+        NUM_OF_COLS,
+        NUM_OF_ROWS,
+        Matrix<'a, NUM_OF_COLS, NUM_OF_ROWS, Output, Input>,
+        UsbBus,
+    >
 where
     [(); NUM_OF_COLS * NUM_OF_ROWS]: Sized,
 {
     pub fn new(
-        layout: &'a [&[&[Keycode]]],
+        layout: &'a [[[Keycode; NUM_OF_COLS]; NUM_OF_ROWS]],
         output_pins: &'a mut [Output],
         input_pins: &'a mut [Input],
-        timer0: &'a mut Timer,
-        timer1: &'a mut Timer,
+        config: Config,
+        clock: &'a dyn Clock,
+        usb_bus: &'a UsbBusAllocator<UsbBus>,
+    ) -> Self {
+        let debouncer = Debouncer::new(config.debounce.algorithm, config.debounce.time);
+        let matrix = Matrix::new(output_pins, input_pins, clock, debouncer, config.matrix);
+
+        Self::from_scanner(layout, matrix, config, usb_bus)
+    }
+}
+
+#[cfg(not(feature = "encoders"))]
+impl<
+        'a,
+        const NUM_OF_COLS: usize,
+        const NUM_OF_ROWS: usize,
+        Scan: Scanner,
+        UsbBus: UsbBusTrait,
+    > Keyboard<'a, NUM_OF_COLS, NUM_OF_ROWS, Scan, UsbBus>
+where
+    [(); NUM_OF_COLS * NUM_OF_ROWS]: Sized,
+{
+    /// Makes a keyboard scanning its keys with `scanner`, like a
+    /// [`DuplexMatrix`](matrix::DuplexMatrix).
+    pub fn from_scanner(
+        layout: &'a [[[Keycode; NUM_OF_COLS]; NUM_OF_ROWS]],
+        scanner: Scan,
+        config: Config,
         usb_bus: &'a UsbBusAllocator<UsbBus>,
     ) -> Self {
         Self {
-            state: State::new(layout),
-            matrix: Matrix::new(output_pins, input_pins, timer0),
-            usb: Usb::new(usb_bus, timer1),
+            base: Base::new(layout, scanner, &config, usb_bus),
             buffer: [Keycode::KC_NO; NUM_OF_COLS * NUM_OF_ROWS],
         }
     }
@@ -175,21 +310,913 @@ where
     //     self.usb.initialize();
     // }
 
-    // update the keyboard
-    pub fn periodic(&mut self) {
-        if self.matrix.scan(&mut self.state) {
-            let flatten_state = self.matrix.state.iter().flatten();
-            let mut index = 0;
+    /// Updates the keyboard.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error of the update unless the recovery policy is
+    /// [`Recovery::Ignore`](crate::config::Recovery::Ignore), the rest of the update still runs.
+    pub fn periodic(&mut self) -> PeriodicResult<Scan> {
+        let (has_changed, scanned) = self.base.scan(&mut self.buffer);
+        let updated = self.base.update(&self.buffer, has_changed, |_| ());
+
+        self.base.recover(scanned.and(updated))
+    }
+
+    /// Returns the number of errors counted by the recovery policy.
+    pub const fn error_count(&self) -> u32 {
+        self.base.error_count()
+    }
+
+    /// Returns the LEDs last set by the host.
+    pub const fn leds(&self) -> Leds {
+        self.base.leds()
+    }
+
+    /// Returns the words per minute typed over the last few seconds.
+    pub fn wpm(&self) -> u16 {
+        self.base.wpm()
+    }
+
+    /// Calls `callback` whenever the host changes its LEDs and moves to the layer of the `KC_TO`,
+    /// `KC_DF` or `KC_LAYER` key it returns, like `KC_TO(1)` when Caps Lock turns on.
+    ///
+    /// Other keys do nothing, like `KC_NO` or `KC_MO` which no key would ever release, and so do
+    /// layers that aren't in the layout.
+    #[must_use]
+    pub fn with_led_callback(mut self, callback: fn(Leds) -> Keycode) -> Self {
+        self.base.set_led_callback(callback);
+        self
+    }
+
+    /// Drives RGB underglow with `driver`, set up by [`Underglow`](crate::config::Underglow).
+    #[must_use]
+    pub fn with_underglow(mut self, driver: &'a mut dyn LedDriver) -> Self {
+        self.base.set_led_driver(driver);
+        self
+    }
+
+    /// Shows the layer, lock LEDs and WPM on `display`, set up by
+    /// [`DisplayConfig`](crate::config::DisplayConfig).
+    #[must_use]
+    pub fn with_display(mut self, display: &'a mut dyn StatusDisplay) -> Self {
+        self.base.set_display(display);
+        self
+    }
+
+    /// Merges the keys of the secondary half into the layout and sends it the active layer.
+    ///
+    /// Call this every loop alongside [`periodic`](Self::periodic) on the primary half.
+    pub fn sync<Serial: Read<u8> + Write<u8>>(&mut self, primary: &mut Primary<Serial>) {
+        self.base.sync(primary);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::cell::RefCell;
+
+    use embedded_hal::serial::Read;
+    use usb_device::class_prelude::UsbBusAllocator;
+
+    use super::{Keyboard, Secondary};
+    use super::matrix::{DuplexMatrix, RoundRobinMatrix, Scanner};
+    use crate::config::{
+        ActiveLevel, Config, DisplayConfig, KeyOverride, Leader, Recovery, Sequence, TapHold,
+        Underglow,
+    };
+    use crate::debounce::{Algorithm, Debouncer};
+    use crate::error::KeyboardError;
+    #[cfg(feature = "encoders")]
+    use crate::hardware::{Encoder, StepCounter, Switch};
+    use crate::keycode::{Keycode, Keycode::*};
+    use crate::macros::MacroStep;
+    use crate::rgb::{Hsv, Rgb};
+    use crate::split::{Decoder, Message, Primary};
+    use crate::tap_dance::Action;
+    use crate::testing::{
+        MockClock, MockDisplay, MockFlexMatrix, MockLedDriver, MockMatrix, MockPinError, MockSerial,
+        MockUsbBus,
+    };
+
+    const COLS: usize = 4;
+    const ROWS: usize = 3;
+
+    #[rustfmt::skip]
+    const KEYS: &[[[Keycode; COLS]; ROWS]] = &[
+        [
+            [KC_Q, KC_W, KC_E, KC_R],
+            [KC_A, KC_S, KC_D, KC_F],
+            [KC_Z, KC_X, KC_C, KC_S],
+        ],
+    ];
+
+    // sets up a keyboard on a mock matrix, clock and usb bus
+    macro_rules! setup {
+        ($keyboard:ident, $matrix:ident, $clock:ident, $capture:ident, $keys:expr, $config:expr) => {
+            let $matrix = MockMatrix::<COLS, ROWS>::new();
+            let mut cols = $matrix.output_pins();
+            let mut rows = $matrix.input_pins();
+
+            let $clock = MockClock::new();
+
+            let (bus, $capture) = MockUsbBus::new();
+            let usb_bus = UsbBusAllocator::new(bus);
+
+            #[cfg(feature = "encoders")]
+            let mut $keyboard = Keyboard::<COLS, ROWS, 0, _, _, _>::new(
+                $keys,
+                &mut cols,
+                &mut rows,
+                [] as [Encoder<crate::testing::MockInputPin<COLS, ROWS>>; 0],
+                $config,
+                &$clock,
+                &usb_bus,
+            );
+            #[cfg(not(feature = "encoders"))]
+            let mut $keyboard = Keyboard::<COLS, ROWS, _, _>::new(
+                $keys,
+                &mut cols,
+                &mut rows,
+                $config,
+                &$clock,
+                &usb_bus,
+            );
+        };
+    }
+
+    // runs the keyboard for `ms`, advancing the clock by 1ms every loop
+    macro_rules! run_for {
+        ($keyboard:expr, $clock:expr, $ms:expr) => {
+            for _ in 0..$ms {
+                $clock.advance_ms(1);
+                $keyboard.periodic().unwrap();
+            }
+        };
+    }
+
+    #[test]
+    fn duplex_matrix_keys_are_reported() {
+        // two column pins and three row pins
+        let mock = MockFlexMatrix::<5>::new();
+        let mut pins = mock.pins();
+        let (col_pins, row_pins) = pins.split_at_mut(2);
+
+        let clock = MockClock::new();
+
+        let (bus, capture) = MockUsbBus::new();
+        let usb_bus = UsbBusAllocator::new(bus);
+
+        let matrix = DuplexMatrix::<COLS, ROWS, _>::new(
+            col_pins,
+            row_pins,
+            &clock,
+            Debouncer::new(Algorithm::SymmetricDefer, 5),
+            ActiveLevel::High,
+        );
+        #[cfg(feature = "encoders")]
+        let mut keyboard = Keyboard::<COLS, ROWS, 0, _, _, _>::from_scanner(
+            KEYS,
+            matrix,
+            [] as [Encoder<crate::testing::MockPin>; 0],
+            Config::new(),
+            &usb_bus,
+        );
+        #[cfg(not(feature = "encoders"))]
+        let mut keyboard = Keyboard::<COLS, ROWS, _, _>::from_scanner(
+            KEYS,
+            matrix,
+            Config::new(),
+            &usb_bus,
+        );
+
+        // column 0 to row 1, and row 2 back to column 1
+        mock.press(0, 3);
+        mock.press(4, 1);
+        run_for!(keyboard, clock, 30);
+        let report = capture.last_keyboard_report().unwrap();
+        assert!(report.contains(KC_A) && report.contains(KC_S));
+    }
+
+    #[test]
+    fn round_robin_matrix_keys_are_reported() {
+        #[rustfmt::skip]
+        const PIN_KEYS: &[[[Keycode; 3]; 4]] = &[
+            [
+                [KC_A, KC_B, KC_C],
+                [KC_D, KC_E, KC_F],
+                [KC_G, KC_H, KC_I],
+                [KC_J, KC_K, KC_L],
+            ],
+        ];
+        let mock = MockFlexMatrix::<4>::active_low();
+        let mut pins = mock.pins();
+
+        let clock = MockClock::new();
+
+        let (bus, capture) = MockUsbBus::new();
+        let usb_bus = UsbBusAllocator::new(bus);
+
+        let matrix = RoundRobinMatrix::<3, 4, _>::new(
+            &mut pins,
+            &clock,
+            Debouncer::new(Algorithm::SymmetricDefer, 5),
+            ActiveLevel::Low,
+        );
+        #[cfg(feature = "encoders")]
+        let mut keyboard = Keyboard::<3, 4, 0, _, _, _>::from_scanner(
+            PIN_KEYS,
+            matrix,
+            [] as [Encoder<crate::testing::MockPin>; 0],
+            Config::new(),
+            &usb_bus,
+        );
+        #[cfg(not(feature = "encoders"))]
+        let mut keyboard = Keyboard::<3, 4, _, _>::from_scanner(
+            PIN_KEYS,
+            matrix,
+            Config::new(),
+            &usb_bus,
+        );
 
-            flatten_state.for_each(|keycode| {
-                self.buffer[index] = *keycode;
-                index += 1;
-            });
+        // pin 1 to pin 0, and pin 2 to pin 3
+        mock.press(1, 0);
+        mock.press(2, 3);
+        run_for!(keyboard, clock, 30);
+        let report = capture.last_keyboard_report().unwrap();
+        assert!(report.contains(KC_D) && report.contains(KC_I));
 
-            self.usb.write_keyboard_report(&self.buffer);
-            self.usb.write_consumer_report(&self.buffer);
+        mock.release(1, 0);
+        run_for!(keyboard, clock, 30);
+        let report = capture.last_keyboard_report().unwrap();
+        assert!(!report.contains(KC_D) && report.contains(KC_I));
+    }
+
+    #[test]
+    #[cfg(feature = "encoders")]
+    #[should_panic(expected = "an encoder switch is wired to a position outside the matrix")]
+    fn encoder_switch_outside_the_matrix() {
+        static COUNTER: StepCounter = StepCounter::new();
+        let matrix = MockMatrix::<COLS, ROWS>::new();
+        let mut cols = matrix.output_pins();
+        let mut rows = matrix.input_pins();
+
+        let clock = MockClock::new();
+
+        let (bus, _capture) = MockUsbBus::new();
+        let usb_bus = UsbBusAllocator::new(bus);
+
+        let encoder = Encoder::<crate::testing::MockPin>::from_interrupt(&COUNTER, &[])
+            .with_switch(Switch::Matrix { row: ROWS, col: 0 }, &[], &[]);
+        let _keyboard = Keyboard::<COLS, ROWS, 1, _, _, _>::new(
+            KEYS,
+            &mut cols,
+            &mut rows,
+            [encoder],
+            Config::new(),
+            &clock,
+            &usb_bus,
+        );
+    }
+
+    #[test]
+    fn press_is_reported_after_debounce() {
+        setup!(keyboard, matrix, clock, capture, KEYS, Config::new());
+
+        matrix.press(2, 3);
+        run_for!(keyboard, clock, 2);
+        assert!(capture
+            .keyboard_reports()
+            .iter()
+            .all(|report| !report.contains(KC_S)));
+
+        run_for!(keyboard, clock, 28);
+        let report = capture.last_keyboard_report().unwrap();
+        assert!(report.contains(KC_S));
+        assert!(!report.contains(KC_A));
+
+        matrix.release(2, 3);
+        run_for!(keyboard, clock, 30);
+        assert!(capture.last_keyboard_report().unwrap().is_empty());
+    }
+
+    #[test]
+    fn slow_loop_keeps_the_time() {
+        setup!(keyboard, matrix, clock, capture, KEYS, Config::new());
+
+        // every loop takes 4ms, so the 5ms debounce is over by the third
+        matrix.press(2, 3);
+        for _ in 0..3 {
+            clock.advance_ms(4);
+            keyboard.periodic().unwrap();
+        }
+        assert!(capture.last_keyboard_report().unwrap().contains(KC_S));
+        assert_eq!(keyboard.base.matrix.now(), 12);
+    }
+
+    #[test]
+    fn consumer_and_system_keys_use_their_own_reports() {
+        #[rustfmt::skip]
+        const KEYS: &[[[Keycode; COLS]; ROWS]] = &[
+            [
+                [KC_CALCULATOR, KC_SYSTEM_SLEEP, KC_BRIGHTNESS_UP, KC_ASSISTANT],
+                [KC_MAIL, KC_CALCULATOR, KC_WWW_HOME, KC_F],
+                [KC_Z, KC_X, KC_C, KC_S],
+            ],
+        ];
+        setup!(keyboard, matrix, clock, capture, KEYS, Config::new());
+
+        matrix.press(0, 0);
+        matrix.press(0, 1);
+        run_for!(keyboard, clock, 30);
+        let consumer = capture.consumer_reports();
+        assert_eq!(consumer.last().unwrap().codes, [0x192, 0, 0, 0]);
+        assert_eq!(capture.system_reports().last().unwrap().usage, 0x82);
+        assert!(capture.last_keyboard_report().unwrap().is_empty());
+
+        // duplicates take one slot and keys past the fourth wait for a free one
+        for (row, col) in [(1, 1), (0, 2), (0, 3), (1, 0), (1, 2)] {
+            matrix.press(row, col);
+        }
+        run_for!(keyboard, clock, 30);
+        let codes = capture.consumer_reports().last().unwrap().codes;
+        assert!(codes.contains(&0x192));
+        assert!(codes.iter().all(|code| *code != 0));
+
+        for (row, col) in [(0, 0), (0, 1), (1, 1), (0, 2), (0, 3), (1, 0), (1, 2)] {
+            matrix.release(row, col);
+        }
+        run_for!(keyboard, clock, 30);
+        assert_eq!(capture.consumer_reports().last().unwrap().codes, [0; 4]);
+        assert_eq!(capture.system_reports().last().unwrap().usage, 0);
+    }
+
+    #[test]
+    fn led_callback_switches_layers() {
+        #[rustfmt::skip]
+        const KEYS: &[[[Keycode; COLS]; ROWS]] = &[
+            [
+                [KC_Q, KC_W, KC_E, KC_R],
+                [KC_A, KC_S, KC_D, KC_F],
+                [KC_Z, KC_X, KC_C, KC_S],
+            ],
+            [
+                [KC_1, KC_TRANS, KC_TRANS, KC_TRANS],
+                [KC_TRANS, KC_TRANS, KC_TRANS, KC_TRANS],
+                [KC_TRANS, KC_TRANS, KC_TRANS, KC_TRANS],
+            ],
+        ];
+        setup!(keyboard, matrix, clock, capture, KEYS, Config::new());
+        keyboard = keyboard.with_led_callback(|leds| {
+            if leds.caps_lock {
+                KC_TO(1)
+            } else {
+                KC_TO(0)
+            }
+        });
+
+        capture.send_output_report(&[0b10]);
+        run_for!(keyboard, clock, 1);
+        assert!(keyboard.leds().caps_lock);
+        matrix.press(0, 0);
+        run_for!(keyboard, clock, 30);
+        assert!(capture.last_keyboard_report().unwrap().contains(KC_1));
+        matrix.release(0, 0);
+        run_for!(keyboard, clock, 30);
+
+        capture.send_output_report(&[0]);
+        run_for!(keyboard, clock, 1);
+        assert!(!keyboard.leds().caps_lock);
+        matrix.press(0, 0);
+        run_for!(keyboard, clock, 30);
+        assert!(capture.last_keyboard_report().unwrap().contains(KC_Q));
+    }
+
+    #[test]
+    fn led_callback_only_moves_to_layers_in_the_layout() {
+        #[rustfmt::skip]
+        const KEYS: &[[[Keycode; COLS]; ROWS]] = &[
+            [
+                [KC_Q, KC_W, KC_E, KC_R],
+                [KC_A, KC_S, KC_D, KC_F],
+                [KC_Z, KC_X, KC_C, KC_S],
+            ],
+            [
+                [KC_1, KC_TRANS, KC_TRANS, KC_TRANS],
+                [KC_TRANS, KC_TRANS, KC_TRANS, KC_TRANS],
+                [KC_TRANS, KC_TRANS, KC_TRANS, KC_TRANS],
+            ],
+        ];
+        setup!(keyboard, matrix, clock, capture, KEYS, Config::new());
+        keyboard = keyboard.with_led_callback(|leds| {
+            if leds.caps_lock {
+                KC_MO(1)
+            } else if leds.num_lock {
+                KC_DF(5)
+            } else {
+                KC_NO
+            }
+        });
+
+        for report in [0b10, 0, 0b1] {
+            capture.send_output_report(&[report]);
+            run_for!(keyboard, clock, 1);
+            matrix.press(0, 0);
+            run_for!(keyboard, clock, 30);
+            assert!(capture.last_keyboard_report().unwrap().contains(KC_Q));
+            matrix.release(0, 0);
+            run_for!(keyboard, clock, 30);
+        }
+        assert!(keyboard.leds().num_lock);
+    }
+
+    #[test]
+    fn rgb_keys_change_the_underglow() {
+        #[rustfmt::skip]
+        const KEYS: &[[[Keycode; COLS]; ROWS]] = &[
+            [
+                [KC_RGB_TOG, KC_RGB_HUI, KC_RGB_VAD, KC_R],
+                [KC_A, KC_S, KC_D, KC_F],
+                [KC_Z, KC_X, KC_C, KC_S],
+            ],
+        ];
+        let config = Config {
+            underglow: Underglow {
+                leds: 5,
+                color: Hsv::new(0, 255, 128),
+                ..Underglow::new()
+            },
+            ..Config::new()
+        };
+        let frames = RefCell::new(Vec::new());
+        // takes two writes per frame
+        let mut leds = MockLedDriver::new(&frames, 3);
+        setup!(keyboard, matrix, clock, capture, KEYS, config);
+        keyboard = keyboard.with_underglow(&mut leds);
+
+        run_for!(keyboard, clock, 40);
+        let frame = frames.borrow().last().unwrap().clone();
+        assert_eq!(frame, [Hsv::new(0, 255, 128).to_rgb(); 5]);
+        // one frame every 16ms
+        assert_eq!(frames.borrow().len(), 3);
+
+        macro_rules! tap {
+            ($row:expr, $col:expr) => {
+                matrix.press($row, $col);
+                run_for!(keyboard, clock, 30);
+                matrix.release($row, $col);
+                run_for!(keyboard, clock, 30);
+            };
+        }
+        tap!(0, 1);
+        tap!(0, 2);
+        let frame = frames.borrow().last().unwrap()[0];
+        assert_eq!(frame, Hsv::new(8, 255, 112).to_rgb());
+
+        tap!(0, 0);
+        let count = frames.borrow().len();
+        assert_eq!(frames.borrow().last().unwrap(), &[Rgb::OFF; 5]);
+        run_for!(keyboard, clock, 100);
+        assert_eq!(frames.borrow().len(), count);
+        assert!(capture.keyboard_reports().iter().all(|report| report.is_empty()));
+    }
+
+    #[test]
+    fn display_shows_status_changes() {
+        #[rustfmt::skip]
+        const KEYS: &[[[Keycode; COLS]; ROWS]] = &[
+            [
+                [KC_MO(1), KC_W, KC_E, KC_R],
+                [KC_A, KC_S, KC_D, KC_F],
+                [KC_Z, KC_X, KC_C, KC_S],
+            ],
+            [
+                [KC_TRANS, KC_TRANS, KC_TRANS, KC_TRANS],
+                [KC_TRANS, KC_TRANS, KC_TRANS, KC_TRANS],
+                [KC_TRANS, KC_TRANS, KC_TRANS, KC_TRANS],
+            ],
+        ];
+        let config = Config {
+            display: DisplayConfig {
+                layer_names: &["Base", "Nav"],
+                ..DisplayConfig::new()
+            },
+            ..Config::new()
+        };
+        let shown = RefCell::new(Vec::new());
+        let mut display = MockDisplay::new(&shown);
+        setup!(keyboard, matrix, clock, capture, KEYS, config);
+        keyboard = keyboard.with_display(&mut display);
+
+        run_for!(keyboard, clock, 300);
+        assert_eq!(shown.borrow().len(), 1);
+        assert_eq!(shown.borrow()[0].layer_name, "Base");
+
+        // the layer change is shown once the refresh interval has passed
+        matrix.press(0, 0);
+        run_for!(keyboard, clock, 30);
+        assert_eq!(shown.borrow().len(), 2);
+        assert_eq!(shown.borrow()[1].layer, 1);
+        assert_eq!(shown.borrow()[1].layer_name, "Nav");
+        matrix.release(0, 0);
+        run_for!(keyboard, clock, 30);
+        assert_eq!(shown.borrow().len(), 2);
+        run_for!(keyboard, clock, 100);
+        assert_eq!(shown.borrow().len(), 3);
+
+        capture.send_output_report(&[0b10]);
+        run_for!(keyboard, clock, 100);
+        let last = *shown.borrow().last().unwrap();
+        assert!(last.leds.caps_lock);
+        assert_eq!(last.layer_name, "Base");
+        // a single press is 2 wpm over the 5s window
+        assert_eq!(last.wpm, 2);
+        assert_eq!(keyboard.wpm(), 2);
+        assert!(capture.keyboard_reports().iter().all(|report| report.is_empty()));
+    }
+
+    #[test]
+    fn mod_tap_reports_tap_then_hold() {
+        #[rustfmt::skip]
+        const KEYS: &[[[Keycode; COLS]; ROWS]] = &[
+            [
+                [KC_MT(&KC_LEFT_SHIFT, &KC_A), KC_W, KC_E, KC_R],
+                [KC_A, KC_S, KC_D, KC_F],
+                [KC_Z, KC_X, KC_C, KC_S],
+            ],
+        ];
+        let config = Config {
+            tap_hold: TapHold {
+                tapping_term: 100,
+                ..TapHold::new()
+            },
+            ..Config::new()
+        };
+        setup!(keyboard, matrix, clock, capture, KEYS, config);
+
+        matrix.press(0, 0);
+        run_for!(keyboard, clock, 30);
+        matrix.release(0, 0);
+        run_for!(keyboard, clock, 30);
+
+        let reports = capture.keyboard_reports();
+        assert!(reports.iter().any(|report| report.contains(KC_A)));
+        assert!(reports.iter().all(|report| !report.contains(KC_LEFT_SHIFT)));
+        assert!(reports.last().unwrap().is_empty());
+
+        matrix.press(0, 0);
+        run_for!(keyboard, clock, 150);
+        let report = capture.last_keyboard_report().unwrap();
+        assert!(report.contains(KC_LEFT_SHIFT));
+        assert!(!report.contains(KC_A));
+    }
+
+    #[test]
+    fn macro_types_one_key_per_report() {
+        #[rustfmt::skip]
+        const KEYS: &[[[Keycode; COLS]; ROWS]] = &[
+            [
+                [KC_MACRO(&[MacroStep::Type("Hi")]), KC_W, KC_E, KC_R],
+                [KC_A, KC_S, KC_D, KC_F],
+                [KC_Z, KC_X, KC_C, KC_S],
+            ],
+        ];
+        setup!(keyboard, matrix, clock, capture, KEYS, Config::new());
+
+        matrix.press(0, 0);
+        run_for!(keyboard, clock, 30);
+        matrix.release(0, 0);
+        run_for!(keyboard, clock, 30);
+
+        let mut reports = capture.keyboard_reports();
+        reports.dedup();
+        let reports: Vec<_> = reports
+            .into_iter()
+            .skip_while(|report| report.is_empty())
+            .collect();
+
+        assert_eq!(reports.len(), 4);
+        assert!(reports[0].contains(KC_LEFT_SHIFT) && reports[0].contains(KC_H));
+        assert!(reports[1].is_empty());
+        assert!(reports[2].contains(KC_I) && !reports[2].contains(KC_LEFT_SHIFT));
+        assert!(reports[3].is_empty());
+    }
+
+    #[test]
+    fn leader_sequence_keys_are_not_sent() {
+        #[rustfmt::skip]
+        const KEYS: &[[[Keycode; COLS]; ROWS]] = &[
+            [
+                [KC_LEAD, KC_G, KC_S, KC_R],
+                [KC_A, KC_S, KC_D, KC_F],
+                [KC_Z, KC_X, KC_C, KC_S],
+            ],
+        ];
+        const SEQUENCES: &[Sequence] = &[Sequence::new(
+            &[KC_G, KC_S],
+            Action::Key(KC_MACRO(&[MacroStep::Type("gs")])),
+        )];
+        let config = Config {
+            leader: Leader {
+                sequences: SEQUENCES,
+                ..Leader::new()
+            },
+            ..Config::new()
+        };
+        setup!(keyboard, matrix, clock, capture, KEYS, config);
+
+        for (row, col) in [(0, 0), (0, 1), (0, 2)] {
+            matrix.press(row, col);
+            run_for!(keyboard, clock, 30);
+            matrix.release(row, col);
+            run_for!(keyboard, clock, 30);
+        }
+
+        let mut reports = capture.keyboard_reports();
+        reports.dedup();
+        let reports: Vec<_> = reports
+            .into_iter()
+            .filter(|report| !report.is_empty())
+            .collect();
+
+        // only the macro is typed
+        assert_eq!(reports.len(), 2);
+        assert!(reports[0].contains(KC_G) && !reports[0].contains(KC_S));
+        assert!(reports[1].contains(KC_S) && !reports[1].contains(KC_G));
+    }
+
+    #[test]
+    fn key_override_drops_its_modifier() {
+        #[rustfmt::skip]
+        const KEYS: &[[[Keycode; COLS]; ROWS]] = &[
+            [
+                [KC_LEFT_SHIFT, KC_BACKSPACE, KC_E, KC_R],
+                [KC_A, KC_S, KC_D, KC_F],
+                [KC_Z, KC_X, KC_C, KC_S],
+            ],
+        ];
+        const OVERRIDES: &[KeyOverride] = &[KeyOverride::new(
+            &[KC_LEFT_SHIFT],
+            KC_BACKSPACE,
+            KC_DELETE_FORWARD,
+        )];
+        let config = Config {
+            key_overrides: OVERRIDES,
+            ..Config::new()
+        };
+        setup!(keyboard, matrix, clock, capture, KEYS, config);
+
+        matrix.press(0, 0);
+        matrix.press(0, 1);
+        run_for!(keyboard, clock, 30);
+        let report = capture.last_keyboard_report().unwrap();
+        assert!(report.contains(KC_DELETE_FORWARD));
+        assert!(!report.contains(KC_LEFT_SHIFT) && !report.contains(KC_BACKSPACE));
+
+        matrix.release(0, 1);
+        run_for!(keyboard, clock, 30);
+        let report = capture.last_keyboard_report().unwrap();
+        assert!(report.contains(KC_LEFT_SHIFT) && !report.contains(KC_DELETE_FORWARD));
+    }
+
+    #[test]
+    fn key_override_reads_the_modifiers_of_keys_2() {
+        #[rustfmt::skip]
+        const KEYS: &[[[Keycode; COLS]; ROWS]] = &[
+            [
+                [KEYS_2(&KC_RIGHT_SHIFT, &KC_LEFT_CTRL), KC_BACKSPACE, KC_E, KC_R],
+                [KC_A, KC_S, KC_D, KC_F],
+                [KC_Z, KC_X, KC_C, KC_S],
+            ],
+        ];
+        const OVERRIDES: &[KeyOverride] = &[KeyOverride::new(
+            &[KC_LEFT_SHIFT],
+            KC_BACKSPACE,
+            KC_DELETE_FORWARD,
+        )];
+        let config = Config {
+            key_overrides: OVERRIDES,
+            ..Config::new()
+        };
+        setup!(keyboard, matrix, clock, capture, KEYS, config);
+
+        matrix.press(0, 0);
+        matrix.press(0, 1);
+        run_for!(keyboard, clock, 30);
+        let report = capture.last_keyboard_report().unwrap();
+        assert!(report.contains(KC_DELETE_FORWARD) && report.contains(KC_LEFT_CTRL));
+        assert!(!report.contains(KC_RIGHT_SHIFT) && !report.contains(KC_BACKSPACE));
+    }
+
+    #[test]
+    fn mouse_key_moves_while_held() {
+        #[rustfmt::skip]
+        const KEYS: &[[[Keycode; COLS]; ROWS]] = &[
+            [
+                [KC_MS_RIGHT, KC_BTN1, KC_E, KC_R],
+                [KC_A, KC_S, KC_D, KC_F],
+                [KC_Z, KC_X, KC_C, KC_S],
+            ],
+        ];
+        setup!(keyboard, matrix, clock, capture, KEYS, Config::new());
+
+        matrix.press(0, 0);
+        run_for!(keyboard, clock, 200);
+        matrix.release(0, 0);
+        run_for!(keyboard, clock, 30);
+
+        let reports = capture.mouse_reports();
+        assert!(reports.len() > 5);
+        assert!(reports.iter().all(|report| report.x > 0 && report.y == 0));
+        assert!(reports.first().unwrap().x < reports.last().unwrap().x);
+
+        capture.clear();
+        matrix.press(0, 1);
+        run_for!(keyboard, clock, 30);
+        matrix.release(0, 1);
+        run_for!(keyboard, clock, 30);
+
+        let buttons: Vec<_> = capture
+            .mouse_reports()
+            .iter()
+            .map(|report| report.buttons)
+            .collect();
+        assert_eq!(buttons, [1, 0]);
+    }
+
+    #[test]
+    fn split_halves_share_one_layout() {
+        #[rustfmt::skip]
+        const KEYS: &[[[Keycode; COLS]; ROWS]] = &[
+            [
+                [KC_MO(1), KC_W, KC_E, KC_R],
+                [KC_A, KC_S, KC_D, KC_F],
+                [KC_Z, KC_X, KC_C, KC_S],
+            ],
+            [
+                [KC_TRANS, KC_TRANS, KC_TRANS, KC_1],
+                [KC_TRANS, KC_TRANS, KC_TRANS, KC_TRANS],
+                [KC_TRANS, KC_TRANS, KC_TRANS, KC_TRANS],
+            ],
+        ];
+        setup!(keyboard, matrix, clock, capture, KEYS, Config::new());
+
+        // the secondary half holds the two right columns
+        let (primary_serial, secondary_serial) = MockSerial::pair();
+        let mut primary = Primary::new(primary_serial, 0, 2);
+
+        let half = MockMatrix::<2, ROWS>::new();
+        let mut half_cols = half.output_pins();
+        let mut half_rows = half.input_pins();
+        let mut secondary = Secondary::<2, ROWS, _, _, _>::new(
+            &mut half_cols,
+            &mut half_rows,
+            Config::new(),
+            &clock,
+            secondary_serial,
+        );
+
+        macro_rules! run_both_for {
+            ($ms:expr) => {
+                for _ in 0..$ms {
+                    clock.advance_ms(1);
+                    secondary.periodic().unwrap();
+                    keyboard.periodic().unwrap();
+                    keyboard.sync(&mut primary);
+                }
+            };
         }
 
-        self.usb.periodic();
+        half.press(1, 0);
+        run_both_for!(30);
+        let report = capture.last_keyboard_report().unwrap();
+        assert!(report.contains(KC_D));
+
+        half.release(1, 0);
+        run_both_for!(30);
+        assert!(capture.last_keyboard_report().unwrap().is_empty());
+
+        matrix.press(0, 0);
+        run_both_for!(30);
+        assert_eq!(secondary.layer(), 1);
+
+        half.press(0, 1);
+        run_both_for!(30);
+        assert!(capture.last_keyboard_report().unwrap().contains(KC_1));
+    }
+
+    #[test]
+    fn failing_inputs_follow_recovery_policy() {
+        let config = Config {
+            recovery: Recovery::Count,
+            ..Config::new()
+        };
+        setup!(keyboard, matrix, clock, capture, KEYS, config);
+
+        matrix.press(0, 0);
+        run_for!(keyboard, clock, 30);
+
+        // the key keeps its last state while its input can't be read
+        matrix.fail_inputs(true);
+        matrix.release(0, 0);
+        let mut errors = 0;
+        for _ in 0..30 {
+            clock.advance_ms(1);
+            if let Err(error) = keyboard.periodic() {
+                assert!(matches!(error, KeyboardError::Input(MockPinError)));
+                errors += 1;
+            }
+        }
+        assert!(errors > 0);
+        assert_eq!(keyboard.error_count(), errors);
+        assert!(capture.last_keyboard_report().unwrap().contains(KC_Q));
+
+        matrix.fail_inputs(false);
+        run_for!(keyboard, clock, 30);
+        assert!(capture.last_keyboard_report().unwrap().is_empty());
+    }
+
+    #[test]
+    fn secondary_resends_held_keys() {
+        let clock = MockClock::new();
+        let (serial, mut primary_serial) = MockSerial::pair();
+        let half = MockMatrix::<2, ROWS>::new();
+        let mut half_cols = half.output_pins();
+        let mut half_rows = half.input_pins();
+        let mut secondary = Secondary::<2, ROWS, _, _, _>::new(
+            &mut half_cols,
+            &mut half_rows,
+            Config::new(),
+            &clock,
+            serial,
+        );
+
+        half.press(2, 1);
+        let mut decoder = Decoder::new();
+        let mut received = Vec::new();
+        for _ in 0..1000 {
+            clock.advance_ms(1);
+            secondary.periodic().unwrap();
+            while let Ok(byte) = primary_serial.read() {
+                received.extend(decoder.push(byte));
+            }
+        }
+
+        assert_eq!(received[0], Message::Press { row: 2, col: 1 });
+        let keys: Vec<Message> = (0..3)
+            .map(|row| Message::Keys {
+                row,
+                chunk: 0,
+                held: if row == 2 { 0b10 } else { 0 },
+            })
+            .collect();
+        assert_eq!(received[1..], keys);
+    }
+
+    #[test]
+    fn secondary_follows_recovery_policy() {
+        for (recovery, counted) in [(Recovery::Count, true), (Recovery::Ignore, false)] {
+            let clock = MockClock::new();
+            let (serial, _primary_serial) = MockSerial::pair();
+            let half = MockMatrix::<2, ROWS>::new();
+            let mut half_cols = half.output_pins();
+            let mut half_rows = half.input_pins();
+            let mut secondary = Secondary::<2, ROWS, _, _, _>::new(
+                &mut half_cols,
+                &mut half_rows,
+                Config {
+                    recovery,
+                    ..Config::new()
+                },
+                &clock,
+                serial,
+            );
+
+            half.fail_inputs(true);
+            let mut errors = 0;
+            for _ in 0..30 {
+                clock.advance_ms(1);
+                if let Err(error) = secondary.periodic() {
+                    assert!(matches!(error, KeyboardError::Input(MockPinError)));
+                    errors += 1;
+                }
+            }
+            assert_eq!(errors > 0, counted);
+            assert_eq!(secondary.error_count(), errors);
+        }
+    }
+
+    #[test]
+    fn ignored_errors_are_not_returned() {
+        let config = Config {
+            recovery: Recovery::Ignore,
+            ..Config::new()
+        };
+        setup!(keyboard, matrix, clock, capture, KEYS, config);
+
+        matrix.fail_inputs(true);
+        run_for!(keyboard, clock, 30);
+        assert_eq!(keyboard.error_count(), 0);
+        assert!(capture.keyboard_reports().iter().all(|report| report.is_empty()));
     }
 }
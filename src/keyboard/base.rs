@@ -0,0 +1,279 @@
+use embedded_hal::serial::{Read, Write};
+use usb_device::class_prelude::{UsbBus as UsbBusTrait, UsbBusAllocator};
+use usbd_human_interface_device::UsbHidError;
+
+use super::combo::{ComboEvents, Combos};
+use super::key_override::KeyOverrides;
+use super::macro_player::MacroPlayer;
+use super::matrix::Scanner;
+use super::mouse::Mouse;
+use super::screen::Screen;
+use super::state::State;
+use super::underglow::Underglow;
+use super::usb::Usb;
+use super::wpm::Wpm;
+use super::PeriodicResult;
+use crate::config::{Config, Recovery};
+use crate::display::StatusDisplay;
+use crate::error::KeyboardError;
+use crate::indicator::Leds;
+use crate::keycode::Keycode;
+use crate::rgb::LedDriver;
+use crate::split::Primary;
+
+// everything a keyboard does besides reading its encoders, shared by the keyboards with and
+// without the `encoders` feature
+pub(super) struct Base<
+    'a,
+    const NUM_OF_COLS: usize,
+    const NUM_OF_ROWS: usize,
+    Scan: Scanner,
+    UsbBus: UsbBusTrait,
+> {
+    pub(super) state: State<'a, NUM_OF_COLS, NUM_OF_ROWS>,
+    combos: Combos,
+    key_overrides: KeyOverrides,
+    pub(super) matrix: Scan,
+    usb: Usb<'a, UsbBus>,
+    macro_player: MacroPlayer,
+    mouse: Mouse,
+    underglow: Underglow<'a>,
+    screen: Screen<'a>,
+    wpm: Wpm,
+    recovery: Recovery,
+    // errors counted by the recovery policy
+    errors: u32,
+    leds: Leds,
+    // called when the host changes its leds
+    led_callback: Option<fn(Leds) -> Keycode>,
+}
+
+impl<
+        'a,
+        const NUM_OF_COLS: usize,
+        const NUM_OF_ROWS: usize,
+        Scan: Scanner,
+        UsbBus: UsbBusTrait,
+    > Base<'a, NUM_OF_COLS, NUM_OF_ROWS, Scan, UsbBus>
+{
+    pub(super) fn new(
+        layout: &'a [[[Keycode; NUM_OF_COLS]; NUM_OF_ROWS]],
+        scanner: Scan,
+        config: &Config,
+        usb_bus: &'a UsbBusAllocator<UsbBus>,
+    ) -> Self {
+        Self {
+            state: State::new(layout, config),
+            combos: Combos::new(&config.combos),
+            key_overrides: KeyOverrides::new(config.key_overrides),
+            matrix: scanner,
+            usb: Usb::new(usb_bus),
+            macro_player: MacroPlayer::new(),
+            mouse: Mouse::new(&config.mouse_keys),
+            underglow: Underglow::new(&config.underglow),
+            screen: Screen::new(&config.display),
+            wpm: Wpm::new(),
+            recovery: config.recovery,
+            errors: 0,
+            leds: Leds::from_report(0),
+            led_callback: None,
+        }
+    }
+
+    // scans the matrix and copies the layout keys to the start of `keys` if they changed,
+    // returning if they did
+    pub(super) fn scan<EncoderError>(
+        &mut self,
+        keys: &mut [Keycode],
+    ) -> (bool, PeriodicResult<Scan, EncoderError>) {
+        let scanned = self.matrix.scan(&mut ComboEvents {
+            combos: &mut self.combos,
+            state: &mut self.state,
+        });
+        // a scan that failed part way may still have changed keys
+        let has_changed = scanned.as_ref().map_or(true, |has_changed| *has_changed);
+
+        if has_changed {
+            let flatten_state = self.state.keys().iter().flatten();
+
+            for (keycode, key) in keys.iter_mut().zip(flatten_state) {
+                *keycode = *key;
+            }
+        }
+
+        (has_changed, scanned.map(|_| ()))
+    }
+
+    // runs the rest of an update with the keys read: the underglow, macros, the reports, mouse
+    // keys, USB, the host leds and the display
+    // the reports are sent if `send` is set or a macro is playing, then `on_sent` is called with
+    // if the keyboard report went out
+    pub(super) fn update<EncoderError>(
+        &mut self,
+        keys: &[Keycode],
+        send: bool,
+        on_sent: impl FnOnce(bool),
+    ) -> PeriodicResult<Scan, EncoderError> {
+        while let Some(keycode) = self.state.take_rgb() {
+            self.underglow.apply(keycode);
+        }
+        self.underglow.periodic(
+            self.state.layer(),
+            self.matrix.now(),
+            self.state.last_press(),
+        );
+
+        let played = self
+            .play_macro(keys, send, on_sent)
+            .map_err(KeyboardError::Usb);
+        let moved = self.move_mouse(keys).map_err(KeyboardError::Usb);
+
+        let polled = self
+            .usb
+            .periodic(self.matrix.now())
+            .map_err(KeyboardError::Usb);
+        if let Some(leds) = self.usb.take_leds() {
+            self.update_leds(leds);
+        }
+
+        let now = self.matrix.now();
+        self.wpm.update(self.state.presses(), now);
+        self.screen
+            .periodic(self.state.layer(), self.leds, self.wpm.wpm(), now);
+
+        played.and(moved).and(polled)
+    }
+
+    // applies the recovery policy to the result of an update
+    pub(super) fn recover<EncoderError>(
+        &mut self,
+        result: PeriodicResult<Scan, EncoderError>,
+    ) -> PeriodicResult<Scan, EncoderError> {
+        let Err(error) = result else {
+            return Ok(());
+        };
+
+        if self.recovery == Recovery::Ignore {
+            return Ok(());
+        }
+
+        self.errors = self.errors.saturating_add(1);
+
+        if self.recovery == Recovery::ResetUsb && matches!(error, KeyboardError::Usb(_)) {
+            self.usb.reset();
+        }
+
+        Err(error)
+    }
+
+    pub(super) const fn error_count(&self) -> u32 {
+        self.errors
+    }
+
+    pub(super) const fn leds(&self) -> Leds {
+        self.leds
+    }
+
+    pub(super) fn wpm(&self) -> u16 {
+        self.wpm.wpm()
+    }
+
+    pub(super) fn set_led_callback(&mut self, callback: fn(Leds) -> Keycode) {
+        self.led_callback = Some(callback);
+    }
+
+    pub(super) fn set_led_driver(&mut self, driver: &'a mut dyn LedDriver) {
+        self.underglow.set_driver(driver);
+    }
+
+    pub(super) fn set_display(&mut self, display: &'a mut dyn StatusDisplay) {
+        self.screen.set_display(display);
+    }
+
+    // records the leds set by the host and calls the callback when they change
+    fn update_leds(&mut self, leds: Leds) {
+        if leds != self.leds {
+            self.leds = leds;
+
+            if let Some(callback) = self.led_callback {
+                let keycode = callback(leds);
+                if matches!(
+                    keycode,
+                    Keycode::KC_TO(_) | Keycode::KC_DF(_) | Keycode::KC_LAYER(_)
+                ) {
+                    self.state.switch_layer(keycode);
+                }
+            }
+        }
+    }
+
+    // plays the next step of a macro and sends the reports of `keys` if `send` is set or a macro
+    // is playing
+    fn play_macro(
+        &mut self,
+        keys: &[Keycode],
+        send: bool,
+        on_sent: impl FnOnce(bool),
+    ) -> Result<(), UsbHidError> {
+        if let Some(steps) = self.state.take_macro() {
+            self.macro_player.play(steps);
+        }
+
+        let playing = self.macro_player.is_playing();
+        if playing {
+            self.macro_player.step(self.matrix.now());
+        }
+
+        if send || playing {
+            let keys = self.key_overrides.apply(
+                keys.iter().chain(self.macro_player.keys()),
+                self.state.layer(),
+            );
+            let sent = self.usb.write_keyboard_report(keys.clone());
+            let consumer = self.usb.write_consumer_report(keys.clone());
+            let system = self.usb.write_system_report(keys);
+
+            self.macro_player.sent(matches!(sent, Ok(true)));
+            on_sent(matches!(sent, Ok(true)));
+
+            return sent.and(consumer).and(system);
+        }
+
+        Ok(())
+    }
+
+    // merges the keys of the secondary half into the layout and sends it the active layer
+    pub(super) fn sync<Serial: Read<u8> + Write<u8>>(&mut self, primary: &mut Primary<Serial>) {
+        let now = self.matrix.now();
+
+        while let Some((row, col, pressed)) = primary.receive() {
+            if row >= NUM_OF_ROWS || col >= NUM_OF_COLS {
+                continue;
+            }
+
+            if pressed {
+                self.combos.press(&mut self.state, row, col, now);
+            } else {
+                self.combos.release(&mut self.state, row, col, now);
+            }
+        }
+
+        primary.sync_layer(self.state.layer());
+    }
+
+    // sends a mouse report while mouse keys are held and when they change
+    fn move_mouse(&mut self, keys: &[Keycode]) -> Result<(), UsbHidError> {
+        let keys = self.key_overrides.apply(
+            keys.iter().chain(self.macro_player.keys()),
+            self.state.layer(),
+        );
+
+        if let Some(report) = self.mouse.report(keys, self.matrix.now()) {
+            if self.usb.write_mouse_report(&report)? {
+                self.mouse.sent(&report);
+            }
+        }
+
+        Ok(())
+    }
+}
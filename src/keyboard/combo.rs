@@ -0,0 +1,394 @@
+use crate::config::{self, Combo};
+
+use super::matrix::Events;
+use super::State;
+
+// keys a combo can have, one bit each in `Active::held`
+const MAX_KEYS: usize = 8;
+// combos that can be held down at once
+const MAX_ACTIVE: usize = 4;
+
+// a combo that was pressed and still has keys held down
+#[derive(Clone, Copy)]
+struct Active {
+    combo: &'static Combo,
+    // the position its keycode is sent from
+    row: usize,
+    col: usize,
+    // the keys of the combo still held down
+    held: u8,
+    // a key was lifted, so the keycode was released and the rest of the keys are ignored
+    released: bool,
+}
+
+// holds back the presses of combo keys between the matrix and the state, until they make a
+// combo or can't
+pub(super) struct Combos {
+    combos: &'static [Combo],
+    term: u32,
+    // presses held back, in order
+    buffer: [(usize, usize); MAX_KEYS],
+    len: usize,
+    // when the first key was held back
+    started_at: u32,
+    active: [Option<Active>; MAX_ACTIVE],
+    // keys lifted in the scan where keys held back were pressed on their own, released on the
+    // next tick so the presses are sent first
+    releases: [(usize, usize); MAX_KEYS],
+    releases_len: usize,
+}
+
+impl Combos {
+    pub(super) const fn new(config: &config::Combos) -> Self {
+        Self {
+            combos: config.combos,
+            term: config.term,
+            buffer: [(0, 0); MAX_KEYS],
+            len: 0,
+            started_at: 0,
+            active: [None; MAX_ACTIVE],
+            releases: [(0, 0); MAX_KEYS],
+            releases_len: 0,
+        }
+    }
+
+    // handles a key being pressed on the matrix
+    pub(super) fn press<const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize>(
+        &mut self,
+        state: &mut State<NUM_OF_COLS, NUM_OF_ROWS>,
+        row: usize,
+        col: usize,
+        now: u32,
+    ) {
+        let layer = state.layer();
+        let buffered = &self.buffer[..self.len];
+        let is_candidate = |combo: &Combo| {
+            self.len < MAX_KEYS
+                && combo.keys.contains(&(row, col))
+                && buffered.iter().all(|key| combo.keys.contains(key))
+        };
+
+        if !self.combos(layer).any(is_candidate) {
+            if self.len > 0 {
+                self.flush(state, now);
+                self.press(state, row, col, now);
+            } else {
+                state.press(row, col, now);
+            }
+            return;
+        }
+
+        if self.len == 0 {
+            self.started_at = now;
+        }
+        self.buffer[self.len] = (row, col);
+        self.len += 1;
+
+        // a combo is pressed as soon as no bigger combo could still be
+        let complete = self.complete(layer);
+        let bigger = self
+            .candidates(layer)
+            .any(|combo| combo.keys.len() > self.len);
+        if let (Some(combo), false) = (complete, bigger) {
+            self.fire(state, combo, now);
+        }
+    }
+
+    // handles a key being released on the matrix
+    pub(super) fn release<const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize>(
+        &mut self,
+        state: &mut State<NUM_OF_COLS, NUM_OF_ROWS>,
+        row: usize,
+        col: usize,
+        now: u32,
+    ) {
+        // lifted before the combo was complete, so the keys are pressed on their own
+        if self.buffer[..self.len].contains(&(row, col)) {
+            self.flush(state, now);
+            self.release_later(state, row, col, now);
+            return;
+        }
+
+        for slot in &mut self.active {
+            let Some(active) = slot else {
+                continue;
+            };
+            let Some(index) = active.combo.keys.iter().position(|key| *key == (row, col)) else {
+                continue;
+            };
+            if active.held & (1 << index) == 0 {
+                continue;
+            }
+
+            // the first key lifted releases the combo
+            active.held &= !(1 << index);
+            if !active.released {
+                active.released = true;
+                state.release(active.row, active.col, now);
+            }
+            if active.held == 0 {
+                *slot = None;
+            }
+            return;
+        }
+
+        // keys pressed by the flush may be lifted in the same scan too
+        if self.releases_len > 0 {
+            self.release_later(state, row, col, now);
+        } else {
+            state.release(row, col, now);
+        }
+    }
+
+    fn release_later<const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize>(
+        &mut self,
+        state: &mut State<NUM_OF_COLS, NUM_OF_ROWS>,
+        row: usize,
+        col: usize,
+        now: u32,
+    ) {
+        if let Some(slot) = self.releases.get_mut(self.releases_len) {
+            *slot = (row, col);
+            self.releases_len += 1;
+        } else {
+            state.release(row, col, now);
+        }
+    }
+
+    // presses a complete combo or the keys held back once the term of every combo they could
+    // make has passed
+    pub(super) fn tick<const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize>(
+        &mut self,
+        state: &mut State<NUM_OF_COLS, NUM_OF_ROWS>,
+        now: u32,
+    ) {
+        let releases = core::mem::take(&mut self.releases_len);
+        for (row, col) in self.releases[..releases].iter().copied() {
+            state.release(row, col, now);
+        }
+
+        if self.len > 0 {
+            let layer = state.layer();
+            let term = self
+                .candidates(layer)
+                .map(|combo| combo.term.unwrap_or(self.term))
+                .max()
+                .unwrap_or(0);
+
+            if now.wrapping_sub(self.started_at) >= term {
+                match self.complete(layer) {
+                    Some(combo) => self.fire(state, combo, now),
+                    None => self.flush(state, now),
+                }
+            }
+        }
+
+        state.tick(now);
+    }
+
+    // the combos working on `layer`
+    fn combos(&self, layer: usize) -> impl Iterator<Item = &'static Combo> {
+        let combos = self.combos;
+        combos
+            .iter()
+            .filter(move |combo| combo.layer.is_none_or(|combo_layer| combo_layer == layer))
+    }
+
+    // the combos the keys held back could still make
+    fn candidates(&self, layer: usize) -> impl Iterator<Item = &'static Combo> + '_ {
+        let buffered = &self.buffer[..self.len];
+        self.combos(layer)
+            .filter(move |combo| buffered.iter().all(|key| combo.keys.contains(key)))
+    }
+
+    // the combo made by exactly the keys held back
+    fn complete(&self, layer: usize) -> Option<&'static Combo> {
+        self.candidates(layer)
+            .find(|combo| combo.keys.len() == self.len)
+    }
+
+    fn fire<const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize>(
+        &mut self,
+        state: &mut State<NUM_OF_COLS, NUM_OF_ROWS>,
+        combo: &'static Combo,
+        now: u32,
+    ) {
+        let Some(slot) = self.active.iter_mut().find(|slot| slot.is_none()) else {
+            // too many combos held down, the keys are pressed on their own
+            self.flush(state, now);
+            return;
+        };
+
+        let (row, col) = self.buffer[0];
+        #[allow(clippy::cast_possible_truncation)]
+        let held = ((1u16 << combo.keys.len()) - 1) as u8;
+        *slot = Some(Active {
+            combo,
+            row,
+            col,
+            held,
+            released: false,
+        });
+        self.len = 0;
+
+        state.press_as(row, col, combo.keycode, now);
+    }
+
+    // presses the keys held back on their own
+    fn flush<const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize>(
+        &mut self,
+        state: &mut State<NUM_OF_COLS, NUM_OF_ROWS>,
+        now: u32,
+    ) {
+        let len = core::mem::take(&mut self.len);
+        for (row, col) in self.buffer[..len].iter().copied() {
+            state.press(row, col, now);
+        }
+    }
+}
+
+// sends the changes of a scan to the state through the combos
+pub(super) struct ComboEvents<'c, 's, 'a, const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize> {
+    pub(super) combos: &'c mut Combos,
+    pub(super) state: &'s mut State<'a, NUM_OF_COLS, NUM_OF_ROWS>,
+}
+
+impl<const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize> Events
+    for ComboEvents<'_, '_, '_, NUM_OF_COLS, NUM_OF_ROWS>
+{
+    fn tick(&mut self, now: u32) {
+        self.combos.tick(self.state, now);
+    }
+
+    fn press(&mut self, row: usize, col: usize, now: u32) {
+        self.combos.press(self.state, row, col, now);
+    }
+
+    fn release(&mut self, row: usize, col: usize, now: u32) {
+        self.combos.release(self.state, row, col, now);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Combos;
+    use crate::config::{self, Combo, Config};
+    use crate::keyboard::State;
+    use crate::keycode::{Keycode, Keycode::*};
+
+    #[rustfmt::skip]
+    const KEYS: &[[[Keycode; 5]; 2]] = &[
+        [
+            [KC_J, KC_K, KC_L, KC_A, KC_MO(1)],
+            [KC_Z, KC_X, KC_C, KC_V, KC_B],
+        ],
+        [
+            [KC_TRANS, KC_TRANS, KC_TRANS, KC_TRANS, KC_TRANS],
+            [KC_TRANS, KC_TRANS, KC_TRANS, KC_TRANS, KC_TRANS],
+        ],
+    ];
+
+    const COMBOS: &[Combo] = &[
+        Combo::new(&[(0, 0), (0, 1)], KC_ESCAPE),
+        Combo::new(&[(0, 1), (0, 2)], KC_BACKSPACE),
+        Combo::new(&[(0, 1), (0, 2), (0, 3)], KC_TAB),
+        Combo {
+            layer: Some(1),
+            ..Combo::new(&[(1, 0), (1, 1)], KC_ENTER)
+        },
+    ];
+
+    fn setup() -> (Combos, State<'static, 5, 2>) {
+        let config = Config {
+            combos: config::Combos {
+                combos: COMBOS,
+                term: 50,
+            },
+            ..Config::new()
+        };
+        (Combos::new(&config.combos), State::new(KEYS, &config))
+    }
+
+    #[test]
+    fn combo_sends_its_keycode() {
+        let (mut combos, mut state) = setup();
+
+        combos.press(&mut state, 0, 0, 0);
+        assert_eq!(state.keys()[0][0], KC_NO);
+        combos.press(&mut state, 0, 1, 10);
+        assert_eq!(state.keys()[0], [KC_ESCAPE, KC_NO, KC_NO, KC_NO, KC_NO]);
+
+        // the first key lifted releases the combo, the other is ignored
+        combos.release(&mut state, 0, 1, 20);
+        assert_eq!(state.keys()[0], [KC_NO; 5]);
+        combos.release(&mut state, 0, 0, 30);
+        combos.press(&mut state, 1, 4, 40);
+        assert_eq!(state.keys()[1], [KC_NO, KC_NO, KC_NO, KC_NO, KC_B]);
+    }
+
+    #[test]
+    fn keys_are_pressed_on_their_own_after_the_term() {
+        let (mut combos, mut state) = setup();
+
+        combos.press(&mut state, 0, 0, 0);
+        combos.tick(&mut state, 40);
+        assert_eq!(state.keys()[0][0], KC_NO);
+        combos.tick(&mut state, 50);
+        assert_eq!(state.keys()[0][0], KC_J);
+
+        // another key stops a combo straight away
+        combos.press(&mut state, 0, 1, 60);
+        combos.press(&mut state, 1, 4, 70);
+        assert_eq!(state.keys()[0][1], KC_K);
+        assert_eq!(state.keys()[1][4], KC_B);
+    }
+
+    #[test]
+    fn key_lifted_early_is_tapped() {
+        let (mut combos, mut state) = setup();
+
+        combos.press(&mut state, 0, 1, 0);
+        combos.press(&mut state, 0, 2, 10);
+        combos.release(&mut state, 0, 1, 20);
+        combos.release(&mut state, 0, 2, 20);
+        // released on the next tick so the presses are sent
+        assert_eq!(state.keys()[0], [KC_NO, KC_K, KC_L, KC_NO, KC_NO]);
+        combos.tick(&mut state, 21);
+        assert_eq!(state.keys()[0], [KC_NO; 5]);
+    }
+
+    #[test]
+    fn smaller_combo_waits_for_bigger_one() {
+        let (mut combos, mut state) = setup();
+
+        combos.press(&mut state, 0, 1, 0);
+        combos.press(&mut state, 0, 2, 10);
+        combos.tick(&mut state, 40);
+        assert_eq!(state.keys()[0], [KC_NO; 5]);
+        combos.tick(&mut state, 50);
+        assert_eq!(state.keys()[0][1], KC_BACKSPACE);
+        combos.release(&mut state, 0, 1, 60);
+        combos.release(&mut state, 0, 2, 60);
+
+        combos.press(&mut state, 0, 1, 100);
+        combos.press(&mut state, 0, 2, 110);
+        combos.press(&mut state, 0, 3, 120);
+        assert_eq!(state.keys()[0], [KC_NO, KC_TAB, KC_NO, KC_NO, KC_NO]);
+    }
+
+    #[test]
+    fn combo_only_works_on_its_layer() {
+        let (mut combos, mut state) = setup();
+
+        combos.press(&mut state, 1, 0, 0);
+        combos.press(&mut state, 1, 1, 10);
+        assert_eq!(state.keys()[1], [KC_Z, KC_X, KC_NO, KC_NO, KC_NO]);
+        combos.release(&mut state, 1, 0, 20);
+        combos.release(&mut state, 1, 1, 20);
+
+        combos.press(&mut state, 0, 4, 30);
+        combos.press(&mut state, 1, 0, 40);
+        combos.press(&mut state, 1, 1, 50);
+        assert_eq!(state.keys()[1], [KC_ENTER, KC_NO, KC_NO, KC_NO, KC_NO]);
+    }
+}
@@ -1,39 +1,205 @@
 use embedded_hal::digital::v2::InputPin;
+use hardware::encoder::Dir;
 
-use crate::hardware::encoder::Encoder;
+use crate::config::Encoders;
+use crate::hardware::encoder::{Encoder, ReadError};
 use crate::keycode::Keycode;
 
+// the most taps an encoder can have waiting to be sent, more are dropped
+const MAX_QUEUED_TAPS: usize = 16;
+
+// taps waiting to be sent for one encoder, sent one at a time as a press and then a release
+#[derive(Clone, Copy)]
+struct Taps {
+    queue: [Keycode; MAX_QUEUED_TAPS],
+    // index of the oldest tap in the queue
+    start: usize,
+    len: usize,
+    // the key of the tap being sent, `KC_NO` once it's released
+    key: Keycode,
+    // when the press report was sent
+    pressed_at: Option<u32>,
+    // the key changed and the report hasn't been sent yet
+    pending: bool,
+}
+
+impl Taps {
+    const fn new() -> Self {
+        Self {
+            queue: [Keycode::KC_NO; MAX_QUEUED_TAPS],
+            start: 0,
+            len: 0,
+            key: Keycode::KC_NO,
+            pressed_at: None,
+            pending: false,
+        }
+    }
+
+    fn push(&mut self, keycode: Keycode) {
+        if keycode == Keycode::KC_NO || self.len == MAX_QUEUED_TAPS {
+            return;
+        }
+
+        self.queue[(self.start + self.len) % MAX_QUEUED_TAPS] = keycode;
+        self.len += 1;
+    }
+
+    // releases the tap being sent after `duration` and starts the next one once it's released
+    const fn tick(&mut self, now: u32, duration: u32) {
+        if self.pending {
+            return;
+        }
+
+        if let Some(pressed_at) = self.pressed_at {
+            if now.wrapping_sub(pressed_at) >= duration {
+                self.key = Keycode::KC_NO;
+                self.pressed_at = None;
+                self.pending = true;
+            }
+        } else if self.len > 0 {
+            self.key = self.queue[self.start];
+            self.start = (self.start + 1) % MAX_QUEUED_TAPS;
+            self.len -= 1;
+            self.pending = true;
+        }
+    }
+
+    fn sent(&mut self, now: u32) {
+        if self.pending {
+            self.pending = false;
+            if self.key != Keycode::KC_NO {
+                self.pressed_at = Some(now);
+            }
+        }
+    }
+}
+
 #[cfg(feature = "encoders")]
 pub(super) struct EncoderController<const NUM_OF_ENCODERS: usize, EncoderPin: InputPin> {
     encoders: [Encoder<EncoderPin>; NUM_OF_ENCODERS],
-    pub encoders_state: [Keycode; NUM_OF_ENCODERS],
+    taps: [Taps; NUM_OF_ENCODERS],
+    tap_duration: u32,
+    // ms of the last update
+    now: u32,
 }
 
 #[cfg(feature = "encoders")]
 impl<const NUM_OF_ENCODERS: usize, EncoderPin: InputPin>
     EncoderController<NUM_OF_ENCODERS, EncoderPin>
 {
-    pub(super) const fn new(encoders: [Encoder<EncoderPin>; NUM_OF_ENCODERS]) -> Self {
+    pub(super) const fn new(
+        encoders: [Encoder<EncoderPin>; NUM_OF_ENCODERS],
+        config: &Encoders,
+    ) -> Self {
         Self {
             encoders,
-            encoders_state: [Keycode::KC_A; NUM_OF_ENCODERS],
+            taps: [Taps::new(); NUM_OF_ENCODERS],
+            tap_duration: config.tap_duration,
+            now: 0,
         }
     }
 
-    pub(super) fn periodic(&mut self) {
-        self.encoders.iter_mut().for_each(|encoder| {
-            encoder.update();
-        });
+    // updates every encoder and queues the action of each detent on `layer` as a tap,
+    // returning the first error after trying them all
+    // `is_pressed` returns the state of a matrix position, for switches wired into the matrix
+    pub(super) fn periodic(
+        &mut self,
+        layer: usize,
+        now: u32,
+        is_pressed: impl Fn(usize, usize) -> bool + Copy,
+    ) -> Result<(), ReadError<EncoderPin::Error>> {
+        self.now = now;
+
+        let result = self
+            .encoders
+            .iter_mut()
+            .map(|encoder| encoder.update(now, is_pressed))
+            .fold(Ok(()), Result::and);
+
+        let actions = self.actions(layer);
+        for (taps, (action, count)) in self.taps.iter_mut().zip(actions) {
+            for _ in 0..count {
+                taps.push(action);
+            }
+            taps.tick(now, self.tap_duration);
+        }
+
+        result
     }
 
-    pub(super) fn actions(&self, layer: usize) -> [Keycode; NUM_OF_ENCODERS] {
-        let mut actions = [Keycode::KC_NO; NUM_OF_ENCODERS];
+    // returns the action of each encoder and how many times to tap it
+    fn actions(&self, layer: usize) -> [(Keycode, u32); NUM_OF_ENCODERS] {
+        let mut actions = [(Keycode::KC_NO, 0); NUM_OF_ENCODERS];
 
         self.encoders
             .iter()
-            .map(|encoder| encoder.action(layer))
+            .map(|encoder| match encoder.steps() {
+                0 => (encoder.click_action(layer), 1),
+                steps if steps > 0 => (encoder.turn_action(layer, Dir::Cw), steps.unsigned_abs()),
+                steps => (encoder.turn_action(layer, Dir::Cww), steps.unsigned_abs()),
+            })
             .enumerate()
             .for_each(|(index, action)| actions[index] = action);
         actions
     }
+
+    // the key held down by the tap of each encoder
+    pub(super) fn keys(&self) -> impl Iterator<Item = Keycode> + '_ {
+        self.taps.iter().map(|taps| taps.key)
+    }
+
+    // returns if a tap changed and has to be sent
+    pub(super) fn has_pending(&self) -> bool {
+        self.taps.iter().any(|taps| taps.pending)
+    }
+
+    // records if the report with the pending taps was sent
+    pub(super) fn sent(&mut self, sent: bool) {
+        if sent {
+            for taps in &mut self.taps {
+                taps.sent(self.now);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::cell::Cell;
+
+    use super::EncoderController;
+    use crate::config::Encoders;
+    use crate::hardware::Encoder;
+    use crate::keycode::{Keycode, Keycode::*};
+    use crate::testing::MockPin;
+
+    // one detent clockwise
+    const CW: [(bool, bool); 4] = [(true, false), (true, true), (false, true), (false, false)];
+
+    #[test]
+    fn fast_detents_are_queued_as_taps() {
+        let (a, b) = (Cell::new(false), Cell::new(false));
+        let mut controller = EncoderController::new(
+            [Encoder::new(MockPin::new(&a), MockPin::new(&b), &[[KC_A, KC_B]])],
+            &Encoders { tap_duration: 5 },
+        );
+
+        // three detents before anything is sent
+        for (level_a, level_b) in CW.repeat(3) {
+            a.set(level_a);
+            b.set(level_b);
+            controller.periodic(0, 0, |_, _| false).unwrap();
+        }
+
+        let mut reports: Vec<Keycode> = Vec::new();
+        for now in 0..100 {
+            controller.periodic(0, now, |_, _| false).unwrap();
+            if controller.has_pending() {
+                reports.extend(controller.keys());
+                controller.sent(true);
+            }
+        }
+
+        assert!(reports == [KC_B, KC_NO, KC_B, KC_NO, KC_B, KC_NO]);
+    }
 }
@@ -0,0 +1,205 @@
+// hid devices not provided by usbd-human-interface-device
+use fugit::ExtU32;
+use usb_device::class_prelude::{UsbBus as UsbBusTrait, UsbBusAllocator};
+use usbd_human_interface_device::usb_class::prelude::{
+    DeviceClass, InBytes8, Interface, InterfaceBuilder, InterfaceConfig, OutNone, ReportSingle,
+    UsbAllocatable, UsbHidError,
+};
+
+// the most consumer keys reported at once
+pub(super) const CONSUMER_SLOTS: usize = 4;
+
+// four consumer page usages as an array, like the one from usbd-human-interface-device but
+// covering usages up to 0x3FF so the brightness, assistant and launchpad keys fit
+#[rustfmt::skip]
+const CONSUMER_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x0C, // Usage Page (Consumer),
+    0x09, 0x01, // Usage (Consumer Control),
+    0xA1, 0x01, // Collection (Application),
+    0x75, 0x10, //     Report Size(16)
+    0x95, 0x04, //     Report Count(4)
+    0x15, 0x00, //     Logical Minimum(0)
+    0x26, 0xFF, 0x03, //     Logical Maximum(0x03FF)
+    0x19, 0x00, //     Usage Minimum(0)
+    0x2A, 0xFF, 0x03, //     Usage Maximum(0x03FF)
+    0x81, 0x00, //     Input (Data, Array, Absolute)
+    0xC0, // End Collection
+];
+
+// one generic desktop system control usage, 0 when nothing is pressed
+// the logical range takes two bytes, one byte items are signed so 0x81 would be -127
+#[rustfmt::skip]
+const SYSTEM_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01, // Usage Page (Generic Desktop),
+    0x09, 0x80, // Usage (System Control),
+    0xA1, 0x01, // Collection (Application),
+    0x75, 0x08, //     Report Size(8)
+    0x95, 0x01, //     Report Count(1)
+    0x16, 0x81, 0x00, //     Logical Minimum(0x81)
+    0x26, 0x83, 0x00, //     Logical Maximum(0x83)
+    0x19, 0x81, //     Usage Minimum(System Power Down)
+    0x29, 0x83, //     Usage Maximum(System Wake Up)
+    0x81, 0x00, //     Input (Data, Array, Absolute)
+    0xC0, // End Collection
+];
+
+type HidInterface<'a, Usb> = Interface<'a, Usb, InBytes8, OutNone, ReportSingle>;
+
+fn interface_config<'a>(
+    descriptor: &'a [u8],
+    description: &'a str,
+) -> InterfaceConfig<'a, InBytes8, OutNone, ReportSingle> {
+    InterfaceBuilder::new(descriptor)
+        .unwrap()
+        .description(description)
+        .in_endpoint(10.millis())
+        .unwrap()
+        .without_out_endpoint()
+        .build()
+}
+
+pub(super) struct Consumer<'a, Usb: UsbBusTrait> {
+    interface: HidInterface<'a, Usb>,
+}
+
+impl<Usb: UsbBusTrait> Consumer<'_, Usb> {
+    pub(super) fn write_report(&mut self, codes: &[u16; CONSUMER_SLOTS]) -> Result<(), UsbHidError> {
+        let mut report = [0; CONSUMER_SLOTS * 2];
+        for (bytes, code) in report.chunks_exact_mut(2).zip(codes) {
+            bytes.copy_from_slice(&code.to_le_bytes());
+        }
+
+        self.interface
+            .write_report(&report)
+            .map(|_| ())
+            .map_err(UsbHidError::from)
+    }
+}
+
+impl<'a, Usb: UsbBusTrait> DeviceClass<'a> for Consumer<'a, Usb> {
+    type I = HidInterface<'a, Usb>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        &mut self.interface
+    }
+
+    fn reset(&mut self) {}
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        Ok(())
+    }
+}
+
+pub(super) struct ConsumerConfig;
+
+impl<'a, Usb: UsbBusTrait + 'a> UsbAllocatable<'a, Usb> for ConsumerConfig {
+    type Allocated = Consumer<'a, Usb>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<Usb>) -> Self::Allocated {
+        Consumer {
+            interface: Interface::new(
+                usb_alloc,
+                interface_config(CONSUMER_REPORT_DESCRIPTOR, "Consumer Control"),
+            ),
+        }
+    }
+}
+
+pub(super) struct SystemControl<'a, Usb: UsbBusTrait> {
+    interface: HidInterface<'a, Usb>,
+}
+
+impl<Usb: UsbBusTrait> SystemControl<'_, Usb> {
+    // `usage` is 0 when nothing is pressed
+    pub(super) fn write_report(&mut self, usage: u8) -> Result<(), UsbHidError> {
+        self.interface
+            .write_report(&[usage])
+            .map(|_| ())
+            .map_err(UsbHidError::from)
+    }
+}
+
+impl<'a, Usb: UsbBusTrait> DeviceClass<'a> for SystemControl<'a, Usb> {
+    type I = HidInterface<'a, Usb>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        &mut self.interface
+    }
+
+    fn reset(&mut self) {}
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        Ok(())
+    }
+}
+
+pub(super) struct SystemControlConfig;
+
+impl<'a, Usb: UsbBusTrait + 'a> UsbAllocatable<'a, Usb> for SystemControlConfig {
+    type Allocated = SystemControl<'a, Usb>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<Usb>) -> Self::Allocated {
+        SystemControl {
+            interface: Interface::new(
+                usb_alloc,
+                interface_config(SYSTEM_REPORT_DESCRIPTOR, "System Control"),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CONSUMER_REPORT_DESCRIPTOR, SYSTEM_REPORT_DESCRIPTOR};
+
+    const LOGICAL_MINIMUM: u8 = 0x14;
+    const LOGICAL_MAXIMUM: u8 = 0x24;
+    const USAGE_MINIMUM: u8 = 0x18;
+    const USAGE_MAXIMUM: u8 = 0x28;
+
+    // the data of the short item with `tag`, signed like logical values
+    fn signed(descriptor: &[u8], tag: u8) -> i32 {
+        let data = item(descriptor, tag);
+        match data.len() {
+            1 => i32::from(data[0] as i8),
+            2 => i32::from(i16::from_le_bytes([data[0], data[1]])),
+            _ => i32::from_le_bytes(data.try_into().unwrap()),
+        }
+    }
+
+    // the data of the short item with `tag`, unsigned like usages
+    fn unsigned(descriptor: &[u8], tag: u8) -> i32 {
+        item(descriptor, tag)
+            .iter()
+            .rev()
+            .fold(0, |value, byte| value << 8 | i32::from(*byte))
+    }
+
+    fn item(mut descriptor: &[u8], tag: u8) -> &[u8] {
+        while let Some((prefix, rest)) = descriptor.split_first() {
+            let size = [0, 1, 2, 4][usize::from(prefix & 0x03)];
+            if prefix & 0xFC == tag {
+                return &rest[..size];
+            }
+            descriptor = &rest[size..];
+        }
+        panic!("no item with tag {tag:#04x}");
+    }
+
+    #[test]
+    fn logical_range_matches_the_usages() {
+        for descriptor in [CONSUMER_REPORT_DESCRIPTOR, SYSTEM_REPORT_DESCRIPTOR] {
+            assert_eq!(
+                signed(descriptor, LOGICAL_MINIMUM),
+                unsigned(descriptor, USAGE_MINIMUM)
+            );
+            assert_eq!(
+                signed(descriptor, LOGICAL_MAXIMUM),
+                unsigned(descriptor, USAGE_MAXIMUM)
+            );
+        }
+
+        assert_eq!(signed(SYSTEM_REPORT_DESCRIPTOR, LOGICAL_MINIMUM), 0x81);
+        assert_eq!(signed(SYSTEM_REPORT_DESCRIPTOR, LOGICAL_MAXIMUM), 0x83);
+    }
+}
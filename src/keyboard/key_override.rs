@@ -0,0 +1,126 @@
+use core::iter;
+
+use crate::config::KeyOverride;
+use crate::keycode::Keycode;
+
+// the bit of a modifier in the report's modifier byte, the same for the left and right keys
+const fn modifier_bit(keycode: Keycode) -> u8 {
+    match keycode.into_keyboard() {
+        Some(usage @ 0xE0..=0xE7) => 1 << ((usage - 0xE0) % 4),
+        _ => 0,
+    }
+}
+
+fn modifier_bits(keys: &[Keycode]) -> u8 {
+    keys.iter().fold(0, |bits, key| bits | modifier_bit(*key))
+}
+
+// a key of the report, `KEYS_2` is split into its two keys
+fn split(key: &Keycode) -> impl Iterator<Item = &Keycode> + Clone {
+    let (first, second) = match key {
+        Keycode::KEYS_2(first, second) => (*first, Some(*second)),
+        _ => (key, None),
+    };
+
+    iter::once(first).chain(second)
+}
+
+// swaps keys for their replacement while the modifiers of an override are held
+pub(super) struct KeyOverrides {
+    overrides: &'static [KeyOverride],
+}
+
+impl KeyOverrides {
+    pub(super) const fn new(overrides: &'static [KeyOverride]) -> Self {
+        Self { overrides }
+    }
+
+    // the keys of a report with the overridden keys replaced and the modifiers that triggered
+    // them dropped, the modifiers held are read from every key of the report, macros included
+    pub(super) fn apply<'k>(
+        &'k self,
+        keys: impl Iterator<Item = &'k Keycode> + Clone + 'k,
+        layer: usize,
+    ) -> impl Iterator<Item = &'k Keycode> + Clone + 'k {
+        let keys = keys.flat_map(split);
+        let held = keys.clone().fold(0, |bits, key| bits | modifier_bit(*key));
+        let triggered = keys
+            .clone()
+            .filter_map(|key| self.find(*key, held, layer))
+            .fold(0, |triggered, key_override| {
+                triggered | modifier_bits(key_override.modifiers)
+            });
+
+        keys.map(move |key| {
+            if modifier_bit(*key) & triggered != 0 {
+                &Keycode::KC_NO
+            } else if let Some(key_override) = self.find(*key, held, layer) {
+                &key_override.replacement
+            } else {
+                key
+            }
+        })
+    }
+
+    // the override of the key on the layer with its modifiers held
+    fn find(&self, key: Keycode, held: u8, layer: usize) -> Option<&'static KeyOverride> {
+        self.overrides.iter().find(|key_override| {
+            let modifiers = modifier_bits(key_override.modifiers);
+
+            key_override.key == key
+                && key_override.layer.is_none_or(|only| only == layer)
+                && held & modifiers == modifiers
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::KeyOverrides;
+    use crate::config::KeyOverride;
+    use crate::keycode::Keycode::{self, *};
+
+    const OVERRIDES: &[KeyOverride] = &[
+        KeyOverride::new(&[KC_LEFT_SHIFT], KC_BACKSPACE, KC_DELETE_FORWARD),
+        KeyOverride {
+            layer: Some(1),
+            ..KeyOverride::new(&[KC_LEFT_SHIFT], KC_COMMA, KC_SEMICOLON)
+        },
+    ];
+
+    // the keys of the report for `keys` on `layer`
+    fn apply(keys: &[Keycode], layer: usize) -> Vec<Keycode> {
+        KeyOverrides::new(OVERRIDES)
+            .apply(keys.iter(), layer)
+            .copied()
+            .collect()
+    }
+
+    #[test]
+    fn modifier_is_replaced_with_the_key() {
+        let keys = apply(&[KC_RIGHT_SHIFT, KC_LEFT_CTRL, KC_BACKSPACE], 0);
+        assert_eq!(keys, [KC_NO, KC_LEFT_CTRL, KC_DELETE_FORWARD]);
+
+        let keys = apply(&[KC_LEFT_CTRL, KC_BACKSPACE, KC_A], 0);
+        assert_eq!(keys, [KC_LEFT_CTRL, KC_BACKSPACE, KC_A]);
+    }
+
+    #[test]
+    fn override_only_works_on_its_layer() {
+        let keys = apply(&[KC_LEFT_SHIFT, KC_COMMA], 0);
+        assert_eq!(keys, [KC_LEFT_SHIFT, KC_COMMA]);
+
+        let keys = apply(&[KC_LEFT_SHIFT, KC_COMMA], 1);
+        assert_eq!(keys, [KC_NO, KC_SEMICOLON]);
+    }
+
+    #[test]
+    fn modifier_of_a_keys_2_triggers_an_override() {
+        let keys = apply(&[KEYS_2(&KC_LEFT_SHIFT, &KC_A), KC_BACKSPACE], 0);
+        assert_eq!(keys, [KC_NO, KC_A, KC_DELETE_FORWARD]);
+
+        // a macro holding shift
+        let keys = apply(&[KC_BACKSPACE, KC_RIGHT_SHIFT], 0);
+        assert_eq!(keys, [KC_DELETE_FORWARD, KC_NO]);
+    }
+}
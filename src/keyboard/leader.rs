@@ -0,0 +1,209 @@
+use crate::config::{self, Sequence};
+use crate::keycode::Keycode;
+use crate::tap_dance::Action;
+
+// the most keys of a sequence
+const MAX_KEYS: usize = 8;
+
+// the keys typed after `KC_LEAD`
+#[derive(Clone, Copy)]
+struct Typed {
+    keys: [Keycode; MAX_KEYS],
+    len: usize,
+    // when the last key was typed
+    typed_at: u32,
+    // the position of the last key typed
+    row: usize,
+    col: usize,
+}
+
+impl Typed {
+    fn keys(&self) -> &[Keycode] {
+        &self.keys[..self.len]
+    }
+}
+
+pub(super) struct Leader {
+    sequences: &'static [Sequence],
+    timeout: u32,
+    // `None` when no sequence is being typed
+    typed: Option<Typed>,
+}
+
+impl Leader {
+    pub(super) const fn new(config: &config::Leader) -> Self {
+        Self {
+            sequences: config.sequences,
+            timeout: config.timeout,
+            typed: None,
+        }
+    }
+
+    pub(super) const fn start(&mut self, row: usize, col: usize, now: u32) {
+        self.typed = Some(Typed {
+            keys: [Keycode::KC_NO; MAX_KEYS],
+            len: 0,
+            typed_at: now,
+            row,
+            col,
+        });
+    }
+
+    pub(super) const fn is_leading(&self) -> bool {
+        self.typed.is_some()
+    }
+
+    // adds a key to the sequence, returning the action of the sequence once it's typed
+    //
+    // a sequence that is the start of a longer one waits for the timeout
+    pub(super) fn capture(
+        &mut self,
+        keycode: Keycode,
+        row: usize,
+        col: usize,
+        now: u32,
+    ) -> Option<Action> {
+        let typed = self.typed.as_mut()?;
+        if typed.len == MAX_KEYS {
+            self.typed = None;
+            return None;
+        }
+
+        typed.keys[typed.len] = keycode;
+        typed.len += 1;
+        typed.typed_at = now;
+        (typed.row, typed.col) = (row, col);
+
+        let typed = *typed;
+        let mut candidates = self
+            .sequences
+            .iter()
+            .filter(|sequence| sequence.keys.starts_with(typed.keys()));
+        let Some(first) = candidates.next() else {
+            // no sequence can match anymore
+            self.typed = None;
+            return None;
+        };
+
+        if first.keys == typed.keys() && candidates.next().is_none() {
+            self.typed = None;
+            return Some(first.action);
+        }
+
+        None
+    }
+
+    // stops the sequence after the timeout, returning the action and the position of the last key
+    // if the keys typed are a sequence
+    pub(super) fn tick(&mut self, now: u32) -> Option<(Action, usize, usize)> {
+        let typed = self.typed?;
+        if now.wrapping_sub(typed.typed_at) < self.timeout {
+            return None;
+        }
+
+        self.typed = None;
+        self.sequences
+            .iter()
+            .find(|sequence| sequence.keys == typed.keys())
+            .map(|sequence| (sequence.action, typed.row, typed.col))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::{self, Config, Sequence};
+    use crate::keyboard::State;
+    use crate::keycode::{Keycode, Keycode::*};
+    use crate::macros::MacroStep;
+    use crate::tap_dance::Action;
+
+    #[rustfmt::skip]
+    const KEYS: &[[[Keycode; 4]; 1]] = &[
+        [
+            [KC_LEAD, KC_G, KC_S, KC_T],
+        ],
+    ];
+
+    const GIT_STATUS: &[MacroStep] = &[MacroStep::Type("git status")];
+
+    const SEQUENCES: &[Sequence] = &[
+        Sequence::new(&[KC_G, KC_S], Action::Key(KC_MACRO(GIT_STATUS))),
+        Sequence::new(&[KC_G], Action::Key(KC_ESCAPE)),
+        Sequence::new(&[KC_T, KC_T], Action::Key(KC_TAB)),
+    ];
+
+    fn setup() -> State<'static, 4, 1> {
+        let config = Config {
+            leader: config::Leader {
+                sequences: SEQUENCES,
+                timeout: 300,
+            },
+            ..Config::new()
+        };
+        State::new(KEYS, &config)
+    }
+
+    // presses and releases the key
+    fn tap(state: &mut State<4, 1>, col: usize, now: u32) {
+        state.press(0, col, now);
+        state.release(0, col, now + 10);
+    }
+
+    #[test]
+    fn sequence_runs_its_action() {
+        let mut state = setup();
+
+        tap(&mut state, 0, 0);
+        state.press(0, 1, 100);
+        assert!(state.keys()[0] == [KC_NO; 4]);
+        state.press(0, 2, 200);
+        assert!(state.keys()[0] == [KC_NO, KC_NO, KC_MACRO(GIT_STATUS), KC_NO]);
+        assert!(state.take_macro() == Some(GIT_STATUS));
+
+        state.tick(210);
+        state.release(0, 1, 220);
+        state.release(0, 2, 230);
+        assert!(state.keys()[0] == [KC_NO; 4]);
+
+        // the sequence is over
+        state.press(0, 1, 300);
+        assert!(state.keys()[0][1] == KC_G);
+    }
+
+    #[test]
+    fn start_of_longer_sequence_runs_after_timeout() {
+        let mut state = setup();
+
+        tap(&mut state, 0, 0);
+        tap(&mut state, 1, 100);
+        // every key resets the timeout
+        state.tick(350);
+        assert!(state.keys()[0] == [KC_NO; 4]);
+
+        state.tick(410);
+        assert!(state.keys()[0][1] == KC_ESCAPE);
+    }
+
+    #[test]
+    fn keys_not_in_a_sequence_stop_it() {
+        let mut state = setup();
+
+        tap(&mut state, 0, 0);
+        tap(&mut state, 2, 100);
+        assert!(state.keys()[0] == [KC_NO; 4]);
+
+        state.press(0, 3, 200);
+        assert!(state.keys()[0][3] == KC_T);
+    }
+
+    #[test]
+    fn unfinished_sequence_times_out() {
+        let mut state = setup();
+
+        tap(&mut state, 0, 0);
+        tap(&mut state, 3, 100);
+        state.tick(410);
+        state.press(0, 3, 500);
+        assert!(state.keys()[0][3] == KC_T);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::keycode::Keycode;
+use crate::macros::{from_ascii, MacroStep};
+
+// the most keys a macro can hold down at once
+const MAX_HELD_KEYS: usize = 8;
+
+// plays a macro one report at a time
+pub(super) struct MacroPlayer {
+    steps: &'static [MacroStep],
+    step: usize,
+    // index of the next character of a `MacroStep::Type`
+    char: usize,
+    // the current key of a tap is pressed and has to be released
+    tapping: bool,
+    wait_until: Option<u32>,
+    // the last report was sent, so the next step can be played
+    sent: bool,
+    held: [Keycode; MAX_HELD_KEYS],
+}
+
+impl MacroPlayer {
+    pub(super) const fn new() -> Self {
+        Self {
+            steps: &[],
+            step: 0,
+            char: 0,
+            tapping: false,
+            wait_until: None,
+            sent: true,
+            held: [Keycode::KC_NO; MAX_HELD_KEYS],
+        }
+    }
+
+    // starts playing a macro, replacing the one playing
+    pub(super) const fn play(&mut self, steps: &'static [MacroStep]) {
+        *self = Self {
+            steps,
+            ..Self::new()
+        };
+    }
+
+    pub(super) fn is_playing(&self) -> bool {
+        self.step < self.steps.len() || self.held.iter().any(|key| *key != Keycode::KC_NO)
+    }
+
+    // the keys held down by the macro
+    pub(super) const fn keys(&self) -> &[Keycode] {
+        &self.held
+    }
+
+    // records if the report for the last step was sent
+    pub(super) const fn sent(&mut self, sent: bool) {
+        self.sent = sent;
+    }
+
+    // plays steps until the keys held change or the macro has to wait
+    pub(super) fn step(&mut self, now: u32) {
+        if !self.sent {
+            return;
+        }
+
+        if let Some(wait_until) = self.wait_until {
+            // wrapping difference, so waits survive the ms counter overflowing
+            if now.wrapping_sub(wait_until) > u32::MAX / 2 {
+                return;
+            }
+            self.wait_until = None;
+        }
+
+        while let Some(step) = self.steps.get(self.step) {
+            match *step {
+                MacroStep::Tap(keycode) => {
+                    self.tap(keycode, false);
+                    if !self.tapping {
+                        self.step += 1;
+                    }
+                    return;
+                }
+                MacroStep::Press(keycode) => {
+                    self.press(keycode);
+                    self.step += 1;
+                    return;
+                }
+                MacroStep::Release(keycode) => {
+                    self.release(keycode);
+                    self.step += 1;
+                    return;
+                }
+                MacroStep::Delay(ms) => {
+                    self.step += 1;
+                    self.wait_until = Some(now.wrapping_add(ms));
+                    return;
+                }
+                MacroStep::Type(text) => {
+                    let Some(ascii) = text.as_bytes().get(self.char) else {
+                        self.char = 0;
+                        self.step += 1;
+                        continue;
+                    };
+
+                    let Some((keycode, shift)) = from_ascii(*ascii) else {
+                        self.char += 1;
+                        continue;
+                    };
+
+                    self.tap(keycode, shift);
+                    if !self.tapping {
+                        self.char += 1;
+                    }
+                    return;
+                }
+            }
+        }
+
+        // release anything left held once the macro is done
+        self.held = [Keycode::KC_NO; MAX_HELD_KEYS];
+    }
+
+    // presses the key on the first call and releases it on the second
+    fn tap(&mut self, keycode: Keycode, shift: bool) {
+        if self.tapping {
+            self.release(keycode);
+            if shift {
+                self.release(Keycode::KC_LEFT_SHIFT);
+            }
+        } else {
+            if shift {
+                self.press(Keycode::KC_LEFT_SHIFT);
+            }
+            self.press(keycode);
+        }
+        self.tapping = !self.tapping;
+    }
+
+    fn press(&mut self, keycode: Keycode) {
+        if let Some(slot) = self.held.iter_mut().find(|key| **key == Keycode::KC_NO) {
+            *slot = keycode;
+        }
+    }
+
+    fn release(&mut self, keycode: Keycode) {
+        if let Some(slot) = self.held.iter_mut().find(|key| **key == keycode) {
+            *slot = Keycode::KC_NO;
+        }
+    }
+}
@@ -1,24 +1,140 @@
-use crate::{debounce::debounce_countdown, keycode::Keycode};
-use embedded_hal::{
-    digital::v2::{InputPin, OutputPin},
-    timer::CountDown,
-};
+//! Scanners reading the keys of a [`Keyboard`](crate::Keyboard).
+//!
+//! [`Keyboard::new`](crate::Keyboard::new) scans a [`Matrix`] of rows and columns, other
+//! scanners like a [`DuplexMatrix`] or [`RoundRobinMatrix`] are passed to
+//! [`Keyboard::from_scanner`](crate::Keyboard::from_scanner).
+
+use crate::clock::Clock;
+use crate::config::{ActiveLevel, MatrixConfig};
+use crate::debounce::Debouncer;
+use crate::error::KeyboardError;
+use embedded_hal::digital::v2::{InputPin, OutputPin};
 
 use super::State;
 
-pub(super) struct Matrix<
+mod duplex;
+mod flex_pin;
+mod round_robin;
+
+pub use duplex::DuplexMatrix;
+pub use flex_pin::FlexPin;
+pub use round_robin::RoundRobinMatrix;
+
+/// Receives the debounced changes of a scan.
+pub trait Events {
+    /// Called before every scan.
+    fn tick(&mut self, now: u32);
+    fn press(&mut self, row: usize, col: usize, now: u32);
+    fn release(&mut self, row: usize, col: usize, now: u32);
+}
+
+impl<const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize> Events
+    for State<'_, NUM_OF_COLS, NUM_OF_ROWS>
+{
+    fn tick(&mut self, now: u32) {
+        Self::tick(self, now);
+    }
+
+    fn press(&mut self, row: usize, col: usize, now: u32) {
+        Self::press(self, row, col, now);
+    }
+
+    fn release(&mut self, row: usize, col: usize, now: u32) {
+        Self::release(self, row, col, now);
+    }
+}
+
+/// Scans the keys of a [`Keyboard`](crate::Keyboard), like a [`Matrix`] of rows and columns, a
+/// [`DuplexMatrix`] or a [`RoundRobinMatrix`].
+pub trait Scanner {
+    type OutputError;
+    type InputError;
+
+    /// Scans the keys if it is time to, sending the debounced changes to `events`.
+    ///
+    /// Returns if the keys were scanned.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first pin that failed, the rest of the keys are still scanned.
+    fn scan<EncoderError>(
+        &mut self,
+        events: &mut impl Events,
+    ) -> Result<bool, KeyboardError<Self::OutputError, Self::InputError, EncoderError>>;
+
+    /// Returns if the key at a position is pressed after debouncing.
+    fn is_pressed(&self, row: usize, col: usize) -> bool;
+
+    /// Returns the ms since the scanner was created, read from its clock by the last scan.
+    fn now(&self) -> u32;
+}
+
+// the debounced keys and the clock shared by every scanner
+struct Keys<'a, const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize> {
+    pressed: [[bool; NUM_OF_COLS]; NUM_OF_ROWS],
+    clock: &'a dyn Clock,
+    // the time of the clock when the keys were created
+    started_at: u32,
+    debouncer: Debouncer<NUM_OF_COLS, NUM_OF_ROWS>,
+    // ms since the keys were created
+    now: u32,
+    // the keys are scanned once per ms
+    scanned_at: Option<u32>,
+}
+
+impl<'a, const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize> Keys<'a, NUM_OF_COLS, NUM_OF_ROWS> {
+    fn new(clock: &'a dyn Clock, debouncer: Debouncer<NUM_OF_COLS, NUM_OF_ROWS>) -> Self {
+        Self {
+            pressed: [[false; NUM_OF_COLS]; NUM_OF_ROWS],
+            clock,
+            started_at: clock.now(),
+            debouncer,
+            now: 0,
+            scanned_at: None,
+        }
+    }
+
+    // reads the clock and returns if the keys should be scanned
+    fn tick(&mut self, events: &mut impl Events) -> bool {
+        self.now = self.clock.now().wrapping_sub(self.started_at);
+
+        if self.scanned_at == Some(self.now) {
+            return false;
+        }
+        self.scanned_at = Some(self.now);
+
+        events.tick(self.now);
+        true
+    }
+
+    // debounces the level read for a key and sends it to `events` if it changed
+    fn update(&mut self, row: usize, col: usize, raw: bool, events: &mut impl Events) {
+        let result = self.debouncer.update(row, col, raw, self.now);
+
+        if result != self.pressed[row][col] {
+            self.pressed[row][col] = result;
+
+            if result {
+                events.press(row, col, self.now);
+            } else {
+                events.release(row, col, self.now);
+            }
+        }
+    }
+}
+
+/// A matrix of output pins driving input pins through a diode on every key.
+pub struct Matrix<
     'a,
     const NUM_OF_COLS: usize,
     const NUM_OF_ROWS: usize,
     Output: OutputPin,
     Input: InputPin,
-    Timer: CountDown,
 > {
-    pub state: [[Keycode; NUM_OF_COLS]; NUM_OF_ROWS],
+    keys: Keys<'a, NUM_OF_COLS, NUM_OF_ROWS>,
     output_pins: &'a mut [Output],
     input_pins: &'a mut [Input],
-    timer: &'a mut Timer,
-    timer_debounce: &'a mut Timer,
+    config: MatrixConfig,
 }
 
 impl<
@@ -27,84 +143,253 @@ impl<
         const NUM_OF_ROWS: usize,
         Output: OutputPin,
         Input: InputPin,
-        Timer: CountDown,
-    > Matrix<'a, NUM_OF_COLS, NUM_OF_ROWS, Output, Input, Timer>
+    > Matrix<'a, NUM_OF_COLS, NUM_OF_ROWS, Output, Input>
 {
-    pub(super) const fn new(
+    pub(super) fn new(
         output_pins: &'a mut [Output],
         input_pins: &'a mut [Input],
-        timer: &'a mut Timer,
-        timer_debounce: &'a mut Timer,
+        clock: &'a dyn Clock,
+        debouncer: Debouncer<NUM_OF_COLS, NUM_OF_ROWS>,
+        config: MatrixConfig,
     ) -> Self {
+        // outputs idle at the inactive level
+        // a pin that fails here is reported by the first scan
+        for output_pin in output_pins.iter_mut() {
+            let _ = set_level(output_pin, config.active_level, false);
+        }
+
         Self {
-            state: [[Keycode::KC_NO; NUM_OF_COLS]; NUM_OF_ROWS],
+            keys: Keys::new(clock, debouncer),
             output_pins,
             input_pins,
-            timer,
-            timer_debounce,
+            config,
         }
     }
+}
 
-    // scans the matrix
-    // returns if the matrix has changed
-    pub(super) fn scan(&mut self, state: &mut State<NUM_OF_COLS, NUM_OF_ROWS>) -> bool where Timer::Time: From<fugit::Duration<u32, 1, 1000000>> {
-        if self.should_scan() {
-            false
-        } else {
-            let mut has_changed = false;
-
-            self.output_pins
-                .iter_mut()
-                .enumerate()
-                .for_each(|(output_index, output_pin)| {
-                    if output_pin.set_high().is_err() {
-                        panic!("");
-                    }
+impl<
+        const NUM_OF_COLS: usize,
+        const NUM_OF_ROWS: usize,
+        Output: OutputPin,
+        Input: InputPin,
+    > Scanner for Matrix<'_, NUM_OF_COLS, NUM_OF_ROWS, Output, Input>
+{
+    type OutputError = Output::Error;
+    type InputError = Input::Error;
+
+    fn scan<EncoderError>(
+        &mut self,
+        events: &mut impl Events,
+    ) -> Result<bool, KeyboardError<Output::Error, Input::Error, EncoderError>> {
+        if !self.keys.tick(events) {
+            return Ok(false);
+        }
+
+        let mut has_changed = false;
+        // the first pins that failed, the rest of the matrix is still scanned
+        let mut output_error = None;
+        let mut input_error = None;
+
+        let active_level = self.config.active_level;
+        let outputs_are_cols = self.config.outputs_are_cols();
 
-                    self.input_pins
-                        .iter_mut()
-                        .enumerate()
-                        .for_each(|(input_index, input_pin)| {
-                            let mut result: bool = false;
-
-                            if input_pin.is_high().is_ok() {
-                                result = debounce_countdown(|| input_pin.is_high().map_or_else(|_| panic!("error is high"), |i| i), self.timer_debounce);
-                            }
-
-                            // input_pin
-                            //     .is_high()
-                            //     .map_or_else(|_| panic!(""), |result_input| result = result_input);
-
-                            if !has_changed {
-                                has_changed = true;
-                            }
-
-                            if result {
-                                let key = state.get_key(input_index, output_index);
-                                if self.state[input_index][output_index] != key {
-                                    state.on_press(key, input_index, output_index);
-                                    self.state[input_index][output_index] = key;
-                                }
-                            } else if self.state[input_index][output_index] != Keycode::KC_NO {
-                                state.on_release(
-                                    self.state[input_index][output_index],
-                                    input_index,
-                                    output_index,
-                                );
-                                self.state[input_index][output_index] = Keycode::KC_NO;
-                            }
-                        });
-
-                    if output_pin.set_low().is_err() {
-                        panic!("");
+        for (output_index, output_pin) in self.output_pins.iter_mut().enumerate() {
+            if let Err(error) = set_level(output_pin, active_level, true) {
+                // back to the inactive level so its keys don't read as pressed
+                let _ = set_level(output_pin, active_level, false);
+                output_error.get_or_insert(error);
+                continue;
+            }
+
+            for (input_index, input_pin) in self.input_pins.iter_mut().enumerate() {
+                let (row, col) = if outputs_are_cols {
+                    (input_index, output_index)
+                } else {
+                    (output_index, input_index)
+                };
+
+                let raw = match active_level {
+                    ActiveLevel::High => input_pin.is_high(),
+                    ActiveLevel::Low => input_pin.is_low(),
+                };
+                // a key that can't be read keeps its last state
+                let raw = match raw {
+                    Ok(raw) => raw,
+                    Err(error) => {
+                        input_error.get_or_insert(error);
+                        continue;
                     }
-                });
-            has_changed
+                };
+
+                if !has_changed {
+                    has_changed = true;
+                }
+
+                self.keys.update(row, col, raw, events);
+            }
+
+            if let Err(error) = set_level(output_pin, active_level, false) {
+                output_error.get_or_insert(error);
+            }
         }
+
+        if let Some(error) = output_error {
+            return Err(KeyboardError::Output(error));
+        }
+        input_error.map_or(Ok(has_changed), |error| Err(KeyboardError::Input(error)))
+    }
+
+    fn is_pressed(&self, row: usize, col: usize) -> bool {
+        self.keys.pressed[row][col]
+    }
+
+    fn now(&self) -> u32 {
+        self.keys.now
+    }
+}
+
+// drives an output pin to the active level, or the inactive level if `active` is false
+fn set_level<Output: OutputPin>(
+    output_pin: &mut Output,
+    active_level: ActiveLevel,
+    active: bool,
+) -> Result<(), Output::Error> {
+    if active == (active_level == ActiveLevel::High) {
+        output_pin.set_high()
+    } else {
+        output_pin.set_low()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use embedded_hal::digital::v2::InputPin;
+
+    use super::{Matrix, Scanner, State};
+    use crate::config::{ActiveLevel, Config, DiodeDirection, MatrixConfig};
+    use crate::debounce::{Algorithm, Debouncer};
+    use crate::error::KeyboardError;
+    use crate::keycode::{Keycode, Keycode::*};
+    use crate::testing::{MockClock, MockInputPin, MockMatrix, MockOutputPin, MockPinError};
+
+    #[rustfmt::skip]
+    const KEYS: &[[[Keycode; 3]; 2]] = &[
+        [
+            [KC_A, KC_B, KC_C],
+            [KC_D, KC_E, KC_F],
+        ],
+    ];
+
+    // presses the key at row 1, col 2, scans once and returns the keys sent
+    fn scan_pressed(
+        mock: &MockMatrix<3, 2>,
+        output_pins: &mut [MockOutputPin<3, 2>],
+        input_pins: &mut [MockInputPin<3, 2>],
+        diode_direction: DiodeDirection,
+        active_level: ActiveLevel,
+    ) -> [[Keycode; 3]; 2] {
+        let clock = MockClock::new();
+
+        let mut matrix = Matrix::<3, 2, _, _>::new(
+            output_pins,
+            input_pins,
+            &clock,
+            Debouncer::new(Algorithm::SymmetricDefer, 0),
+            MatrixConfig {
+                diode_direction,
+                active_level,
+            },
+        );
+        let mut state = State::new(KEYS, &Config::new());
+
+        mock.press(1, 2);
+        matrix.scan::<()>(&mut state).unwrap();
+        *state.keys()
+    }
+
+    fn only_f_pressed(keys: [[Keycode; 3]; 2]) -> bool {
+        keys.iter().flatten().enumerate().all(|(index, keycode)| {
+            if index == 5 {
+                *keycode == KC_F
+            } else {
+                *keycode == KC_NO
+            }
+        })
     }
 
-    // checks if the matrix should be scanned
-    fn should_scan(&mut self) -> bool {
-        self.timer.wait().is_ok()
+    #[test]
+    fn col2row_active_high() {
+        let mock = MockMatrix::new();
+        let keys = scan_pressed(
+            &mock,
+            &mut mock.output_pins(),
+            &mut mock.input_pins(),
+            DiodeDirection::Col2Row,
+            ActiveLevel::High,
+        );
+        assert!(only_f_pressed(keys));
+    }
+
+    #[test]
+    fn row2col_active_high() {
+        let mock = MockMatrix::new();
+        let keys = scan_pressed(
+            &mock,
+            &mut mock.row_output_pins(),
+            &mut mock.col_input_pins(),
+            DiodeDirection::Row2Col,
+            ActiveLevel::High,
+        );
+        assert!(only_f_pressed(keys));
+    }
+
+    #[test]
+    fn col2row_active_low() {
+        let mock = MockMatrix::active_low();
+        let keys = scan_pressed(
+            &mock,
+            &mut mock.row_output_pins(),
+            &mut mock.col_input_pins(),
+            DiodeDirection::Col2Row,
+            ActiveLevel::Low,
+        );
+        assert!(only_f_pressed(keys));
+    }
+
+    #[test]
+    fn row2col_active_low() {
+        let mock = MockMatrix::active_low();
+        let keys = scan_pressed(
+            &mock,
+            &mut mock.output_pins(),
+            &mut mock.input_pins(),
+            DiodeDirection::Row2Col,
+            ActiveLevel::Low,
+        );
+        assert!(only_f_pressed(keys));
+    }
+
+    #[test]
+    fn failed_output_is_set_back_to_inactive() {
+        let mock = MockMatrix::<3, 2>::new();
+        let mut output_pins = mock.output_pins();
+        let mut input_pins = mock.input_pins();
+        let clock = MockClock::new();
+        let mut matrix = Matrix::<3, 2, _, _>::new(
+            &mut output_pins,
+            &mut input_pins,
+            &clock,
+            Debouncer::new(Algorithm::SymmetricDefer, 0),
+            MatrixConfig::new(),
+        );
+        let mut state = State::new(KEYS, &Config::new());
+
+        mock.press(1, 0);
+        mock.fail_outputs(true);
+        let error = matrix.scan::<()>(&mut state).unwrap_err();
+        assert!(matches!(error, KeyboardError::Output(MockPinError)));
+
+        // a column left driven makes its pressed keys read on every column
+        assert!(!mock.input_pins()[1].is_high().unwrap());
     }
 }
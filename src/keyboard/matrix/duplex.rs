@@ -0,0 +1,232 @@
+use crate::clock::Clock;
+use crate::config::ActiveLevel;
+use crate::debounce::Debouncer;
+use crate::error::KeyboardError;
+
+use super::flex_pin::{drive, PinErrors};
+use super::{Events, FlexPin, Keys, Scanner};
+
+/// A duplex matrix, with two keys between every column and row pin, one with its diode from
+/// the column to the row and the other from the row to the column.
+///
+/// The columns drive the rows and then the rows drive the columns, so each column pin reads two
+/// columns of keys: the keys of column pin `c` going to the rows are column `2 * c` of the layout
+/// and the keys going back are column `2 * c + 1`.
+pub struct DuplexMatrix<
+    'a,
+    const NUM_OF_COLS: usize,
+    const NUM_OF_ROWS: usize,
+    Pin: FlexPin,
+> {
+    keys: Keys<'a, NUM_OF_COLS, NUM_OF_ROWS>,
+    col_pins: &'a mut [Pin],
+    row_pins: &'a mut [Pin],
+    active_level: ActiveLevel,
+}
+
+impl<'a, const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize, Pin: FlexPin>
+    DuplexMatrix<'a, NUM_OF_COLS, NUM_OF_ROWS, Pin>
+{
+    /// Takes half as many column pins as there are columns in the layout.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there aren't half as many column pins as columns, or as many row pins as rows.
+    pub fn new(
+        col_pins: &'a mut [Pin],
+        row_pins: &'a mut [Pin],
+        clock: &'a dyn Clock,
+        debouncer: Debouncer<NUM_OF_COLS, NUM_OF_ROWS>,
+        active_level: ActiveLevel,
+    ) -> Self {
+        assert!(
+            col_pins.len() * 2 == NUM_OF_COLS,
+            "a duplex matrix needs half as many column pins as columns"
+        );
+        assert!(
+            row_pins.len() == NUM_OF_ROWS,
+            "a duplex matrix needs as many row pins as rows"
+        );
+
+        // every pin floats until it drives
+        // a pin that fails here is reported by the first scan
+        for pin in col_pins.iter_mut().chain(row_pins.iter_mut()) {
+            let _ = pin.float();
+        }
+
+        Self {
+            keys: Keys::new(clock, debouncer),
+            col_pins,
+            row_pins,
+            active_level,
+        }
+    }
+}
+
+impl<const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize, Pin: FlexPin> Scanner
+    for DuplexMatrix<'_, NUM_OF_COLS, NUM_OF_ROWS, Pin>
+{
+    type OutputError = Pin::Error;
+    type InputError = Pin::Error;
+
+    fn scan<EncoderError>(
+        &mut self,
+        events: &mut impl Events,
+    ) -> Result<bool, KeyboardError<Pin::Error, Pin::Error, EncoderError>> {
+        if !self.keys.tick(events) {
+            return Ok(false);
+        }
+
+        let active_level = self.active_level;
+        let keys = &mut self.keys;
+        // the first pins that failed, the rest of the matrix is still scanned
+        let mut errors = PinErrors::new();
+
+        // the keys from the columns to the rows
+        drive_each(
+            self.col_pins,
+            self.row_pins,
+            active_level,
+            &mut errors,
+            |col_pin, row_pin, raw| keys.update(row_pin, col_pin * 2, raw, events),
+        );
+        // and back
+        drive_each(
+            self.row_pins,
+            self.col_pins,
+            active_level,
+            &mut errors,
+            |row_pin, col_pin, raw| keys.update(row_pin, col_pin * 2 + 1, raw, events),
+        );
+
+        errors.into_result()
+    }
+
+    fn is_pressed(&self, row: usize, col: usize) -> bool {
+        self.keys.pressed[row][col]
+    }
+
+    fn now(&self) -> u32 {
+        self.keys.now
+    }
+}
+
+// drives each of `drivers` in turn, calling `update` with the index of the driver, the index of
+// the reader and if the key is pressed
+fn drive_each<Pin: FlexPin>(
+    drivers: &mut [Pin],
+    readers: &[Pin],
+    active_level: ActiveLevel,
+    errors: &mut PinErrors<Pin::Error>,
+    mut update: impl FnMut(usize, usize, bool),
+) {
+    for (driver_index, driver) in drivers.iter_mut().enumerate() {
+        drive(
+            driver,
+            readers,
+            active_level,
+            errors,
+            |reader_index, pressed| {
+                update(driver_index, reader_index, pressed);
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DuplexMatrix, Scanner};
+    use crate::config::{ActiveLevel, Config};
+    use crate::debounce::{Algorithm, Debouncer};
+    use crate::keyboard::State;
+    use crate::error::KeyboardError;
+    use crate::keycode::{Keycode, Keycode::*};
+    use crate::testing::{MockClock, MockFlexMatrix, MockPinError};
+
+    // two column pins and two row pins
+    #[rustfmt::skip]
+    const KEYS: &[[[Keycode; 4]; 2]] = &[
+        [
+            [KC_A, KC_B, KC_C, KC_D],
+            [KC_E, KC_F, KC_G, KC_H],
+        ],
+    ];
+
+    // scans the keys pressed from pin `driver` to pin `reader` once, the first two pins are
+    // the column pins
+    fn scan(
+        mock: &MockFlexMatrix<4>,
+        presses: &[(usize, usize)],
+        active_level: ActiveLevel,
+    ) -> Vec<(usize, usize)> {
+        let clock = MockClock::new();
+
+        let mut pins = mock.pins();
+        let (col_pins, row_pins) = pins.split_at_mut(2);
+        let mut matrix = DuplexMatrix::<4, 2, _>::new(
+            col_pins,
+            row_pins,
+            &clock,
+            Debouncer::new(Algorithm::SymmetricDefer, 0),
+            active_level,
+        );
+        mock.scan_pressed(&mut matrix, presses)
+    }
+
+    #[test]
+    fn both_directions_are_read() {
+        for (mock, active_level) in MockFlexMatrix::at_both_levels() {
+            // column 0 to row 0, and row 1 back to column 1
+            assert_eq!(
+                scan(&mock, &[(0, 2), (3, 1)], active_level),
+                [(0, 0), (1, 3)]
+            );
+        }
+    }
+
+    #[test]
+    fn keys_on_the_same_pins_are_apart() {
+        // row 0 back to column 1 shares its pins with column 1 to row 0
+        let mock = MockFlexMatrix::new();
+        assert_eq!(scan(&mock, &[(2, 1)], ActiveLevel::High), [(0, 3)]);
+    }
+
+    #[test]
+    fn failed_drive_lets_the_pin_go() {
+        let mock = MockFlexMatrix::<4>::new();
+        let clock = MockClock::new();
+
+        let mut pins = mock.pins();
+        let (col_pins, row_pins) = pins.split_at_mut(2);
+        let mut matrix = DuplexMatrix::<4, 2, _>::new(
+            col_pins,
+            row_pins,
+            &clock,
+            Debouncer::new(Algorithm::SymmetricDefer, 0),
+            ActiveLevel::High,
+        );
+        let mut state = State::new(KEYS, &Config::new());
+
+        mock.fail_drives(true);
+        let error = matrix.scan::<()>(&mut state).unwrap_err();
+        assert!(matches!(error, KeyboardError::Output(MockPinError)));
+        assert!(!mock.is_driven());
+    }
+
+    #[test]
+    #[should_panic(expected = "half as many column pins as columns")]
+    fn wrong_number_of_pins() {
+        let mock = MockFlexMatrix::<4>::new();
+        let clock = MockClock::new();
+
+        let mut pins = mock.pins();
+        let (col_pins, row_pins) = pins.split_at_mut(1);
+        let _ = DuplexMatrix::<4, 3, _>::new(
+            col_pins,
+            row_pins,
+            &clock,
+            Debouncer::new(Algorithm::SymmetricDefer, 0),
+            ActiveLevel::High,
+        );
+    }
+}
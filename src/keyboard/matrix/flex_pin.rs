@@ -0,0 +1,119 @@
+#[cfg(feature = "rp2040")]
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+#[cfg(feature = "rp2040")]
+use rp2040_hal::gpio::{FunctionSioOutput, OutputEnableOverride, Pin, PinId, PullType};
+
+use crate::config::ActiveLevel;
+use crate::error::KeyboardError;
+
+/// A pin switched between driving a level and being read, for matrices where the same pins
+/// take turns driving the others.
+///
+/// A pin that isn't driven is left to its pull resistor, which must pull it to the inactive
+/// level of the matrix.
+pub trait FlexPin {
+    type Error;
+
+    /// Drives the pin high, or low if `high` is false.
+    fn drive(&mut self, high: bool) -> Result<(), Self::Error>;
+
+    /// Stops driving the pin so it can be read.
+    fn float(&mut self) -> Result<(), Self::Error>;
+
+    /// Reads the level of the pin.
+    fn is_high(&self) -> Result<bool, Self::Error>;
+}
+
+/// Switches the output driver of the pin with its output enable override, so the pull set up
+/// before turning it into an output, e.g. `pin.into_pull_up_input().into_push_pull_output()`,
+/// holds it while it floats.
+#[cfg(feature = "rp2040")]
+impl<I: PinId, P: PullType> FlexPin for Pin<I, FunctionSioOutput, P> {
+    type Error = rp2040_hal::gpio::Error;
+
+    fn drive(&mut self, high: bool) -> Result<(), Self::Error> {
+        if high {
+            self.set_high()?;
+        } else {
+            self.set_low()?;
+        }
+        self.set_output_enable_override(OutputEnableOverride::Normal);
+        Ok(())
+    }
+
+    fn float(&mut self) -> Result<(), Self::Error> {
+        self.set_output_enable_override(OutputEnableOverride::Disable);
+        Ok(())
+    }
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        InputPin::is_high(self)
+    }
+}
+
+// the first pin that couldn't be driven and the first that couldn't be read during a scan
+pub(super) struct PinErrors<Error> {
+    output: Option<Error>,
+    input: Option<Error>,
+}
+
+impl<Error> PinErrors<Error> {
+    pub(super) const fn new() -> Self {
+        Self {
+            output: None,
+            input: None,
+        }
+    }
+
+    pub(super) fn into_result<EncoderError>(
+        self,
+    ) -> Result<bool, KeyboardError<Error, Error, EncoderError>> {
+        match (self.output, self.input) {
+            (Some(error), _) => Err(KeyboardError::Output(error)),
+            (None, Some(error)) => Err(KeyboardError::Input(error)),
+            (None, None) => Ok(true),
+        }
+    }
+}
+
+// drives `driver` and reads every key it reaches through `readers`, calling `update` with the
+// index of the reader and if the key is pressed
+//
+// a key that can't be read keeps its last state, a driver that fails is let go so its keys don't
+// read as pressed while the next one drives
+pub(super) fn drive<'p, Pin: FlexPin + 'p>(
+    driver: &mut Pin,
+    readers: impl IntoIterator<Item = &'p Pin>,
+    active_level: ActiveLevel,
+    errors: &mut PinErrors<Pin::Error>,
+    mut update: impl FnMut(usize, bool),
+) {
+    let active_high = active_level == ActiveLevel::High;
+
+    if let Err(error) = driver.drive(active_high) {
+        let_go(driver, active_high);
+        errors.output.get_or_insert(error);
+        return;
+    }
+
+    for (reader_index, reader) in readers.into_iter().enumerate() {
+        match reader.is_high() {
+            Ok(high) => update(reader_index, high == active_high),
+            Err(error) => {
+                errors.input.get_or_insert(error);
+            }
+        }
+    }
+
+    if let Err(error) = driver.float() {
+        let_go(driver, active_high);
+        errors.output.get_or_insert(error);
+    }
+}
+
+// floats a pin that failed, or drives the inactive level if it can't float
+fn let_go<Pin: FlexPin>(pin: &mut Pin, active_high: bool) {
+    if pin.float().is_err() {
+        let _ = pin.drive(!active_high);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::clock::Clock;
+use crate::config::ActiveLevel;
+use crate::debounce::Debouncer;
+use crate::error::KeyboardError;
+
+use super::flex_pin::{drive, PinErrors};
+use super::{Events, FlexPin, Keys, Scanner};
+
+/// A round-robin matrix, with a key and diode from every pin to every other pin.
+///
+/// The pins take turns driving while the rest are read, so `n` pins read `n` rows of `n - 1`
+/// keys: the keys driven by pin `i` are row `i` of the layout, in the order of the pins they
+/// reach with pin `i` skipped.
+pub struct RoundRobinMatrix<
+    'a,
+    const NUM_OF_COLS: usize,
+    const NUM_OF_ROWS: usize,
+    Pin: FlexPin,
+> {
+    keys: Keys<'a, NUM_OF_COLS, NUM_OF_ROWS>,
+    pins: &'a mut [Pin],
+    active_level: ActiveLevel,
+}
+
+impl<'a, const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize, Pin: FlexPin>
+    RoundRobinMatrix<'a, NUM_OF_COLS, NUM_OF_ROWS, Pin>
+{
+    /// Takes as many pins as there are rows in the layout, one more than there are columns.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there aren't as many pins as rows, or one more than there are columns.
+    pub fn new(
+        pins: &'a mut [Pin],
+        clock: &'a dyn Clock,
+        debouncer: Debouncer<NUM_OF_COLS, NUM_OF_ROWS>,
+        active_level: ActiveLevel,
+    ) -> Self {
+        assert!(
+            pins.len() == NUM_OF_ROWS && pins.len() == NUM_OF_COLS + 1,
+            "a round-robin matrix needs as many pins as rows and one more than columns"
+        );
+
+        // every pin floats until it drives
+        // a pin that fails here is reported by the first scan
+        for pin in pins.iter_mut() {
+            let _ = pin.float();
+        }
+
+        Self {
+            keys: Keys::new(clock, debouncer),
+            pins,
+            active_level,
+        }
+    }
+}
+
+impl<const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize, Pin: FlexPin> Scanner
+    for RoundRobinMatrix<'_, NUM_OF_COLS, NUM_OF_ROWS, Pin>
+{
+    type OutputError = Pin::Error;
+    type InputError = Pin::Error;
+
+    fn scan<EncoderError>(
+        &mut self,
+        events: &mut impl Events,
+    ) -> Result<bool, KeyboardError<Pin::Error, Pin::Error, EncoderError>> {
+        if !self.keys.tick(events) {
+            return Ok(false);
+        }
+
+        let active_level = self.active_level;
+        let keys = &mut self.keys;
+        // the first pins that failed, the rest of the matrix is still scanned
+        let mut errors = PinErrors::new();
+
+        for row in 0..self.pins.len() {
+            // every pin but the driver reads
+            let (before, rest) = self.pins.split_at_mut(row);
+            if let Some((driver, after)) = rest.split_first_mut() {
+                let readers = before.iter().chain(after.iter());
+                drive(
+                    driver,
+                    readers,
+                    active_level,
+                    &mut errors,
+                    |col, pressed| {
+                        keys.update(row, col, pressed, events);
+                    },
+                );
+            }
+        }
+
+        errors.into_result()
+    }
+
+    fn is_pressed(&self, row: usize, col: usize) -> bool {
+        self.keys.pressed[row][col]
+    }
+
+    fn now(&self) -> u32 {
+        self.keys.now
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RoundRobinMatrix;
+    use crate::config::ActiveLevel;
+    use crate::debounce::{Algorithm, Debouncer};
+    use crate::testing::{MockClock, MockFlexMatrix};
+
+    #[test]
+    fn every_pin_drives_the_others() {
+        for (mock, active_level) in MockFlexMatrix::<4>::at_both_levels() {
+            let clock = MockClock::new();
+
+            let mut pins = mock.pins();
+            let mut matrix = RoundRobinMatrix::<3, 4, _>::new(
+                &mut pins,
+                &clock,
+                Debouncer::new(Algorithm::SymmetricDefer, 0),
+                active_level,
+            );
+
+            // the first and last key of the rows skip the driving pin
+            let pressed = mock.scan_pressed(&mut matrix, &[(0, 1), (1, 0), (2, 3), (3, 2)]);
+            assert_eq!(pressed, [(0, 0), (1, 0), (2, 2), (3, 2)]);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "as many pins as rows and one more than columns")]
+    fn wrong_number_of_pins() {
+        let mock = MockFlexMatrix::<4>::new();
+        let clock = MockClock::new();
+
+        let mut pins = mock.pins();
+        let _ = RoundRobinMatrix::<4, 4, _>::new(
+            &mut pins,
+            &clock,
+            Debouncer::new(Algorithm::SymmetricDefer, 0),
+            ActiveLevel::High,
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use usbd_human_interface_device::device::mouse::WheelMouseReport;
+
+use crate::config::{Acceleration, Motion, MouseKeys};
+use crate::keycode::Keycode;
+
+// scale of both axes when moving diagonally, 181 / 256 is about 1 / sqrt(2)
+const DIAGONAL_SCALE: i32 = 181;
+
+// repeats the moves of the cursor or wheel keys while they are held
+struct Repeat {
+    motion: Motion,
+    // number of moves since the keys were pressed
+    repeat: u8,
+    // when the next move is due
+    next_at: Option<u32>,
+}
+
+impl Repeat {
+    const fn new(motion: Motion) -> Self {
+        Self {
+            motion,
+            repeat: 0,
+            next_at: None,
+        }
+    }
+
+    // returns the distance to move at `now`, 0 if no move is due
+    fn distance(&mut self, held: bool, now: u32) -> i32 {
+        if !held {
+            self.repeat = 0;
+            self.next_at = None;
+            return 0;
+        }
+
+        match self.next_at {
+            // first move as soon as the key is pressed, then wait for the delay
+            None => self.next_at = Some(now.wrapping_add(self.motion.delay)),
+            // wrapping difference, so moves keep repeating when the ms counter overflows
+            Some(next_at) if now.wrapping_sub(next_at) <= u32::MAX / 2 => {
+                self.repeat = self.repeat.saturating_add(1);
+                self.next_at = Some(now.wrapping_add(self.motion.interval));
+            }
+            Some(_) => return 0,
+        }
+
+        self.speed()
+    }
+
+    fn speed(&self) -> i32 {
+        let delta = i32::from(self.motion.delta);
+
+        let speed = match self.motion.acceleration {
+            Acceleration::Constant => delta,
+            Acceleration::Accelerated {
+                max_speed,
+                time_to_max,
+            } => {
+                let max = delta * i32::from(max_speed);
+
+                if self.repeat >= time_to_max {
+                    max
+                } else {
+                    delta + (max - delta) * i32::from(self.repeat) / i32::from(time_to_max)
+                }
+            }
+        };
+
+        speed.clamp(1, i32::from(i8::MAX))
+    }
+}
+
+// turns held mouse keys into mouse reports
+pub(super) struct Mouse {
+    cursor: Repeat,
+    wheel: Repeat,
+    // buttons in the last report sent
+    buttons: u8,
+}
+
+impl Mouse {
+    pub(super) const fn new(config: &MouseKeys) -> Self {
+        Self {
+            cursor: Repeat::new(config.cursor),
+            wheel: Repeat::new(config.wheel),
+            buttons: 0,
+        }
+    }
+
+    // returns the report due at `now`, if the mouse moves or a button changed
+    pub(super) fn report<'k>(
+        &mut self,
+        keys: impl IntoIterator<Item = &'k Keycode>,
+        now: u32,
+    ) -> Option<WheelMouseReport> {
+        let mut buttons = 0;
+        let (mut x, mut y, mut vertical, mut horizontal) = (0i32, 0i32, 0i32, 0i32);
+
+        for keycode in keys {
+            match keycode {
+                Keycode::KC_MS_UP => y -= 1,
+                Keycode::KC_MS_DOWN => y += 1,
+                Keycode::KC_MS_LEFT => x -= 1,
+                Keycode::KC_MS_RIGHT => x += 1,
+                Keycode::KC_BTN1 => buttons |= 1,
+                Keycode::KC_BTN2 => buttons |= 1 << 1,
+                Keycode::KC_BTN3 => buttons |= 1 << 2,
+                Keycode::KC_BTN4 => buttons |= 1 << 3,
+                Keycode::KC_BTN5 => buttons |= 1 << 4,
+                Keycode::KC_WH_UP => vertical += 1,
+                Keycode::KC_WH_DOWN => vertical -= 1,
+                Keycode::KC_WH_LEFT => horizontal -= 1,
+                Keycode::KC_WH_RIGHT => horizontal += 1,
+                _ => {}
+            }
+        }
+
+        // opposite keys cancel each other out
+        let (x, y) = (x.signum(), y.signum());
+        let (vertical, horizontal) = (vertical.signum(), horizontal.signum());
+
+        let mut cursor = self.cursor.distance(x != 0 || y != 0, now);
+        if x != 0 && y != 0 {
+            cursor = (cursor * DIAGONAL_SCALE / 256).max(1);
+        }
+        let wheel = self.wheel.distance(vertical != 0 || horizontal != 0, now);
+
+        if cursor == 0 && wheel == 0 && buttons == self.buttons {
+            return None;
+        }
+
+        // distances are clamped to 127, so they always fit
+        let axis = |direction: i32, distance: i32| i8::try_from(direction * distance).unwrap_or(0);
+
+        Some(WheelMouseReport {
+            buttons,
+            x: axis(x, cursor),
+            y: axis(y, cursor),
+            vertical_wheel: axis(vertical, wheel),
+            horizontal_wheel: axis(horizontal, wheel),
+        })
+    }
+
+    // records the report as sent
+    pub(super) const fn sent(&mut self, report: &WheelMouseReport) {
+        self.buttons = report.buttons;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Mouse;
+    use crate::config::{Acceleration, Motion, MouseKeys};
+    use crate::keycode::Keycode::*;
+
+    const CONSTANT: MouseKeys = MouseKeys {
+        cursor: Motion {
+            delta: 5,
+            delay: 10,
+            interval: 10,
+            acceleration: Acceleration::Constant,
+        },
+        ..MouseKeys::new()
+    };
+
+    #[test]
+    fn constant_speed_repeats_after_delay() {
+        let mut mouse = Mouse::new(&CONSTANT);
+
+        let report = mouse.report(&[KC_MS_RIGHT], 0).unwrap();
+        assert_eq!((report.x, report.y), (5, 0));
+        assert!(mouse.report(&[KC_MS_RIGHT], 9).is_none());
+        assert_eq!(mouse.report(&[KC_MS_RIGHT], 10).unwrap().x, 5);
+        assert!(mouse.report(&[KC_MS_RIGHT], 15).is_none());
+        assert_eq!(mouse.report(&[KC_MS_RIGHT], 20).unwrap().x, 5);
+
+        assert!(mouse.report(&[], 30).is_none());
+    }
+
+    #[test]
+    fn accelerates_to_max_speed() {
+        let mut mouse = Mouse::new(&MouseKeys::new());
+
+        let distances: Vec<_> = (0..40)
+            .filter_map(|tick| mouse.report(&[KC_MS_DOWN], tick * 16))
+            .map(|report| report.y)
+            .collect();
+
+        assert_eq!(distances[0], 8);
+        assert!(distances.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(*distances.last().unwrap(), 80);
+    }
+
+    #[test]
+    fn diagonal_moves_are_scaled() {
+        let mut mouse = Mouse::new(&CONSTANT);
+
+        let report = mouse.report(&[KC_MS_UP, KC_MS_LEFT], 0).unwrap();
+        assert_eq!((report.x, report.y), (-3, -3));
+    }
+
+    #[test]
+    fn buttons_are_reported_on_change() {
+        let mut mouse = Mouse::new(&CONSTANT);
+
+        let report = mouse.report(&[KC_BTN1, KC_BTN3], 0).unwrap();
+        assert_eq!(report.buttons, 0b101);
+        mouse.sent(&report);
+        assert!(mouse.report(&[KC_BTN1, KC_BTN3], 1).is_none());
+
+        let report = mouse.report(&[], 2).unwrap();
+        assert_eq!(report.buttons, 0);
+    }
+}
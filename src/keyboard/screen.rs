@@ -0,0 +1,125 @@
+use crate::config::DisplayConfig;
+use crate::display::{DisplayError, Status, StatusDisplay};
+use crate::indicator::Leds;
+
+pub(super) struct Screen<'a> {
+    display: Option<&'a mut dyn StatusDisplay>,
+    layer_names: &'static [&'static str],
+    refresh_interval: u32,
+    // the status on the display
+    shown: Option<Status>,
+    // when the status was last shown
+    shown_at: Option<u32>,
+    // the status shown was only partly sent to the display
+    flushing: bool,
+}
+
+impl<'a> Screen<'a> {
+    pub(super) const fn new(config: &DisplayConfig) -> Self {
+        Self {
+            display: None,
+            layer_names: config.layer_names,
+            refresh_interval: config.refresh_interval,
+            shown: None,
+            shown_at: None,
+            flushing: false,
+        }
+    }
+
+    pub(super) fn set_display(&mut self, display: &'a mut dyn StatusDisplay) {
+        self.display = Some(display);
+    }
+
+    // shows the status if it changed and the refresh interval has passed, then sends a bit of
+    // it to the display each call
+    pub(super) fn periodic(&mut self, layer: usize, leds: Leds, wpm: u16, now: u32) {
+        let Some(display) = self.display.as_deref_mut() else {
+            return;
+        };
+
+        if self.flushing {
+            self.flush();
+            return;
+        }
+
+        let due = self
+            .shown_at
+            .is_none_or(|shown_at| now.wrapping_sub(shown_at) >= self.refresh_interval);
+        if !due {
+            return;
+        }
+
+        let status = Status {
+            layer,
+            layer_name: self.layer_names.get(layer).copied().unwrap_or_default(),
+            leds,
+            wpm,
+        };
+        if self.shown != Some(status) {
+            display.show(&status);
+            self.shown = Some(status);
+            self.shown_at = Some(now);
+            self.flush();
+        }
+    }
+
+    // sends a bit of the status shown, a display that failed shows it again once it is due
+    fn flush(&mut self) {
+        let Some(display) = self.display.as_deref_mut() else {
+            return;
+        };
+
+        match display.flush() {
+            Ok(()) => self.flushing = false,
+            Err(nb::Error::WouldBlock) => self.flushing = true,
+            Err(nb::Error::Other(DisplayError)) => {
+                self.flushing = false;
+                self.shown = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Screen;
+    use crate::config::DisplayConfig;
+    use crate::display::{DisplayError, Status, StatusDisplay};
+    use crate::indicator::Leds;
+
+    // a display that isn't connected, counting the statuses shown and the flushes
+    #[derive(Default)]
+    struct MissingDisplay {
+        shown: usize,
+        flushes: usize,
+    }
+
+    impl StatusDisplay for MissingDisplay {
+        fn show(&mut self, _status: &Status) {
+            self.shown += 1;
+        }
+
+        fn flush(&mut self) -> nb::Result<(), DisplayError> {
+            self.flushes += 1;
+            Err(nb::Error::Other(DisplayError))
+        }
+    }
+
+    #[test]
+    fn failed_display_is_tried_again_once_per_refresh() {
+        let config = DisplayConfig {
+            refresh_interval: 100,
+            ..DisplayConfig::new()
+        };
+        let mut display = MissingDisplay::default();
+        let mut screen = Screen::new(&config);
+        screen.set_display(&mut display);
+
+        for now in 0..250 {
+            screen.periodic(0, Leds::from_report(0), 0, now);
+        }
+
+        assert_eq!(display.shown, 3);
+        assert_eq!(display.flushes, 3);
+    }
+}
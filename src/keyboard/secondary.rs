@@ -0,0 +1,157 @@
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use embedded_hal::serial::{Read, Write};
+
+use crate::clock::Clock;
+use crate::config::{Config, Recovery};
+use crate::debounce::Debouncer;
+use crate::error::KeyboardError;
+use crate::split::{Link, Message, KEYS_PER_CHUNK};
+
+use super::matrix::{Events, Matrix, Scanner};
+
+// ms between sending every key held, so the primary half recovers from a lost press or release
+const RESYNC_INTERVAL: u32 = 1000;
+
+/// The secondary half of a split keyboard.
+///
+/// Scans its own matrix and sends every change to the primary half over a serial link.
+pub struct Secondary<
+    'a,
+    const NUM_OF_COLS: usize,
+    const NUM_OF_ROWS: usize,
+    Output: OutputPin,
+    Input: InputPin,
+    Serial,
+> {
+    matrix: Matrix<'a, NUM_OF_COLS, NUM_OF_ROWS, Output, Input>,
+    link: Link<Serial>,
+    // the active layer of the primary half
+    layer: usize,
+    recovery: Recovery,
+    // errors counted by the recovery policy
+    errors: u32,
+    // when every key held was last sent
+    resynced_at: u32,
+}
+
+impl<
+        'a,
+        const NUM_OF_COLS: usize,
+        const NUM_OF_ROWS: usize,
+        Output: OutputPin,
+        Input: InputPin,
+        Serial: Read<u8> + Write<u8>,
+    > Secondary<'a, NUM_OF_COLS, NUM_OF_ROWS, Output, Input, Serial>
+{
+    pub fn new(
+        output_pins: &'a mut [Output],
+        input_pins: &'a mut [Input],
+        config: Config,
+        clock: &'a dyn Clock,
+        serial: Serial,
+    ) -> Self {
+        let debouncer = Debouncer::new(config.debounce.algorithm, config.debounce.time);
+
+        Self {
+            matrix: Matrix::new(output_pins, input_pins, clock, debouncer, config.matrix),
+            link: Link::new(serial),
+            layer: 0,
+            recovery: config.recovery,
+            errors: 0,
+            resynced_at: 0,
+        }
+    }
+
+    /// Updates the secondary half.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a matrix pin fails unless the recovery policy is
+    /// [`Recovery::Ignore`], the rest of the update still runs.
+    pub fn periodic(&mut self) -> Result<(), KeyboardError<Output::Error, Input::Error>> {
+        let scanned = self.matrix.scan(&mut Sender(&mut self.link));
+
+        let now = self.matrix.now();
+        if now.wrapping_sub(self.resynced_at) >= RESYNC_INTERVAL {
+            self.resynced_at = now;
+            self.send_keys();
+        }
+
+        while let Some(message) = self.link.receive() {
+            if let Message::Layer(layer) = message {
+                self.layer = usize::from(layer);
+            }
+        }
+
+        self.link.flush();
+
+        self.recover(scanned.map(|_| ()))
+    }
+
+    // sends every key held, a chunk of a row at a time
+    fn send_keys(&mut self) {
+        for row in 0..NUM_OF_ROWS {
+            for chunk in 0..NUM_OF_COLS.div_ceil(KEYS_PER_CHUNK) {
+                let held = (0..KEYS_PER_CHUNK)
+                    .map(|bit| (bit, chunk * KEYS_PER_CHUNK + bit))
+                    .filter(|(_, col)| *col < NUM_OF_COLS && self.matrix.is_pressed(row, *col))
+                    .fold(0, |held, (bit, _)| held | 1 << bit);
+
+                // a chunk that doesn't fit is sent with the next resync
+                self.link.send(Message::Keys {
+                    row: u8::try_from(row).unwrap_or(u8::MAX),
+                    chunk: u8::try_from(chunk).unwrap_or(u8::MAX),
+                    held,
+                });
+            }
+        }
+    }
+
+    // applies the recovery policy to the result of an update, there is no USB to reset
+    fn recover(
+        &mut self,
+        result: Result<(), KeyboardError<Output::Error, Input::Error>>,
+    ) -> Result<(), KeyboardError<Output::Error, Input::Error>> {
+        let Err(error) = result else {
+            return Ok(());
+        };
+
+        if self.recovery == Recovery::Ignore {
+            return Ok(());
+        }
+
+        self.errors = self.errors.saturating_add(1);
+        Err(error)
+    }
+
+    /// Returns the number of errors counted by the recovery policy.
+    pub const fn error_count(&self) -> u32 {
+        self.errors
+    }
+
+    /// Returns the active layer of the primary half.
+    pub const fn layer(&self) -> usize {
+        self.layer
+    }
+}
+
+// sends the changes of a scan over the link
+struct Sender<'l, Serial>(&'l mut Link<Serial>);
+
+impl<Serial: Read<u8> + Write<u8>> Events for Sender<'_, Serial> {
+    fn tick(&mut self, _now: u32) {}
+
+    fn press(&mut self, row: usize, col: usize, _now: u32) {
+        self.0.send(Message::Press {
+            row: u8::try_from(row).unwrap_or(u8::MAX),
+            col: u8::try_from(col).unwrap_or(u8::MAX),
+        });
+    }
+
+    fn release(&mut self, row: usize, col: usize, _now: u32) {
+        self.0.send(Message::Release {
+            row: u8::try_from(row).unwrap_or(u8::MAX),
+            col: u8::try_from(col).unwrap_or(u8::MAX),
+        });
+    }
+}
@@ -1,73 +1,839 @@
+use crate::config::{Config, TapHold};
 use crate::keycode::Keycode;
+use crate::layout::MAX_LAYERS;
+use crate::macros::MacroStep;
+use crate::tap_dance::Action;
 
-struct Layout<'a> {
-    layout: &'a [&'a [&'a [Keycode]]],
+use super::leader::Leader;
+use super::tap_hold::{Dance, Decision, Event, EventBuffer, Pending};
+
+// rgb keys that can be pressed between two scans
+const MAX_RGB_REQUESTS: usize = 4;
+
+struct Layout<'a, const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize> {
+    layout: &'a [[[Keycode; NUM_OF_COLS]; NUM_OF_ROWS]],
 }
 
-impl<'a> Layout<'a> {
-    const fn new(layout: &'a [&[&[Keycode]]]) -> Self {
+impl<'a, const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize> Layout<'a, NUM_OF_COLS, NUM_OF_ROWS> {
+    const fn new(layout: &'a [[[Keycode; NUM_OF_COLS]; NUM_OF_ROWS]]) -> Self {
         Self { layout }
     }
 }
 
-pub(super) struct State<'a, const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize> {
-    layout: Layout<'a>,
+// a layer activated by `KC_OSL` for the next key press
+#[derive(Clone, Copy)]
+struct OneShotLayer {
     layer: usize,
+    // the `KC_OSL` key is still held down
+    held: bool,
+    // a key was pressed while the `KC_OSL` key was held, so it acts like `KC_MO`
+    used: bool,
+    // the key that consumed the one shot layer
+    key: Option<(usize, usize)>,
+}
+
+pub(super) struct State<'a, const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize> {
+    layout: Layout<'a, NUM_OF_COLS, NUM_OF_ROWS>,
+    tap_hold: TapHold,
+    // the keycode sent for each key
+    keys: [[Keycode; NUM_OF_COLS]; NUM_OF_ROWS],
+    // keys that were tapped and are released on the next tick
+    taps: [[bool; NUM_OF_COLS]; NUM_OF_ROWS],
+    // tap keycodes of held tap/hold keys that will be sent on release if no other key is pressed
+    retro_taps: [[Option<Keycode>; NUM_OF_COLS]; NUM_OF_ROWS],
+    pending: Option<Pending>,
+    buffer: EventBuffer,
+    dance: Option<Dance>,
+    leader: Leader,
+    default_layer: usize,
+    // bitmask of the layers active on top of the default layer
+    layers: u32,
+    oneshot: Option<OneShotLayer>,
+    // the layer each held key was pressed on, so it is released on the same layer
     override_keys: [[Option<usize>; NUM_OF_COLS]; NUM_OF_ROWS],
+    // a macro pressed since the last scan, waiting to be played
+    macro_request: Option<&'static [MacroStep]>,
+    // rgb keys pressed since the last scan waiting to be applied, oldest first
+    rgb_requests: [Keycode; MAX_RGB_REQUESTS],
+    rgb_request_count: usize,
+    // when a key was last pressed
+    last_press: Option<u32>,
+    // keys pressed so far, wrapping around
+    presses: u32,
+    // keycodes sent by keys instead of the layout's, like the keycode of a combo
+    substitutes: [[Option<Keycode>; NUM_OF_COLS]; NUM_OF_ROWS],
 }
 
 impl<'a, const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize> State<'a, NUM_OF_COLS, NUM_OF_ROWS> {
-    pub(super) const fn new(layout: &'a [&[&[Keycode]]]) -> Self {
+    pub(super) const fn new(
+        layout: &'a [[[Keycode; NUM_OF_COLS]; NUM_OF_ROWS]],
+        config: &Config,
+    ) -> Self {
         Self {
             layout: Layout::new(layout),
-            layer: 0,
+            tap_hold: config.tap_hold,
+            keys: [[Keycode::KC_NO; NUM_OF_COLS]; NUM_OF_ROWS],
+            taps: [[false; NUM_OF_COLS]; NUM_OF_ROWS],
+            retro_taps: [[None; NUM_OF_COLS]; NUM_OF_ROWS],
+            pending: None,
+            buffer: EventBuffer::new(),
+            dance: None,
+            leader: Leader::new(&config.leader),
+            default_layer: 0,
+            layers: 0,
+            oneshot: None,
             override_keys: [[None; NUM_OF_COLS]; NUM_OF_ROWS],
+            macro_request: None,
+            rgb_requests: [Keycode::KC_NO; MAX_RGB_REQUESTS],
+            rgb_request_count: 0,
+            last_press: None,
+            presses: 0,
+            substitutes: [[None; NUM_OF_COLS]; NUM_OF_ROWS],
+        }
+    }
+
+    // the keycodes to be sent
+    pub(super) const fn keys(&self) -> &[[Keycode; NUM_OF_COLS]; NUM_OF_ROWS] {
+        &self.keys
+    }
+
+    // the last macro pressed, if it hasn't been played yet
+    pub(super) const fn take_macro(&mut self) -> Option<&'static [MacroStep]> {
+        self.macro_request.take()
+    }
+
+    // the oldest rgb key pressed that hasn't been applied yet
+    pub(super) fn take_rgb(&mut self) -> Option<Keycode> {
+        if self.rgb_request_count == 0 {
+            return None;
+        }
+
+        let keycode = self.rgb_requests[0];
+        self.rgb_requests.copy_within(1..self.rgb_request_count, 0);
+        self.rgb_request_count -= 1;
+        Some(keycode)
+    }
+
+    // when a key was last pressed on the matrix
+    pub(super) const fn last_press(&self) -> Option<u32> {
+        self.last_press
+    }
+
+    // the number of keys pressed on the matrix so far, wrapping around
+    pub(super) const fn presses(&self) -> u32 {
+        self.presses
+    }
+
+    // handles a key being pressed on the matrix
+    pub(super) fn press(&mut self, row: usize, col: usize, now: u32) {
+        self.last_press = Some(now);
+        self.presses = self.presses.wrapping_add(1);
+
+        if self.pending.is_none() {
+            self.process_press(row, col, now);
+        } else if self.tap_hold.hold_on_other_key_press || self.buffer.is_full() {
+            self.decide(Decision::Hold, now);
+            self.press(row, col, now);
+        } else {
+            self.buffer.push(Event::Press(row, col));
+        }
+    }
+
+    // handles a key being pressed that sends `keycode` instead of the layout's keycode
+    pub(super) fn press_as(&mut self, row: usize, col: usize, keycode: Keycode, now: u32) {
+        self.substitutes[row][col] = Some(keycode);
+        self.press(row, col, now);
+    }
+
+    // handles a key being released on the matrix
+    pub(super) fn release(&mut self, row: usize, col: usize, now: u32) {
+        match self.pending {
+            None => self.process_release(row, col, now),
+            Some(pending) if (pending.row, pending.col) == (row, col) => {
+                self.decide(Decision::Tap, now);
+            }
+            Some(_) => {
+                let permissive_hold = self.tap_hold.permissive_hold
+                    && self.buffer.contains(Event::Press(row, col));
+
+                if permissive_hold || self.buffer.is_full() {
+                    self.decide(Decision::Hold, now);
+                    self.release(row, col, now);
+                } else {
+                    self.buffer.push(Event::Release(row, col));
+                }
+            }
+        }
+    }
+
+    // releases tapped keys and decides pending keys and tap dances past the tapping term
+    pub(super) fn tick(&mut self, now: u32) {
+        for row in 0..NUM_OF_ROWS {
+            for col in 0..NUM_OF_COLS {
+                if self.taps[row][col] {
+                    self.taps[row][col] = false;
+                    self.on_release(self.keys[row][col], row, col);
+                    self.keys[row][col] = Keycode::KC_NO;
+                }
+            }
+        }
+
+        if let Some(pending) = self.pending {
+            if now.wrapping_sub(pending.pressed_at) >= self.tap_hold.tapping_term {
+                self.decide(Decision::Timeout, now);
+            }
+        }
+
+        if let Some(dance) = self.dance {
+            if now.wrapping_sub(dance.changed_at) >= self.tap_hold.tapping_term {
+                self.finish_dance();
+            }
+        }
+
+        if let Some((action, row, col)) = self.leader.tick(now) {
+            self.act(action, row, col, false);
         }
     }
 
+    fn process_press(&mut self, row: usize, col: usize, now: u32) {
+        if let Some(dance) = &mut self.dance {
+            if (dance.row, dance.col) == (row, col) {
+                dance.taps = dance.taps.saturating_add(1);
+                dance.held = true;
+                dance.changed_at = now;
+
+                if dance.is_decided() {
+                    self.finish_dance();
+                }
+                return;
+            }
+
+            // another key interrupts the dance
+            self.finish_dance();
+        }
+
+        let keycode = self.get_key(row, col);
+        self.substitutes[row][col] = None;
+
+        // any key press stops held keys from retro tapping
+        self.retro_taps = [[None; NUM_OF_COLS]; NUM_OF_ROWS];
+
+        // keys typed after `KC_LEAD` are never sent
+        if self.leader.is_leading() {
+            if let Some(action) = self.leader.capture(keycode, row, col, now) {
+                self.act(action, row, col, false);
+            }
+            return;
+        }
+
+        if keycode == Keycode::KC_LEAD {
+            self.leader.start(row, col, now);
+        } else if let Keycode::KC_TD(dance) = keycode {
+            self.override_keys[row][col] = Some(self.resolve(row, col).0);
+            self.dance = Some(Dance::new(dance, row, col, now));
+
+            if self.dance.is_some_and(|dance| dance.is_decided()) {
+                self.finish_dance();
+            }
+        } else if let Some(pending) = Pending::new(keycode, row, col, now) {
+            self.override_keys[row][col] = Some(self.resolve(row, col).0);
+            self.pending = Some(pending);
+        } else {
+            self.on_press(keycode, row, col);
+            self.keys[row][col] = keycode;
+        }
+    }
+
+    fn process_release(&mut self, row: usize, col: usize, now: u32) {
+        if let Some(dance) = &mut self.dance {
+            if (dance.row, dance.col) == (row, col) {
+                dance.held = false;
+                dance.changed_at = now;
+
+                if dance.is_decided() {
+                    self.finish_dance();
+                }
+                return;
+            }
+        }
+
+        self.on_release(self.keys[row][col], row, col);
+        self.keys[row][col] = Keycode::KC_NO;
+
+        if let Some(keycode) = self.retro_taps[row][col].take() {
+            self.tap(keycode, row, col);
+        }
+    }
+
+    // decides the pending key then replays the events buffered behind it
+    fn decide(&mut self, decision: Decision, now: u32) {
+        let Some(pending) = self.pending.take() else {
+            return;
+        };
+
+        if decision == Decision::Tap {
+            self.tap(pending.tap, pending.row, pending.col);
+        } else {
+            self.on_press(pending.hold, pending.row, pending.col);
+            self.keys[pending.row][pending.col] = pending.hold;
+
+            if decision == Decision::Timeout && self.tap_hold.retro_tapping {
+                self.retro_taps[pending.row][pending.col] = Some(pending.tap);
+            }
+        }
+
+        let buffer = core::mem::take(&mut self.buffer);
+        buffer.iter().for_each(|event| match event {
+            Event::Press(row, col) => self.press(row, col, now),
+            Event::Release(row, col) => self.release(row, col, now),
+        });
+    }
+
+    // does the action of the tap dance for the taps counted so far
+    fn finish_dance(&mut self) {
+        let Some(dance) = self.dance.take() else {
+            return;
+        };
+
+        let action = dance.dance.action(dance.taps, dance.held);
+        self.act(action, dance.row, dance.col, dance.held);
+    }
+
+    // does a tap dance or leader action from the key, its keycode is held down if the key is held
+    fn act(&mut self, action: Action, row: usize, col: usize, held: bool) {
+        match action {
+            Action::Key(keycode) if held => {
+                self.on_press(keycode, row, col);
+                self.keys[row][col] = keycode;
+            }
+            Action::Key(keycode) => self.tap(keycode, row, col),
+            Action::Callback(callback) => {
+                self.override_keys[row][col] = None;
+                callback();
+            }
+        }
+    }
+
+    fn tap(&mut self, keycode: Keycode, row: usize, col: usize) {
+        self.on_press(keycode, row, col);
+        self.keys[row][col] = keycode;
+        self.taps[row][col] = true;
+    }
+
     pub(super) fn get_key(&self, row: usize, col: usize) -> Keycode {
+        if let Some(keycode) = self.substitutes[row][col] {
+            return keycode;
+        }
+
         self.override_keys[row][col].map_or_else(
-            || {
-                let mut keycode = self.layout.layout[self.layer][row][col];
-                let mut layer = self.layer;
-                while keycode == Keycode::KC_TRANS {
-                    layer -= 1;
-                    keycode = self.layout.layout[layer][row][col];
-                }
-                keycode
-            },
+            || self.resolve(row, col).1,
             |layer| self.layout.layout[layer][row][col],
         )
     }
 
-    // handles special press actions
-    pub(super) const fn on_press(&mut self, keycode: Keycode, row: usize, col: usize) {
+    // walks the active layers from the highest down, skipping transparent keys
+    fn resolve(&self, row: usize, col: usize) -> (usize, Keycode) {
+        (0..self.layout.layout.len())
+            .rev()
+            .filter(|layer| self.is_active(*layer))
+            .map(|layer| (layer, self.layout.layout[layer][row][col]))
+            .find(|(_, keycode)| *keycode != Keycode::KC_TRANS)
+            .unwrap_or((self.default_layer, Keycode::KC_NO))
+    }
+
+    // layer keys past the layout, or past the layers the mask can hold, are ignored
+    const fn has_layer(&self, layer: usize) -> bool {
+        layer < self.layout.layout.len() && layer < MAX_LAYERS
+    }
+
+    const fn is_active(&self, layer: usize) -> bool {
+        layer == self.default_layer || (self.has_layer(layer) && self.layers & (1 << layer) != 0)
+    }
+
+    const fn activate(&mut self, layer: usize) {
+        if self.has_layer(layer) {
+            self.layers |= 1 << layer;
+        }
+    }
+
+    const fn deactivate(&mut self, layer: usize) {
+        if self.has_layer(layer) {
+            self.layers &= !(1 << layer);
+        }
+    }
+
+    // applies a layer key without a key being pressed, other keys are ignored
+    pub(super) const fn switch_layer(&mut self, keycode: Keycode) {
         match keycode {
-            Keycode::KC_MO(layer) | Keycode::KC_LAYER(layer) => {
-                self.override_keys[row][col] = Some(self.layer);
-                self.layer = layer;
+            Keycode::KC_MO(layer)
+            | Keycode::KC_TG(layer)
+            | Keycode::KC_LAYER(layer)
+            | Keycode::KC_TO(layer)
+            | Keycode::KC_DF(layer)
+                if !self.has_layer(layer) => {}
+            Keycode::KC_MO(layer) => self.activate(layer),
+            Keycode::KC_TG(layer) => {
+                if self.is_active(layer) {
+                    self.deactivate(layer);
+                } else {
+                    self.activate(layer);
+                }
             }
+            Keycode::KC_LAYER(layer) | Keycode::KC_TO(layer) => {
+                self.layers = 0;
+                self.activate(layer);
+            }
+            Keycode::KC_DF(layer) => self.default_layer = layer,
             _ => {}
         }
     }
 
+    // handles special press actions
+    fn on_press(&mut self, keycode: Keycode, row: usize, col: usize) {
+        self.override_keys[row][col] = Some(self.resolve(row, col).0);
+
+        match keycode {
+            Keycode::KC_MO(_)
+            | Keycode::KC_TG(_)
+            | Keycode::KC_LAYER(_)
+            | Keycode::KC_TO(_)
+            | Keycode::KC_DF(_) => self.switch_layer(keycode),
+            Keycode::KC_OSL(layer) if self.has_layer(layer) => {
+                self.activate(layer);
+                self.oneshot = Some(OneShotLayer {
+                    layer,
+                    held: true,
+                    used: false,
+                    key: None,
+                });
+            }
+            Keycode::KC_MACRO(steps) => {
+                self.macro_request = Some(steps);
+                self.use_oneshot(row, col);
+            }
+            keycode if keycode.is_rgb() => {
+                // more keys than fit in one scan are dropped
+                if self.rgb_request_count < MAX_RGB_REQUESTS {
+                    self.rgb_requests[self.rgb_request_count] = keycode;
+                    self.rgb_request_count += 1;
+                }
+                self.use_oneshot(row, col);
+            }
+            _ => self.use_oneshot(row, col),
+        }
+    }
+
+    // a key other than a layer key was pressed while a one shot layer is active
+    const fn use_oneshot(&mut self, row: usize, col: usize) {
+        if let Some(oneshot) = &mut self.oneshot {
+            if oneshot.held {
+                oneshot.used = true;
+            } else if oneshot.key.is_none() {
+                oneshot.key = Some((row, col));
+            }
+        }
+    }
+
     // handles special release actions
-    pub(super) const fn on_release(&mut self, keycode: Keycode, row: usize, col: usize) {
-        #[allow(clippy::single_match)]
+    fn on_release(&mut self, keycode: Keycode, row: usize, col: usize) {
+        self.override_keys[row][col] = None;
+
         match keycode {
-            Keycode::KC_MO(_) => {
-                self.layer = self.override_keys[row][col].unwrap();
-                self.override_keys[row][col] = None;
+            Keycode::KC_MO(layer) => self.deactivate(layer),
+            Keycode::KC_OSL(layer) => {
+                if let Some(oneshot) = &mut self.oneshot {
+                    if oneshot.layer == layer {
+                        oneshot.held = false;
+
+                        if oneshot.used {
+                            self.deactivate(layer);
+                            self.oneshot = None;
+                        }
+                    }
+                }
             }
-            Keycode::KC_LAYER(_) => {
-                self.override_keys[row][col] = None;
+            _ => {
+                if let Some(oneshot) = self.oneshot {
+                    if oneshot.key == Some((row, col)) {
+                        self.deactivate(oneshot.layer);
+                        self.oneshot = None;
+                    }
+                }
             }
-            _ => {}
         }
     }
 
-    #[cfg(feature = "encoders")]
-    pub(super) const fn layer(&self) -> usize {
-        self.layer
+    /// Returns the highest active layer.
+    pub(super) fn layer(&self) -> usize {
+        (0..self.layout.layout.len())
+            .rev()
+            .find(|layer| self.is_active(*layer))
+            .unwrap_or(self.default_layer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::State;
+    use crate::config::{Config, TapHold};
+    use crate::keycode::{Keycode, Keycode::*};
+    use crate::tap_dance::{Action, TapDance};
+
+    #[rustfmt::skip]
+    const KEYS: &[[[Keycode; 3]; 2]] = &[
+        [
+            [KC_TRANS, KC_B, KC_MO(1)],
+            [KC_TG(2), KC_OSL(1), KC_DF(2)],
+        ],
+        [
+            [KC_C, KC_TRANS, KC_TRANS],
+            [KC_TRANS, KC_TRANS, KC_TO(0)],
+        ],
+        [
+            [KC_D, KC_E, KC_TRANS],
+            [KC_TRANS, KC_TRANS, KC_DF(0)],
+        ],
+    ];
+
+    fn tap(state: &mut State<3, 2>, row: usize, col: usize) {
+        let keycode = state.get_key(row, col);
+        state.on_press(keycode, row, col);
+        state.on_release(keycode, row, col);
+    }
+
+    #[test]
+    fn transparent_on_base_layer_is_no_key() {
+        let state = State::<3, 2>::new(KEYS, &Config::new());
+
+        assert!(state.get_key(0, 0) == KC_NO);
+    }
+
+    #[test]
+    fn momentary_layer_falls_through_transparent_keys() {
+        let mut state = State::<3, 2>::new(KEYS, &Config::new());
+
+        state.on_press(KC_MO(1), 0, 2);
+        assert!(state.get_key(0, 0) == KC_C);
+        assert!(state.get_key(0, 1) == KC_B);
+
+        state.on_release(KC_MO(1), 0, 2);
+        assert!(state.get_key(0, 0) == KC_NO);
+    }
+
+    #[test]
+    fn toggle_layer_stacks_with_momentary_layer() {
+        let mut state = State::<3, 2>::new(KEYS, &Config::new());
+
+        tap(&mut state, 1, 0);
+        assert!(state.get_key(0, 0) == KC_D);
+
+        // layer 2 is still above layer 1
+        state.on_press(KC_MO(1), 0, 2);
+        assert!(state.get_key(0, 0) == KC_D);
+        state.on_release(KC_MO(1), 0, 2);
+
+        tap(&mut state, 1, 0);
+        assert!(state.get_key(0, 0) == KC_NO);
+    }
+
+    #[test]
+    fn one_shot_layer_applies_to_next_key_only() {
+        let mut state = State::<3, 2>::new(KEYS, &Config::new());
+
+        tap(&mut state, 1, 1);
+        assert!(state.get_key(0, 0) == KC_C);
+
+        tap(&mut state, 0, 0);
+        assert!(state.get_key(0, 0) == KC_NO);
+    }
+
+    #[test]
+    fn one_shot_layer_held_acts_like_momentary() {
+        let mut state = State::<3, 2>::new(KEYS, &Config::new());
+
+        state.on_press(KC_OSL(1), 1, 1);
+        tap(&mut state, 0, 0);
+        assert!(state.get_key(0, 0) == KC_C);
+
+        state.on_release(KC_OSL(1), 1, 1);
+        assert!(state.get_key(0, 0) == KC_NO);
+    }
+
+    #[test]
+    fn to_layer_clears_momentary_layer() {
+        let mut state = State::<3, 2>::new(KEYS, &Config::new());
+
+        state.on_press(KC_MO(1), 0, 2);
+        assert!(state.get_key(1, 2) == KC_TO(0));
+
+        state.on_press(KC_TO(0), 1, 2);
+        assert!(state.get_key(0, 0) == KC_NO);
+        // held keys keep the layer they were pressed on
+        assert!(state.get_key(1, 2) == KC_TO(0));
+    }
+
+    #[test]
+    fn default_layer() {
+        let mut state = State::<3, 2>::new(KEYS, &Config::new());
+
+        tap(&mut state, 1, 2);
+        assert!(state.get_key(0, 0) == KC_D);
+        assert!(state.get_key(0, 2) == KC_NO);
+
+        tap(&mut state, 1, 2);
+        assert!(state.get_key(0, 1) == KC_B);
+    }
+
+    #[test]
+    fn layers_past_the_layout_are_ignored() {
+        let mut state = State::<3, 2>::new(KEYS, &Config::new());
+
+        for keycode in [KC_DF(5), KC_TO(5), KC_MO(5), KC_TG(5), KC_OSL(5)] {
+            state.on_press(keycode, 1, 1);
+            assert!(state.get_key(0, 1) == KC_B);
+            state.on_release(keycode, 1, 1);
+        }
+
+        state.switch_layer(KC_DF(3));
+        assert!(state.get_key(0, 1) == KC_B);
+    }
+
+    #[test]
+    fn layers_past_the_mask_are_ignored() {
+        const MANY_KEYS: &[[[Keycode; 1]; 1]] = &[[[KC_A]]; 34];
+        let mut state = State::<1, 1>::new(MANY_KEYS, &Config::new());
+
+        state.switch_layer(KC_MO(32));
+        assert!(!state.is_active(32));
+        state.switch_layer(KC_DF(33));
+        assert!(state.get_key(0, 0) == KC_A);
+        assert!(state.is_active(0));
+    }
+
+    #[test]
+    fn rgb_keys_pressed_together_are_all_applied() {
+        const RGB_KEYS: &[[[Keycode; 2]; 1]] = &[[[KC_RGB_TOG, KC_RGB_VAI]]];
+        let mut state = State::<2, 1>::new(RGB_KEYS, &Config::new());
+
+        state.press(0, 0, 0);
+        state.press(0, 1, 0);
+        assert!(state.take_rgb() == Some(KC_RGB_TOG));
+        assert!(state.take_rgb() == Some(KC_RGB_VAI));
+        assert!(state.take_rgb().is_none());
+    }
+
+    #[rustfmt::skip]
+    const TAP_HOLD_KEYS: &[[[Keycode; 3]; 1]] = &[
+        [
+            [KC_MT(&KC_LEFT_SHIFT, &KC_A), KC_B, KC_LT(1, &KC_C)],
+        ],
+        [
+            [KC_TRANS, KC_D, KC_TRANS],
+        ],
+    ];
+
+    fn tap_hold_state(tap_hold: TapHold) -> State<'static, 3, 1> {
+        State::new(
+            TAP_HOLD_KEYS,
+            &Config {
+                tap_hold,
+                ..Config::new()
+            },
+        )
+    }
+
+    #[test]
+    fn mod_tap_tapped() {
+        let mut state = tap_hold_state(TapHold::new());
+
+        state.press(0, 0, 0);
+        state.tick(10);
+        assert!(state.keys()[0][0] == KC_NO);
+
+        state.release(0, 0, 20);
+        assert!(state.keys()[0][0] == KC_A);
+
+        state.tick(21);
+        assert!(state.keys()[0][0] == KC_NO);
+    }
+
+    #[test]
+    fn mod_tap_held_past_tapping_term() {
+        let mut state = tap_hold_state(TapHold::new());
+
+        state.press(0, 0, 0);
+        state.press(0, 1, 50);
+        assert!(state.keys()[0][1] == KC_NO);
+
+        state.tick(200);
+        assert!(state.keys()[0][0] == KC_LEFT_SHIFT);
+        assert!(state.keys()[0][1] == KC_B);
+
+        state.release(0, 0, 250);
+        assert!(state.keys()[0][0] == KC_NO);
+    }
+
+    #[test]
+    fn interrupted_tap_keeps_key_order() {
+        let mut state = tap_hold_state(TapHold::new());
+
+        state.press(0, 0, 0);
+        state.press(0, 1, 10);
+        state.release(0, 0, 20);
+        assert!(state.keys()[0] == [KC_A, KC_B, KC_NO]);
+    }
+
+    #[test]
+    fn layer_tap_held_resolves_buffered_keys_on_layer() {
+        let mut state = tap_hold_state(TapHold {
+            hold_on_other_key_press: true,
+            ..TapHold::new()
+        });
+
+        state.press(0, 2, 0);
+        state.press(0, 1, 10);
+        assert!(state.keys()[0][1] == KC_D);
+
+        state.release(0, 2, 20);
+        state.release(0, 1, 30);
+        assert!(state.keys()[0] == [KC_NO; 3]);
+    }
+
+    #[test]
+    fn permissive_hold() {
+        let mut state = tap_hold_state(TapHold {
+            permissive_hold: true,
+            ..TapHold::new()
+        });
+
+        state.press(0, 0, 0);
+        state.press(0, 1, 10);
+        assert!(state.keys()[0][0] == KC_NO);
+
+        state.release(0, 1, 20);
+        assert!(state.keys()[0][0] == KC_LEFT_SHIFT);
+        assert!(state.keys()[0][1] == KC_NO);
+    }
+
+    #[test]
+    fn retro_tapping() {
+        let mut state = tap_hold_state(TapHold {
+            retro_tapping: true,
+            ..TapHold::new()
+        });
+
+        state.press(0, 0, 0);
+        state.tick(300);
+        assert!(state.keys()[0][0] == KC_LEFT_SHIFT);
+
+        state.release(0, 0, 310);
+        assert!(state.keys()[0][0] == KC_A);
+
+        // interrupted holds don't retro tap
+        state.tick(311);
+        state.press(0, 0, 400);
+        state.tick(700);
+        state.press(0, 1, 710);
+        state.release(0, 0, 720);
+        assert!(state.keys()[0][0] == KC_NO);
+    }
+
+    const DANCE: TapDance = TapDance {
+        double_tap: Some(Action::Key(KC_B)),
+        hold: Some(Action::Key(KC_MO(1))),
+        ..TapDance::new(Action::Key(KC_A))
+    };
+
+    static CALLED: AtomicUsize = AtomicUsize::new(0);
+
+    fn callback() {
+        CALLED.fetch_add(1, Ordering::Relaxed);
+    }
+
+    const CALLBACK_DANCE: TapDance = TapDance::new(Action::Callback(callback));
+
+    #[rustfmt::skip]
+    const TAP_DANCE_KEYS: &[[[Keycode; 3]; 1]] = &[
+        [
+            [KC_TD(&DANCE), KC_C, KC_TD(&CALLBACK_DANCE)],
+        ],
+        [
+            [KC_TRANS, KC_D, KC_TRANS],
+        ],
+    ];
+
+    fn tap_dance_state() -> State<'static, 3, 1> {
+        State::new(TAP_DANCE_KEYS, &Config::new())
+    }
+
+    #[test]
+    fn tap_dance_tapped_once_after_tapping_term() {
+        let mut state = tap_dance_state();
+
+        state.press(0, 0, 0);
+        state.release(0, 0, 10);
+        state.tick(100);
+        assert!(state.keys()[0][0] == KC_NO);
+
+        state.tick(210);
+        assert!(state.keys()[0][0] == KC_A);
+        state.tick(211);
+        assert!(state.keys()[0][0] == KC_NO);
+    }
+
+    #[test]
+    fn tap_dance_tapped_twice_acts_straight_away() {
+        let mut state = tap_dance_state();
+
+        state.press(0, 0, 0);
+        state.release(0, 0, 10);
+        state.press(0, 0, 20);
+        assert!(state.keys()[0][0] == KC_B);
+
+        state.release(0, 0, 30);
+        assert!(state.keys()[0][0] == KC_NO);
+    }
+
+    #[test]
+    fn tap_dance_held_moves_layer() {
+        let mut state = tap_dance_state();
+
+        state.press(0, 0, 0);
+        state.tick(200);
+        assert!(state.get_key(0, 1) == KC_D);
+
+        state.release(0, 0, 300);
+        assert!(state.get_key(0, 1) == KC_C);
+    }
+
+    #[test]
+    fn tap_dance_interrupted_by_another_key() {
+        let mut state = tap_dance_state();
+
+        // held, so the other key is on the hold layer
+        state.press(0, 0, 0);
+        state.press(0, 1, 10);
+        assert!(state.keys()[0][1] == KC_D);
+        state.release(0, 1, 20);
+        state.release(0, 0, 30);
+
+        // tapped, so the tap is sent before the other key
+        state.press(0, 0, 100);
+        state.release(0, 0, 110);
+        state.press(0, 1, 120);
+        assert!(state.keys()[0] == [KC_A, KC_C, KC_NO]);
+    }
+
+    #[test]
+    fn tap_dance_callback() {
+        let mut state = tap_dance_state();
+
+        state.press(0, 2, 0);
+        assert_eq!(CALLED.load(Ordering::Relaxed), 1);
+        assert!(state.keys()[0][2] == KC_NO);
+        state.release(0, 2, 10);
+        assert_eq!(CALLED.load(Ordering::Relaxed), 1);
     }
 }
@@ -0,0 +1,114 @@
+use crate::keycode::Keycode;
+use crate::tap_dance::TapDance;
+
+const EVENT_BUFFER_SIZE: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(super) enum Event {
+    Press(usize, usize),
+    Release(usize, usize),
+}
+
+// how a pending key was decided
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) enum Decision {
+    Tap,
+    Hold,
+    // held past the tapping term without being interrupted
+    Timeout,
+}
+
+// a `KC_MT` or `KC_LT` key waiting to be decided as a tap or a hold
+#[derive(Clone, Copy)]
+pub(super) struct Pending {
+    pub row: usize,
+    pub col: usize,
+    pub tap: Keycode,
+    pub hold: Keycode,
+    pub pressed_at: u32,
+}
+
+impl Pending {
+    // returns `None` if the keycode isn't a tap/hold key
+    pub(super) const fn new(keycode: Keycode, row: usize, col: usize, now: u32) -> Option<Self> {
+        let (tap, hold) = match keycode {
+            Keycode::KC_MT(hold, tap) => (*tap, *hold),
+            Keycode::KC_LT(layer, tap) => (*tap, Keycode::KC_MO(layer)),
+            _ => return None,
+        };
+
+        Some(Self {
+            row,
+            col,
+            tap,
+            hold,
+            pressed_at: now,
+        })
+    }
+}
+
+// a `KC_TD` key counting its taps
+#[derive(Clone, Copy)]
+pub(super) struct Dance {
+    pub row: usize,
+    pub col: usize,
+    pub dance: &'static TapDance,
+    pub taps: u8,
+    pub held: bool,
+    // when the key was last pressed or released
+    pub changed_at: u32,
+}
+
+impl Dance {
+    pub(super) const fn new(dance: &'static TapDance, row: usize, col: usize, now: u32) -> Self {
+        Self {
+            row,
+            col,
+            dance,
+            taps: 1,
+            held: true,
+            changed_at: now,
+        }
+    }
+
+    // the key can't do anything else whatever happens next
+    pub(super) const fn is_decided(&self) -> bool {
+        self.taps >= self.dance.max_taps()
+            && (!self.held || self.taps > 1 || self.dance.hold.is_none())
+    }
+}
+
+// matrix events held back while a tap/hold key is pending
+#[derive(Clone, Copy, Default)]
+pub(super) struct EventBuffer {
+    events: [Option<Event>; EVENT_BUFFER_SIZE],
+    len: usize,
+}
+
+impl EventBuffer {
+    pub(super) const fn new() -> Self {
+        Self {
+            events: [None; EVENT_BUFFER_SIZE],
+            len: 0,
+        }
+    }
+
+    pub(super) fn push(&mut self, event: Event) {
+        if let Some(slot) = self.events.get_mut(self.len) {
+            *slot = Some(event);
+            self.len += 1;
+        }
+    }
+
+    pub(super) const fn is_full(&self) -> bool {
+        self.len == EVENT_BUFFER_SIZE
+    }
+
+    pub(super) fn contains(&self, event: Event) -> bool {
+        self.iter().any(|buffered| buffered == event)
+    }
+
+    pub(super) fn iter(&self) -> impl Iterator<Item = Event> + '_ {
+        self.events.iter().flatten().copied()
+    }
+}
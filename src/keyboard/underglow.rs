@@ -0,0 +1,99 @@
+use crate::config;
+use crate::keycode::Keycode;
+use crate::rgb::{Animation, Hsv, LedDriver, Rgb, MAX_LEDS};
+
+// hue steps of `KC_RGB_HUI` and `KC_RGB_HUD`, wrapping around
+const HUE_STEP: u8 = 8;
+// saturation and value steps of the other `KC_RGB_*` keys
+const STEP: u8 = 16;
+
+pub(super) struct Underglow<'a> {
+    driver: Option<&'a mut dyn LedDriver>,
+    leds: usize,
+    enabled: bool,
+    animation: Animation,
+    color: Hsv,
+    layer_colors: &'static [Option<Hsv>],
+    period: u32,
+    frame_interval: u32,
+    frame: [Rgb; MAX_LEDS],
+    // when the last frame was rendered
+    rendered_at: Option<u32>,
+    // the frame was only partly written
+    writing: bool,
+    // the strip was turned off and the dark frame hasn't been written yet
+    clearing: bool,
+}
+
+impl<'a> Underglow<'a> {
+    pub(super) fn new(config: &config::Underglow) -> Self {
+        Self {
+            driver: None,
+            leds: config.leds.min(MAX_LEDS),
+            enabled: config.enabled,
+            animation: config.animation,
+            color: config.color,
+            layer_colors: config.layer_colors,
+            period: config.period,
+            frame_interval: config.frame_interval,
+            frame: [Rgb::OFF; MAX_LEDS],
+            rendered_at: None,
+            writing: false,
+            clearing: false,
+        }
+    }
+
+    pub(super) fn set_driver(&mut self, driver: &'a mut dyn LedDriver) {
+        self.driver = Some(driver);
+    }
+
+    // applies a `KC_RGB_*` key
+    pub(super) fn apply(&mut self, keycode: Keycode) {
+        let color = &mut self.color;
+        match keycode {
+            Keycode::KC_RGB_TOG => {
+                self.enabled = !self.enabled;
+                self.clearing = !self.enabled;
+            }
+            Keycode::KC_RGB_MOD => self.animation = self.animation.next(),
+            Keycode::KC_RGB_HUI => color.h = color.h.wrapping_add(HUE_STEP),
+            Keycode::KC_RGB_HUD => color.h = color.h.wrapping_sub(HUE_STEP),
+            Keycode::KC_RGB_SAI => color.s = color.s.saturating_add(STEP),
+            Keycode::KC_RGB_SAD => color.s = color.s.saturating_sub(STEP),
+            Keycode::KC_RGB_VAI => color.v = color.v.saturating_add(STEP),
+            Keycode::KC_RGB_VAD => color.v = color.v.saturating_sub(STEP),
+            _ => {}
+        }
+    }
+
+    // renders a frame every frame interval and writes as much of it as the driver takes
+    pub(super) fn periodic(&mut self, layer: usize, now: u32, last_press: Option<u32>) {
+        let Some(driver) = self.driver.as_deref_mut() else {
+            return;
+        };
+        let frame = &mut self.frame[..self.leds];
+
+        if !self.writing {
+            let due = self.rendered_at.is_none_or(|rendered_at| {
+                now.wrapping_sub(rendered_at) >= self.frame_interval
+            });
+
+            if self.clearing {
+                frame.fill(Rgb::OFF);
+                self.clearing = false;
+            } else if self.enabled && due {
+                let color = match self.layer_colors.get(layer) {
+                    Some(Some(layer_color)) => Hsv::new(layer_color.h, layer_color.s, self.color.v),
+                    _ => self.color,
+                };
+                self.animation.render(color, self.period, now, last_press, frame);
+            } else {
+                return;
+            }
+
+            self.rendered_at = Some(now);
+        }
+
+        self.writing = driver.write(frame).is_err();
+    }
+}
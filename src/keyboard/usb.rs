@@ -1,48 +1,51 @@
-use embedded_hal::timer::CountDown;
 use frunk::HList;
-use fugit::ExtU32;
 use usb_device::{
     class_prelude::{UsbBus as UsbBusTrait, UsbBusAllocator}, device::StringDescriptors, prelude::{UsbDevice, UsbDeviceBuilder, UsbVidPid}, LangID, UsbError
 };
 use usbd_human_interface_device::{
     device::{
-        consumer::{
-            ConsumerControl, ConsumerControlConfig, MultipleConsumerReport,
-            MULTIPLE_CODE_REPORT_DESCRIPTOR,
-        },
         keyboard::{NKROBootKeyboard, NKROBootKeyboardConfig},
+        mouse::{WheelMouse, WheelMouseConfig, WheelMouseReport},
     },
-    interface::InterfaceBuilder,
     page,
     usb_class::{UsbHidClass, UsbHidClassBuilder},
     UsbHidError,
 };
 
-type HidClass<'a, Usb> =
-    UsbHidClass<'a, Usb, HList!(ConsumerControl<'a, Usb>, NKROBootKeyboard<'a, Usb>)>;
+use super::hid::{Consumer, ConsumerConfig, SystemControl, SystemControlConfig, CONSUMER_SLOTS};
 
+type HidClass<'a, Usb> = UsbHidClass<
+    'a,
+    Usb,
+    HList!(
+        SystemControl<'a, Usb>,
+        WheelMouse<'a, Usb>,
+        Consumer<'a, Usb>,
+        NKROBootKeyboard<'a, Usb>
+    ),
+>;
+
+use crate::indicator::Leds;
 use crate::keycode::Keycode;
 
-pub(super) struct Usb<'a, Timer: CountDown, UsbBus: UsbBusTrait> {
+pub(super) struct Usb<'a, UsbBus: UsbBusTrait> {
     usb_dev: UsbDevice<'a, UsbBus>,
     usb_hid_class: HidClass<'a, UsbBus>,
-    usb_tick_timer: &'a mut Timer,
-    last_consumer_report: MultipleConsumerReport,
+    // the ms the usb class was last ticked
+    ticked_at: Option<u32>,
+    last_consumer_report: [u16; CONSUMER_SLOTS],
+    last_system_report: u8,
+    // leds set by the host since they were last taken
+    leds: Option<Leds>,
 }
 
-impl<'a, Timer: CountDown, UsbBus: UsbBusTrait> Usb<'a, Timer, UsbBus> {
-    pub(super) fn new(usb_bus: &'a UsbBusAllocator<UsbBus>, timer: &'a mut Timer) -> Self {
+impl<'a, UsbBus: UsbBusTrait> Usb<'a, UsbBus> {
+    pub(super) fn new(usb_bus: &'a UsbBusAllocator<UsbBus>) -> Self {
         let usb_hid_class = UsbHidClassBuilder::new()
             .add_device(NKROBootKeyboardConfig::default())
-            .add_device(ConsumerControlConfig::new(
-                InterfaceBuilder::new(MULTIPLE_CODE_REPORT_DESCRIPTOR)
-                    .unwrap()
-                    .description("Consumer Control")
-                    .in_endpoint(10.millis())
-                    .unwrap()
-                    .without_out_endpoint()
-                    .build(),
-            ))
+            .add_device(ConsumerConfig)
+            .add_device(WheelMouseConfig::default())
+            .add_device(SystemControlConfig)
             .build(usb_bus);
 
         let strings = StringDescriptors::new(LangID::EN_CA)
@@ -54,27 +57,26 @@ impl<'a, Timer: CountDown, UsbBus: UsbBusTrait> Usb<'a, Timer, UsbBus> {
             .strings(&[strings]).expect("Too many langs")
             .build();
 
-        let usb_tick_timer = timer;
-
         Self {
             usb_dev,
             usb_hid_class,
-            usb_tick_timer,
-            last_consumer_report: MultipleConsumerReport::default(),
+            ticked_at: None,
+            last_consumer_report: [0; CONSUMER_SLOTS],
+            last_system_report: 0,
+            leds: None,
         }
     }
 
     // pub(super) fn initialize(&mut self) {
     // }
 
-    pub(super) fn periodic(&mut self) {
-        // tick usb class
-        if self.usb_tick_timer.wait().is_ok() {
+    // ticks the usb class once per ms and polls the device
+    pub(super) fn periodic(&mut self, now: u32) -> Result<(), UsbHidError> {
+        if self.ticked_at != Some(now) {
+            self.ticked_at = Some(now);
             match self.usb_hid_class.tick() {
                 Err(UsbHidError::WouldBlock) | Ok(()) => {}
-                Err(e) => {
-                    core::panic!("Failed to process keyboard tick: {:?}", e)
-                }
+                Err(e) => return Err(e),
             }
         }
 
@@ -85,90 +87,151 @@ impl<'a, Timer: CountDown, UsbBus: UsbBusTrait> Usb<'a, Timer, UsbBus> {
                 .device::<NKROBootKeyboard<'_, _>, _>()
                 .read_report()
             {
-                Err(UsbError::WouldBlock) | Ok(_) => {}
-                Err(e) => {
-                    core::panic!("Failed to read keyboard report: {:?}", e)
+                Ok(report) => {
+                    self.leds = Some(Leds {
+                        num_lock: report.num_lock,
+                        caps_lock: report.caps_lock,
+                        scroll_lock: report.scroll_lock,
+                        compose: report.compose,
+                        kana: report.kana,
+                    });
                 }
+                Err(UsbError::WouldBlock) => {}
+                Err(e) => return Err(UsbHidError::UsbError(e)),
             }
         }
+
+        Ok(())
     }
 
-    pub(super) fn write_keyboard_report(&mut self, keys: &[Keycode]) {
-        let keyboard = keys
-            .iter()
-            .filter(|keycode| !keycode.is_consumer())
-            .flat_map(|keycode| {
-                if let Keycode::KEYS_2(key1, key2) = keycode {
-                    [
-                        page::Keyboard::from((**key1).try_into().unwrap_or(0)),
-                        page::Keyboard::from((**key2).try_into().unwrap_or(0)),
-                    ]
-                } else {
-                    [
-                        page::Keyboard::from(keycode.try_into().unwrap_or(0)),
-                        page::Keyboard::ErrorUndefine,
-                    ]
-                }
-            });
+    // returns the leds the host set since the last call
+    pub(super) const fn take_leds(&mut self) -> Option<Leds> {
+        self.leds.take()
+    }
+
+    // resets the usb device so the host enumerates it again
+    pub(super) fn reset(&mut self) {
+        self.usb_dev.force_reset().ok();
+    }
+
+    // returns if the report was sent or is already the last report sent
+    pub(super) fn write_keyboard_report<'k>(
+        &mut self,
+        keys: impl IntoIterator<Item = &'k Keycode>,
+    ) -> Result<bool, UsbHidError> {
+        let keyboard = keys.into_iter().flat_map(|keycode| {
+            if let Keycode::KEYS_2(key1, key2) = keycode {
+                [
+                    page::Keyboard::from(key1.into_keyboard().unwrap_or(0)),
+                    page::Keyboard::from(key2.into_keyboard().unwrap_or(0)),
+                ]
+            } else {
+                [
+                    page::Keyboard::from(keycode.into_keyboard().unwrap_or(0)),
+                    page::Keyboard::ErrorUndefine,
+                ]
+            }
+        });
 
         match self
             .usb_hid_class
             .device::<NKROBootKeyboard<'_, _>, _>()
             .write_report(keyboard)
         {
-            Err(UsbHidError::WouldBlock) | Err(UsbHidError::Duplicate) | Ok(_) => {}
-            Err(e) => {
-                core::panic!("Failed to write keyboard report: {:?}", e)
-            }
+            Err(UsbHidError::WouldBlock) => Ok(false),
+            Err(UsbHidError::Duplicate) | Ok(_) => Ok(true),
+            Err(e) => Err(e),
         }
     }
 
-    pub(super) fn write_consumer_report(&mut self, keys: &[Keycode]) {
-        let mut consumer_array = [page::Consumer::Unassigned; 4];
-        keys.iter()
-            .filter_map(|keycode| {
-                if keycode.is_consumer() {
-                    Some(if let Keycode::KEYS_2(key1, key2) = keycode {
-                        [
-                            page::Consumer::from((**key1).into_consumer().unwrap_or(0)),
-                            page::Consumer::from((**key2).into_consumer().unwrap_or(0)),
-                        ]
-                    } else {
-                        [
-                            page::Consumer::from(keycode.into_consumer().unwrap_or(0)),
-                            page::Consumer::Unassigned,
-                        ]
-                    })
+    // reports the first consumer keys pressed, the rest are left out until a slot frees up
+    pub(super) fn write_consumer_report<'k>(
+        &mut self,
+        keys: impl IntoIterator<Item = &'k Keycode>,
+    ) -> Result<(), UsbHidError> {
+        let mut consumer_report = [0; CONSUMER_SLOTS];
+        let mut len = 0;
+        keys.into_iter()
+            .flat_map(|keycode| {
+                if let Keycode::KEYS_2(key1, key2) = keycode {
+                    [key1.into_consumer(), key2.into_consumer()]
                 } else {
-                    None
+                    [keycode.into_consumer(), None]
                 }
             })
             .flatten()
-            .enumerate()
-            .for_each(|(index, consumer)| {
-                if index < 4 {
-                    consumer_array[index] = consumer;
+            .for_each(|usage| {
+                if len < CONSUMER_SLOTS && !consumer_report[..len].contains(&usage) {
+                    consumer_report[len] = usage;
+                    len += 1;
                 }
             });
 
-        let consumer_report = MultipleConsumerReport {
-            codes: consumer_array,
-        };
-
         if self.last_consumer_report != consumer_report {
             match self
                 .usb_hid_class
-                .device::<ConsumerControl<'_, _>, _>()
+                .device::<Consumer<'_, _>, _>()
                 .write_report(&consumer_report)
             {
-                Err(UsbError::WouldBlock) => {}
-                Ok(_) => {
+                Err(UsbHidError::WouldBlock) => {}
+                Ok(()) => {
                     self.last_consumer_report = consumer_report;
                 }
-                Err(e) => {
-                    core::panic!("Failed to write keyboard report: {:?}", e)
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+
+    // reports the first system control key pressed
+    pub(super) fn write_system_report<'k>(
+        &mut self,
+        keys: impl IntoIterator<Item = &'k Keycode>,
+    ) -> Result<(), UsbHidError> {
+        let system_report = keys
+            .into_iter()
+            .flat_map(|keycode| {
+                if let Keycode::KEYS_2(key1, key2) = keycode {
+                    [key1.into_system(), key2.into_system()]
+                } else {
+                    [keycode.into_system(), None]
+                }
+            })
+            .flatten()
+            .next()
+            .unwrap_or(0);
+
+        if self.last_system_report != system_report {
+            match self
+                .usb_hid_class
+                .device::<SystemControl<'_, _>, _>()
+                .write_report(system_report)
+            {
+                Err(UsbHidError::WouldBlock) => {}
+                Ok(()) => {
+                    self.last_system_report = system_report;
                 }
+                Err(e) => return Err(e),
             }
         }
+
+        Ok(())
+    }
+
+    // returns if the report was sent
+    pub(super) fn write_mouse_report(
+        &mut self,
+        report: &WheelMouseReport,
+    ) -> Result<bool, UsbHidError> {
+        match self
+            .usb_hid_class
+            .device::<WheelMouse<'_, _>, _>()
+            .write_report(report)
+        {
+            Err(UsbHidError::WouldBlock) => Ok(false),
+            Err(UsbHidError::Duplicate) | Ok(()) => Ok(true),
+            Err(e) => Err(e),
+        }
     }
 }
@@ -0,0 +1,75 @@
+// key presses are counted over this many ms
+const WINDOW: u32 = 5000;
+// the window is split into buckets so old presses drop out gradually
+const BUCKETS: usize = 10;
+const BUCKET: u32 = WINDOW / BUCKETS as u32;
+// characters in a word
+const WORD: u32 = 5;
+
+// words per minute typed over the last few seconds
+pub(super) struct Wpm {
+    buckets: [u16; BUCKETS],
+    // the bucket counting presses now
+    index: usize,
+    // when the current bucket started
+    started_at: u32,
+    // total presses at the last update
+    presses: u32,
+}
+
+impl Wpm {
+    pub(super) const fn new() -> Self {
+        Self {
+            buckets: [0; BUCKETS],
+            index: 0,
+            started_at: 0,
+            presses: 0,
+        }
+    }
+
+    // counts the presses since the last update, `presses` is the total so far
+    pub(super) fn update(&mut self, presses: u32, now: u32) {
+        let elapsed = now.wrapping_sub(self.started_at) / BUCKET;
+        for _ in 0..elapsed.min(BUCKETS as u32) {
+            self.index = (self.index + 1) % BUCKETS;
+            self.buckets[self.index] = 0;
+        }
+        if elapsed > 0 {
+            self.started_at = self.started_at.wrapping_add(elapsed * BUCKET);
+        }
+
+        let new = presses.wrapping_sub(self.presses);
+        self.presses = presses;
+        let bucket = &mut self.buckets[self.index];
+        *bucket = bucket.saturating_add(u16::try_from(new).unwrap_or(u16::MAX));
+    }
+
+    pub(super) fn wpm(&self) -> u16 {
+        let presses: u32 = self.buckets.iter().map(|bucket| u32::from(*bucket)).sum();
+        u16::try_from(presses * 60_000 / WINDOW / WORD).unwrap_or(u16::MAX)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Wpm;
+
+    #[test]
+    fn presses_over_the_window() {
+        let mut wpm = Wpm::new();
+
+        // a key every 200ms is 5 characters a second, 60 wpm
+        let mut presses = 0;
+        for now in (0..5000).step_by(200) {
+            presses += 1;
+            wpm.update(presses, now);
+        }
+        assert_eq!(wpm.wpm(), 60);
+
+        // drops out gradually once typing stops
+        wpm.update(presses, 7000);
+        assert_eq!(wpm.wpm(), 28);
+        wpm.update(presses, 20_000);
+        assert_eq!(wpm.wpm(), 0);
+    }
+}
@@ -1,478 +1,500 @@
-#[allow(clippy::enum_glob_use)]
-use self::Keycode::*;
-#[derive(Copy, Clone, PartialEq)]
-#[allow(non_camel_case_types, unused)]
-#[repr(u8)]
-pub enum Keycode {
-    KC_NO = 0x00,
-
-    KC_A = 0x04,
-    KC_B = 0x05,
-    KC_C = 0x06,
-    KC_D = 0x07,
-    KC_E = 0x08,
-    KC_F = 0x09,
-    KC_G = 0x0a,
-    KC_H = 0x0b,
-    KC_I = 0x0c,
-    KC_J = 0x0d,
-    KC_K = 0x0e,
-    KC_L = 0x0f,
-    KC_M = 0x10,
-    KC_N = 0x11,
-    KC_O = 0x12,
-    KC_P = 0x13,
-    KC_Q = 0x14,
-    KC_R = 0x15,
-    KC_S = 0x16,
-    KC_T = 0x17,
-    KC_U = 0x18,
-    KC_V = 0x19,
-    KC_W = 0x1a,
-    KC_X = 0x1b,
-    KC_Y = 0x1c,
-    KC_Z = 0x1d,
-
-    KC_1 = 0x1e,
-    KC_2 = 0x1f,
-    KC_3 = 0x20,
-    KC_4 = 0x21,
-    KC_5 = 0x22,
-    KC_6 = 0x23,
-    KC_7 = 0x24,
-    KC_8 = 0x25,
-    KC_9 = 0x26,
-    KC_0 = 0x27,
-
-    KC_ENTER = 0x28,
-    KC_ESCAPE = 0x29,
-    KC_BACKSPACE = 0x2A,
-    KC_TAB = 0x2B,
-    KC_SPACE = 0x2C,
-    KC_MINUS = 0x2D,
-    KC_EQUAL = 0x2E,
-    KC_LEFT_BRACKET = 0x2F,
-    KC_RIGHT_BRACKET = 0x30,
-    KC_BACKSLASH = 0x31,
-    KC_NONUS_HASH = 0x32,
-    KC_SEMICOLON = 0x33,
-    KC_QUOTE = 0x34,
-    KC_GRAVE = 0x35,
-    KC_COMMA = 0x36,
-    KC_DOT = 0x37,
-    KC_SLASH = 0x38,
-    KC_CAPS_LOCK = 0x39,
-
-    KC_F1 = 0x3A,
-    KC_F2 = 0x3B,
-    KC_F3 = 0x3C,
-    KC_F4 = 0x3D,
-    KC_F5 = 0x3E,
-    KC_F6 = 0x3F,
-    KC_F7 = 0x40,
-    KC_F8 = 0x41,
-    KC_F9 = 0x42,
-    KC_F10 = 0x43,
-    KC_F11 = 0x44,
-    KC_F12 = 0x45,
-
-    KC_PRINTSCREEN = 0x46,
-    KC_SCROLL_LOCK = 0x47,
-    KC_PAUSE = 0x48,
-    KC_INSERT = 0x49,
-    KC_HOME = 0x4A,
-    KC_PAGEUP = 0x4B,
-    KC_DELETE_FORWARD = 0x4C,
-    KC_END = 0x4D,
-    KC_PAGEDOWN = 0x4E,
-
-    KC_RIGHT_ARROW = 0x4F,
-    KC_LEFT_ARROW = 0x50,
-    KC_DOWN_ARROW = 0x51,
-    KC_UP_ARROW = 0x52,
-
-    KC_NUM_LOCK = 0x53,
-    KC_KEYPAD_FOWARDSLASH = 0x54,
-    KC_KEYPAD_ASTERISK = 0x55,
-    KC_KEYPAD_MINUS = 0x56,
-    KC_KEYPAD_PLUS = 0x57,
-    KC_KEYPAD_ENTER = 0x58,
-    KC_KEYPAD_1 = 0x59,
-    KC_KEYPAD_2 = 0x5a,
-    KC_KEYPAD_3 = 0x5b,
-    KC_KEYPAD_4 = 0x5c,
-    KC_KEYPAD_5 = 0x5d,
-    KC_KEYPAD_6 = 0x5e,
-    KC_KEYPAD_7 = 0x5f,
-    KC_KEYPAD_8 = 0x60,
-    KC_KEYPAD_9 = 0x61,
-    KC_KEYPAD_0 = 0x62,
-
-    KC_KEYPAD_DOT = 0x63,
-    KC_NONUS_BACKSLASH = 0x64,
-    KC_APP = 0x65,
-    KC_POWER = 0x66,
-    KC_KEYPAD_EQUAL = 0x67,
-    KC_F13 = 0x68,
-    KC_F14 = 0x69,
-    KC_F15 = 0x6a,
-    KC_F16 = 0x6b,
-    KC_F17 = 0x6c,
-    KC_F18 = 0x6d,
-    KC_F19 = 0x6e,
-    KC_F20 = 0x6f,
-    KC_F21 = 0x70,
-    KC_F22 = 0x71,
-    KC_F23 = 0x72,
-    KC_F24 = 0x73,
-
-    KC_EXECUTE = 0x74,
-    KC_HELP = 0x75,
-    KC_MENU = 0x76,
-    KC_SELECT = 0x77,
-    KC_STOP = 0x78,
-    KC_AGAIN = 0x79,
-    KC_UNDO = 0x7A,
-    KC_CUT = 0x7B,
-    KC_PASTE = 0x7D,
-    KC_FIND = 0x7E,
-
-    KC_MUTE = 0x7f,
-    KC_VOLUP = 0x80,
-    KC_VOLDOWN = 0x81,
-    
-    KC_LOCKING_CAPS_LOCK = 0x0082,
-    KC_LOCKING_NUM_LOCK = 0x0083,
-    KC_LOCKING_SCROLL_LOCK = 0x0084,
-    KC_KEYPAD_COMMA = 0x85,
-    KC_EQUAL_SIGN = 0x86,
-    KC_INTERNATIONAL_1 = 0x0087,
-    KC_INTERNATIONAL_2 = 0x0088,
-    KC_INTERNATIONAL_3 = 0x0089,
-    KC_INTERNATIONAL_4 = 0x008A,
-    KC_INTERNATIONAL_5 = 0x008B,
-    KC_INTERNATIONAL_6 = 0x008C,
-    KC_INTERNATIONAL_7 = 0x008D,
-    KC_INTERNATIONAL_8 = 0x008E,
-    KC_INTERNATIONAL_9 = 0x008F,
-    KC_LANGUAGE_1 = 0x0090,
-    KC_LANGUAGE_2 = 0x0091,
-    KC_LANGUAGE_3 = 0x0092,
-    KC_LANGUAGE_4 = 0x0093,
-    KC_LANGUAGE_5 = 0x0094,
-    KC_LANGUAGE_6 = 0x0095,
-    KC_LANGUAGE_7 = 0x0096,
-    KC_LANGUAGE_8 = 0x0097,
-    KC_LANGUAGE_9 = 0x0098,
-    
-    KC_ALTERNATE_ERASE = 0x0099,
-    KC_SYSTEM_REQUEST = 0x009A,
-    KC_CANCEL = 0x009B,
-    KC_CLEAR = 0x009C,
-    KC_PRIOR = 0x009D,
-    KC_RETURN = 0x009E,
-    KC_SEPARATOR = 0x009F,
-    KC_OUT = 0x00A0,
-    KC_OPER = 0x00A1,
-    KC_CLEAR_AGAIN = 0x00A2,
-    KC_CRSEL = 0x00A3,
-    KC_EXSEL = 0x00A4,
-    // figure out how qmk uses these keycodes/do more testing
-    // KC_SYSTEM_POWER = 0x00A5,
-    // KC_SYSTEM_SLEEP = 0x00A6,
-    // KC_SYSTEM_WAKE = 0x00A7,
-    // KC_AUDIO_MUTE = 0x00A8,
-    // KC_AUDIO_VOL_UP = 0x00A9,
-    // KC_AUDIO_VOL_DOWN = 0x00AA,
-    // KC_MEDIA_NEXT_TRACK = 0x00AB,
-    // KC_MEDIA_PREV_TRACK = 0x00AC,
-    // KC_MEDIA_STOP = 0x00AD,
-    // KC_MEDIA_PLAY_PAUSE = 0x00AE,
-    // KC_MEDIA_SELECT = 0x00AF,
-    // KC_MEDIA_EJECT = 0x00B0,
-    // KC_MAIL = 0x00B1,
-    // KC_CALCULATOR = 0x00B2,
-    // KC_MY_COMPUTER = 0x00B3,
-    // KC_WWW_SEARCH = 0x00B4,
-    // KC_WWW_HOME = 0x00B5,
-    // KC_WWW_BACK = 0x00B6,
-    // KC_WWW_FORWARD = 0x00B7,
-    // KC_WWW_STOP = 0x00B8,
-    // KC_WWW_REFRESH = 0x00B9,
-    // KC_WWW_FAVORITES = 0x00BA,
-    // KC_MEDIA_FAST_FORWARD = 0x00BB,
-    // KC_MEDIA_REWIND = 0x00BC,
-    // KC_BRIGHTNESS_UP = 0x00BD,
-    // KC_BRIGHTNESS_DOWN = 0x00BE,
-    // KC_CONTROL_PANEL = 0x00BF,
-    // KC_ASSISTANT = 0x00C0,
-    // KC_MISSION_CONTROL = 0x00C1,
-    // KC_LAUNCHPAD = 0x00C2,
-
-    KC_LEFT_CTRL = 0xE0,
-    KC_LEFT_SHIFT = 0xE1,
-    KC_LEFT_ALT = 0xE2,
-    KC_LEFT_GUI = 0xE3,
-    KC_RIGHT_CTRL = 0xE4,
-    KC_RIGHT_SHIFT = 0xE5,
-    KC_RIGHT_ALT = 0xE6,
-    KC_RIGHT_GUI = 0xE7,
-
-    KC_MNEXT = 0xB5,
-    KC_MPREV = 0xB6,
-    KC_MSTOP = 0xB7,
-    KC_MPLAY_PAUSE = 0xCD,
+//! Keycodes and the HID usage each one sends.
+//!
+//! Every key without parameters is listed once in the `keycodes!` table below, which
+//! generates the enum, its usage, its category, its name and the list of every such key.
+
+use core::fmt;
+
+use crate::macros::MacroStep;
+use crate::tap_dance::TapDance;
+
+/// A usage on one of the HID pages a keyboard reports.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Usage {
+    /// Keyboard/keypad page, sent in the NKRO keyboard report.
+    Keyboard(u8),
+    /// Consumer page, sent in the consumer control report.
+    Consumer(u16),
+    /// Generic desktop page, sent in the system control report.
+    System(u8),
+}
+
+/// A group of keys handled by the keyboard itself instead of being sent as a usage.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Category {
+    /// Mouse keys, sent on the mouse report.
+    Mouse,
+    /// RGB underglow keys.
+    Rgb,
+}
+
+// defines `Keycode` from a table of keys without parameters and their optional usage or category,
+// followed by the keys with parameters
+macro_rules! keycodes {
+    (@usage) => {
+        None
+    };
+    (@usage $page:ident($code:literal)) => {
+        Some(Usage::$page($code))
+    };
+    (@category) => {
+        None
+    };
+    (@category $category:ident) => {
+        Some(Category::$category)
+    };
+    (
+        $($key:ident $(= $page:ident($code:literal))? $(: $category:ident)?,)*
+        ;
+        $($(#[$attr:meta])* $param_key:ident($($param:ty),*),)*
+    ) => {
+        #[derive(Copy, Clone, PartialEq, Eq)]
+        #[allow(non_camel_case_types)]
+        pub enum Keycode {
+            $($key,)*
+            $($(#[$attr])* $param_key($($param),*),)*
+        }
+
+        impl Keycode {
+            /// Every key without parameters.
+            pub const ALL: &'static [Self] = &[$(Self::$key),*];
+
+            /// Returns the usage sent while the key is pressed, `None` for keys handled by the
+            /// keyboard itself.
+            #[must_use]
+            pub const fn hid_usage(&self) -> Option<Usage> {
+                match self {
+                    $(Self::$key => keycodes!(@usage $($page($code))?),)*
+                    _ => None,
+                }
+            }
+
+            /// Returns the group of keys handled by the keyboard the key is in, if any.
+            #[must_use]
+            pub const fn category(&self) -> Option<Category> {
+                match self {
+                    $(Self::$key => keycodes!(@category $($category)?),)*
+                    _ => None,
+                }
+            }
+
+            /// Returns the name of the variant, without its parameters.
+            #[must_use]
+            pub const fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$key => stringify!($key),)*
+                    $(Self::$param_key(..) => stringify!($param_key),)*
+                }
+            }
+
+            /// Returns the key without parameters called `name`, like `"KC_A"`.
+            #[must_use]
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $(stringify!($key) => Some(Self::$key),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+keycodes! {
+    KC_NO,
+
+    KC_A = Keyboard(0x04),
+    KC_B = Keyboard(0x05),
+    KC_C = Keyboard(0x06),
+    KC_D = Keyboard(0x07),
+    KC_E = Keyboard(0x08),
+    KC_F = Keyboard(0x09),
+    KC_G = Keyboard(0x0A),
+    KC_H = Keyboard(0x0B),
+    KC_I = Keyboard(0x0C),
+    KC_J = Keyboard(0x0D),
+    KC_K = Keyboard(0x0E),
+    KC_L = Keyboard(0x0F),
+    KC_M = Keyboard(0x10),
+    KC_N = Keyboard(0x11),
+    KC_O = Keyboard(0x12),
+    KC_P = Keyboard(0x13),
+    KC_Q = Keyboard(0x14),
+    KC_R = Keyboard(0x15),
+    KC_S = Keyboard(0x16),
+    KC_T = Keyboard(0x17),
+    KC_U = Keyboard(0x18),
+    KC_V = Keyboard(0x19),
+    KC_W = Keyboard(0x1A),
+    KC_X = Keyboard(0x1B),
+    KC_Y = Keyboard(0x1C),
+    KC_Z = Keyboard(0x1D),
+
+    KC_1 = Keyboard(0x1E),
+    KC_2 = Keyboard(0x1F),
+    KC_3 = Keyboard(0x20),
+    KC_4 = Keyboard(0x21),
+    KC_5 = Keyboard(0x22),
+    KC_6 = Keyboard(0x23),
+    KC_7 = Keyboard(0x24),
+    KC_8 = Keyboard(0x25),
+    KC_9 = Keyboard(0x26),
+    KC_0 = Keyboard(0x27),
+
+    KC_ENTER = Keyboard(0x28),
+    KC_ESCAPE = Keyboard(0x29),
+    KC_BACKSPACE = Keyboard(0x2A),
+    KC_TAB = Keyboard(0x2B),
+    KC_SPACE = Keyboard(0x2C),
+    KC_MINUS = Keyboard(0x2D),
+    KC_EQUAL = Keyboard(0x2E),
+    KC_LEFT_BRACKET = Keyboard(0x2F),
+    KC_RIGHT_BRACKET = Keyboard(0x30),
+    KC_BACKSLASH = Keyboard(0x31),
+    KC_NONUS_HASH = Keyboard(0x32),
+    KC_SEMICOLON = Keyboard(0x33),
+    KC_QUOTE = Keyboard(0x34),
+    KC_GRAVE = Keyboard(0x35),
+    KC_COMMA = Keyboard(0x36),
+    KC_DOT = Keyboard(0x37),
+    KC_SLASH = Keyboard(0x38),
+    KC_CAPS_LOCK = Keyboard(0x39),
+
+    KC_F1 = Keyboard(0x3A),
+    KC_F2 = Keyboard(0x3B),
+    KC_F3 = Keyboard(0x3C),
+    KC_F4 = Keyboard(0x3D),
+    KC_F5 = Keyboard(0x3E),
+    KC_F6 = Keyboard(0x3F),
+    KC_F7 = Keyboard(0x40),
+    KC_F8 = Keyboard(0x41),
+    KC_F9 = Keyboard(0x42),
+    KC_F10 = Keyboard(0x43),
+    KC_F11 = Keyboard(0x44),
+    KC_F12 = Keyboard(0x45),
+
+    KC_PRINTSCREEN = Keyboard(0x46),
+    KC_SCROLL_LOCK = Keyboard(0x47),
+    KC_PAUSE = Keyboard(0x48),
+    KC_INSERT = Keyboard(0x49),
+    KC_HOME = Keyboard(0x4A),
+    KC_PAGEUP = Keyboard(0x4B),
+    KC_DELETE_FORWARD = Keyboard(0x4C),
+    KC_END = Keyboard(0x4D),
+    KC_PAGEDOWN = Keyboard(0x4E),
+
+    KC_RIGHT_ARROW = Keyboard(0x4F),
+    KC_LEFT_ARROW = Keyboard(0x50),
+    KC_DOWN_ARROW = Keyboard(0x51),
+    KC_UP_ARROW = Keyboard(0x52),
+
+    KC_NUM_LOCK = Keyboard(0x53),
+    KC_KEYPAD_FOWARDSLASH = Keyboard(0x54),
+    KC_KEYPAD_ASTERISK = Keyboard(0x55),
+    KC_KEYPAD_MINUS = Keyboard(0x56),
+    KC_KEYPAD_PLUS = Keyboard(0x57),
+    KC_KEYPAD_ENTER = Keyboard(0x58),
+    KC_KEYPAD_1 = Keyboard(0x59),
+    KC_KEYPAD_2 = Keyboard(0x5A),
+    KC_KEYPAD_3 = Keyboard(0x5B),
+    KC_KEYPAD_4 = Keyboard(0x5C),
+    KC_KEYPAD_5 = Keyboard(0x5D),
+    KC_KEYPAD_6 = Keyboard(0x5E),
+    KC_KEYPAD_7 = Keyboard(0x5F),
+    KC_KEYPAD_8 = Keyboard(0x60),
+    KC_KEYPAD_9 = Keyboard(0x61),
+    KC_KEYPAD_0 = Keyboard(0x62),
+
+    KC_KEYPAD_DOT = Keyboard(0x63),
+    KC_NONUS_BACKSLASH = Keyboard(0x64),
+    KC_APP = Keyboard(0x65),
+    KC_POWER = Keyboard(0x66),
+    KC_KEYPAD_EQUAL = Keyboard(0x67),
+    KC_F13 = Keyboard(0x68),
+    KC_F14 = Keyboard(0x69),
+    KC_F15 = Keyboard(0x6A),
+    KC_F16 = Keyboard(0x6B),
+    KC_F17 = Keyboard(0x6C),
+    KC_F18 = Keyboard(0x6D),
+    KC_F19 = Keyboard(0x6E),
+    KC_F20 = Keyboard(0x6F),
+    KC_F21 = Keyboard(0x70),
+    KC_F22 = Keyboard(0x71),
+    KC_F23 = Keyboard(0x72),
+    KC_F24 = Keyboard(0x73),
+
+    KC_EXECUTE = Keyboard(0x74),
+    KC_HELP = Keyboard(0x75),
+    KC_MENU = Keyboard(0x76),
+    KC_SELECT = Keyboard(0x77),
+    KC_STOP = Keyboard(0x78),
+    KC_AGAIN = Keyboard(0x79),
+    KC_UNDO = Keyboard(0x7A),
+    KC_CUT = Keyboard(0x7B),
+    KC_PASTE = Keyboard(0x7D),
+    KC_FIND = Keyboard(0x7E),
+
+    // sent on the consumer page
+    KC_MUTE = Consumer(0xE2),
+    KC_VOLUP = Consumer(0xE9),
+    KC_VOLDOWN = Consumer(0xEA),
+
+    KC_LOCKING_CAPS_LOCK = Keyboard(0x82),
+    KC_LOCKING_NUM_LOCK = Keyboard(0x83),
+    KC_LOCKING_SCROLL_LOCK = Keyboard(0x84),
+    KC_KEYPAD_COMMA = Keyboard(0x85),
+    KC_EQUAL_SIGN = Keyboard(0x86),
+    KC_INTERNATIONAL_1 = Keyboard(0x87),
+    KC_INTERNATIONAL_2 = Keyboard(0x88),
+    KC_INTERNATIONAL_3 = Keyboard(0x89),
+    KC_INTERNATIONAL_4 = Keyboard(0x8A),
+    KC_INTERNATIONAL_5 = Keyboard(0x8B),
+    KC_INTERNATIONAL_6 = Keyboard(0x8C),
+    KC_INTERNATIONAL_7 = Keyboard(0x8D),
+    KC_INTERNATIONAL_8 = Keyboard(0x8E),
+    KC_INTERNATIONAL_9 = Keyboard(0x8F),
+    KC_LANGUAGE_1 = Keyboard(0x90),
+    KC_LANGUAGE_2 = Keyboard(0x91),
+    KC_LANGUAGE_3 = Keyboard(0x92),
+    KC_LANGUAGE_4 = Keyboard(0x93),
+    KC_LANGUAGE_5 = Keyboard(0x94),
+    KC_LANGUAGE_6 = Keyboard(0x95),
+    KC_LANGUAGE_7 = Keyboard(0x96),
+    KC_LANGUAGE_8 = Keyboard(0x97),
+    KC_LANGUAGE_9 = Keyboard(0x98),
+
+    KC_ALTERNATE_ERASE = Keyboard(0x99),
+    KC_SYSTEM_REQUEST = Keyboard(0x9A),
+    KC_CANCEL = Keyboard(0x9B),
+    KC_CLEAR = Keyboard(0x9C),
+    KC_PRIOR = Keyboard(0x9D),
+    KC_RETURN = Keyboard(0x9E),
+    KC_SEPARATOR = Keyboard(0x9F),
+    KC_OUT = Keyboard(0xA0),
+    KC_OPER = Keyboard(0xA1),
+    KC_CLEAR_AGAIN = Keyboard(0xA2),
+    KC_CRSEL = Keyboard(0xA3),
+    KC_EXSEL = Keyboard(0xA4),
+    KC_LEFT_CTRL = Keyboard(0xE0),
+    KC_LEFT_SHIFT = Keyboard(0xE1),
+    KC_LEFT_ALT = Keyboard(0xE2),
+    KC_LEFT_GUI = Keyboard(0xE3),
+    KC_RIGHT_CTRL = Keyboard(0xE4),
+    KC_RIGHT_SHIFT = Keyboard(0xE5),
+    KC_RIGHT_ALT = Keyboard(0xE6),
+    KC_RIGHT_GUI = Keyboard(0xE7),
+
+    // mouse keys, sent on the mouse report
+    KC_MS_UP: Mouse,
+    KC_MS_DOWN: Mouse,
+    KC_MS_LEFT: Mouse,
+    KC_MS_RIGHT: Mouse,
+    KC_BTN1: Mouse,
+    KC_BTN2: Mouse,
+    KC_BTN3: Mouse,
+    KC_BTN4: Mouse,
+    KC_BTN5: Mouse,
+    KC_WH_UP: Mouse,
+    KC_WH_DOWN: Mouse,
+    KC_WH_LEFT: Mouse,
+    KC_WH_RIGHT: Mouse,
+
+    // system control, sent on the generic desktop page
+    KC_SYSTEM_POWER = System(0x81),
+    KC_SYSTEM_SLEEP = System(0x82),
+    KC_SYSTEM_WAKE = System(0x83),
+
+    // consumer control, along with KC_MUTE, KC_VOLUP, KC_VOLDOWN and the KC_M* media keys
+    KC_MEDIA_SELECT = Consumer(0x183),
+    KC_MEDIA_EJECT = Consumer(0xB8),
+    KC_MEDIA_FAST_FORWARD = Consumer(0xB3),
+    KC_MEDIA_REWIND = Consumer(0xB4),
+    KC_MAIL = Consumer(0x18A),
+    KC_CALCULATOR = Consumer(0x192),
+    KC_MY_COMPUTER = Consumer(0x194),
+    KC_WWW_SEARCH = Consumer(0x221),
+    KC_WWW_HOME = Consumer(0x223),
+    KC_WWW_BACK = Consumer(0x224),
+    KC_WWW_FORWARD = Consumer(0x225),
+    KC_WWW_STOP = Consumer(0x226),
+    KC_WWW_REFRESH = Consumer(0x227),
+    KC_WWW_FAVORITES = Consumer(0x22A),
+    KC_BRIGHTNESS_UP = Consumer(0x6F),
+    KC_BRIGHTNESS_DOWN = Consumer(0x70),
+    KC_CONTROL_PANEL = Consumer(0x19F),
+    KC_ASSISTANT = Consumer(0x1CB),
+    KC_MISSION_CONTROL = Consumer(0x29F),
+    KC_LAUNCHPAD = Consumer(0x2A0),
+
+    KC_MNEXT = Consumer(0xB5),
+    KC_MPREV = Consumer(0xB6),
+    KC_MSTOP = Consumer(0xB7),
+    KC_MPLAY_PAUSE = Consumer(0xCD),
+
+    // rgb underglow, handled by the keyboard
+    KC_RGB_TOG: Rgb,
+    KC_RGB_MOD: Rgb,
+    KC_RGB_HUI: Rgb,
+    KC_RGB_HUD: Rgb,
+    KC_RGB_SAI: Rgb,
+    KC_RGB_SAD: Rgb,
+    KC_RGB_VAI: Rgb,
+    KC_RGB_VAD: Rgb,
+
+    // starts a leader sequence, handled by the keyboard
+    KC_LEAD,
 
-    KC_LAYER(usize),
-    KC_MO(usize),
     KC_NO_KEY,
     KC_TRANS,
+    ;
 
+    KC_LAYER(usize),
+    KC_MO(usize),
+    // toggles a layer on or off
+    KC_TG(usize),
+    // activates a layer for the next key press
+    KC_OSL(usize),
+    // sets the default layer
+    KC_DF(usize),
+    // activates a layer and deactivates every other layer but the default
+    KC_TO(usize),
+    // sends the second key when tapped and the first key, usually a modifier, when held
+    KC_MT(&'static Keycode, &'static Keycode),
+    // sends the key when tapped and activates the layer when held
+    KC_LT(usize, &'static Keycode),
+    // plays the macro steps when pressed
+    KC_MACRO(&'static [MacroStep]),
+    // does one of the tap dance actions depending on how many times it's tapped or if it's held
+    KC_TD(&'static TapDance),
     KEYS_2(&'static Keycode, &'static Keycode),
 }
 
 impl Keycode {
     #[allow(clippy::must_use_candidate)]
     pub const fn is_consumer(&self) -> bool {
-        matches!(
-            self,
-            Self::KC_MNEXT
-                | Self::KC_MPREV
-                | Self::KC_MSTOP
-                | Self::KC_MPLAY_PAUSE
-                | Self::KC_MUTE
-                | Self::KC_VOLUP
-                | Self::KC_VOLDOWN
-        )
+        self.into_consumer().is_some()
+    }
+
+    #[allow(clippy::must_use_candidate)]
+    pub const fn is_system(&self) -> bool {
+        self.into_system().is_some()
+    }
+
+    #[allow(clippy::must_use_candidate)]
+    pub const fn is_mouse(&self) -> bool {
+        matches!(self.category(), Some(Category::Mouse))
+    }
+
+    #[allow(clippy::must_use_candidate)]
+    pub const fn is_rgb(&self) -> bool {
+        matches!(self.category(), Some(Category::Rgb))
     }
 
+    // usage on the keyboard page
+    #[allow(clippy::must_use_candidate)]
+    pub const fn into_keyboard(&self) -> Option<u8> {
+        match self.hid_usage() {
+            Some(Usage::Keyboard(usage)) => Some(usage),
+            _ => None,
+        }
+    }
+
+    // usage on the consumer page
     #[allow(clippy::must_use_candidate)]
     pub const fn into_consumer(&self) -> Option<u16> {
-        match self {
-            Self::KC_MNEXT => Some(0xB5),
-            Self::KC_MPREV => Some(0xB6),
-            Self::KC_MSTOP => Some(0xB7),
-            Self::KC_MPLAY_PAUSE => Some(0xCD),
-            Self::KC_MUTE => Some(0xE2),
-            Self::KC_VOLUP => Some(0xE9),
-            Self::KC_VOLDOWN => Some(0xEA),
+        match self.hid_usage() {
+            Some(Usage::Consumer(usage)) => Some(usage),
+            _ => None,
+        }
+    }
+
+    // usage on the generic desktop page
+    #[allow(clippy::must_use_candidate)]
+    pub const fn into_system(&self) -> Option<u8> {
+        match self.hid_usage() {
+            Some(Usage::System(usage)) => Some(usage),
             _ => None,
         }
     }
 }
 
-impl TryInto<u8> for Keycode {
-    type Error = &'static str;
-    fn try_into(self) -> Result<u8, Self::Error> {
+impl fmt::Display for Keycode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())?;
         match self {
-            KC_NO => Ok(0x00),
-            KC_A => Ok(0x04),
-            KC_B => Ok(0x05),
-            KC_C => Ok(0x06),
-            KC_D => Ok(0x07),
-            KC_E => Ok(0x08),
-            KC_F => Ok(0x09),
-            KC_G => Ok(0x0a),
-            KC_H => Ok(0x0b),
-            KC_I => Ok(0x0c),
-            KC_J => Ok(0x0d),
-            KC_K => Ok(0x0e),
-            KC_L => Ok(0x0f),
-            KC_M => Ok(0x10),
-            KC_N => Ok(0x11),
-            KC_O => Ok(0x12),
-            KC_P => Ok(0x13),
-            KC_Q => Ok(0x14),
-            KC_R => Ok(0x15),
-            KC_S => Ok(0x16),
-            KC_T => Ok(0x17),
-            KC_U => Ok(0x18),
-            KC_V => Ok(0x19),
-            KC_W => Ok(0x1a),
-            KC_X => Ok(0x1b),
-            KC_Y => Ok(0x1c),
-            KC_Z => Ok(0x1d),
-            KC_1 => Ok(0x1e),
-            KC_2 => Ok(0x1f),
-            KC_3 => Ok(0x20),
-            KC_4 => Ok(0x21),
-            KC_5 => Ok(0x22),
-            KC_6 => Ok(0x23),
-            KC_7 => Ok(0x24),
-            KC_8 => Ok(0x25),
-            KC_9 => Ok(0x26),
-            KC_0 => Ok(0x27),
-            KC_ENTER => Ok(0x28),
-            KC_ESCAPE => Ok(0x29),
-            KC_BACKSPACE => Ok(0x2A),
-            KC_TAB => Ok(0x2B),
-            KC_SPACE => Ok(0x2C),
-            KC_MINUS => Ok(0x2D),
-            KC_EQUAL => Ok(0x2E),
-            KC_LEFT_BRACKET => Ok(0x2F),
-            KC_RIGHT_BRACKET => Ok(0x30),
-            KC_BACKSLASH => Ok(0x31),
-            KC_NONUS_HASH => Ok(0x32),
-            KC_SEMICOLON => Ok(0x33),
-            KC_QUOTE => Ok(0x34),
-            KC_GRAVE => Ok(0x35),
-            KC_COMMA => Ok(0x36),
-            KC_DOT => Ok(0x37),
-            KC_SLASH => Ok(0x38),
-            KC_CAPS_LOCK => Ok(0x39),
-            KC_F1 => Ok(0x3A),
-            KC_F2 => Ok(0x3B),
-            KC_F3 => Ok(0x3C),
-            KC_F4 => Ok(0x3D),
-            KC_F5 => Ok(0x3E),
-            KC_F6 => Ok(0x3F),
-            KC_F7 => Ok(0x40),
-            KC_F8 => Ok(0x41),
-            KC_F9 => Ok(0x42),
-            KC_F10 => Ok(0x43),
-            KC_F11 => Ok(0x44),
-            KC_F12 => Ok(0x45),
-            KC_PRINTSCREEN => Ok(0x46),
-            KC_SCROLL_LOCK => Ok(0x47),
-            KC_PAUSE => Ok(0x48),
-            KC_INSERT => Ok(0x49),
-            KC_HOME => Ok(0x4A),
-            KC_PAGEUP => Ok(0x4B),
-            KC_DELETE_FORWARD => Ok(0x4C),
-            KC_END => Ok(0x4D),
-            KC_PAGEDOWN => Ok(0x4E),
-            KC_RIGHT_ARROW => Ok(0x4F),
-            KC_LEFT_ARROW => Ok(0x50),
-            KC_DOWN_ARROW => Ok(0x51),
-            KC_UP_ARROW => Ok(0x52),
-            KC_NUM_LOCK => Ok(0x53),
-            KC_KEYPAD_FOWARDSLASH => Ok(0x54),
-            KC_KEYPAD_ASTERISK => Ok(0x55),
-            KC_KEYPAD_MINUS => Ok(0x56),
-            KC_KEYPAD_PLUS => Ok(0x57),
-            KC_KEYPAD_ENTER => Ok(0x58),
-            KC_KEYPAD_1 => Ok(0x59),
-            KC_KEYPAD_2 => Ok(0x5a),
-            KC_KEYPAD_3 => Ok(0x5b),
-            KC_KEYPAD_4 => Ok(0x5c),
-            KC_KEYPAD_5 => Ok(0x5d),
-            KC_KEYPAD_6 => Ok(0x5e),
-            KC_KEYPAD_7 => Ok(0x5f),
-            KC_KEYPAD_8 => Ok(0x60),
-            KC_KEYPAD_9 => Ok(0x61),
-            KC_KEYPAD_0 => Ok(0x62),
-            KC_KEYPAD_DOT => Ok(0x63),
-            KC_NONUS_BACKSLASH => Ok(0x64),
-            KC_APP => Ok(0x65),
-            KC_POWER => Ok(0x66),
-            KC_KEYPAD_EQUAL => Ok(0x67),
-            KC_F13 => Ok(0x68),
-            KC_F14 => Ok(0x69),
-            KC_F15 => Ok(0x6a),
-            KC_F16 => Ok(0x6b),
-            KC_F17 => Ok(0x6c),
-            KC_F18 => Ok(0x6d),
-            KC_F19 => Ok(0x6e),
-            KC_F20 => Ok(0x6f),
-            KC_F21 => Ok(0x70),
-            KC_F22 => Ok(0x71),
-            KC_F23 => Ok(0x72),
-            KC_F24 => Ok(0x73),
-            KC_EXECUTE => Ok(0x74),
-            KC_HELP => Ok(0x75),
-            KC_MENU => Ok(0x76),
-            KC_SELECT => Ok(0x77),
-            KC_STOP => Ok(0x78),
-            KC_AGAIN => Ok(0x79),
-            KC_UNDO => Ok(0x7A),
-            KC_CUT => Ok(0x7B),
-            KC_PASTE => Ok(0x7D),
-            KC_FIND => Ok(0x7E),
-            KC_MUTE => Ok(0x7f),
-            KC_VOLUP => Ok(0x80),
-            KC_VOLDOWN => Ok(0x81),
-            KC_LOCKING_CAPS_LOCK => Ok(0x0082),
-            KC_LOCKING_NUM_LOCK => Ok(0x0083),
-            KC_LOCKING_SCROLL_LOCK => Ok(0x0084),
-            KC_KEYPAD_COMMA => Ok(0x85),
-            KC_EQUAL_SIGN => Ok(0x86),
-            KC_INTERNATIONAL_1 => Ok(0x0087),
-            KC_INTERNATIONAL_2 => Ok(0x0088),
-            KC_INTERNATIONAL_3 => Ok(0x0089),
-            KC_INTERNATIONAL_4 => Ok(0x008A),
-            KC_INTERNATIONAL_5 => Ok(0x008B),
-            KC_INTERNATIONAL_6 => Ok(0x008C),
-            KC_INTERNATIONAL_7 => Ok(0x008D),
-            KC_INTERNATIONAL_8 => Ok(0x008E),
-            KC_INTERNATIONAL_9 => Ok(0x008F),
-            KC_LANGUAGE_1 => Ok(0x0090),
-            KC_LANGUAGE_2 => Ok(0x0091),
-            KC_LANGUAGE_3 => Ok(0x0092),
-            KC_LANGUAGE_4 => Ok(0x0093),
-            KC_LANGUAGE_5 => Ok(0x0094),
-            KC_LANGUAGE_6 => Ok(0x0095),
-            KC_LANGUAGE_7 => Ok(0x0096),
-            KC_LANGUAGE_8 => Ok(0x0097),
-            KC_LANGUAGE_9 => Ok(0x0098),
-            KC_ALTERNATE_ERASE => Ok(0x0099),
-            KC_SYSTEM_REQUEST => Ok(0x009A),
-            KC_CANCEL => Ok(0x009B),
-            KC_CLEAR => Ok(0x009C),
-            KC_PRIOR => Ok(0x009D),
-            KC_RETURN => Ok(0x009E),
-            KC_SEPARATOR => Ok(0x009F),
-            KC_OUT => Ok(0x00A0),
-            KC_OPER => Ok(0x00A1),
-            KC_CLEAR_AGAIN => Ok(0x00A2),
-            KC_CRSEL => Ok(0x00A3),
-            KC_EXSEL => Ok(0x00A4),
-            // KC_SYSTEM_POWER => Ok(0x00A5),
-            // KC_SYSTEM_SLEEP => Ok(0x00A6),
-            // KC_SYSTEM_WAKE => Ok(0x00A7),
-            // KC_AUDIO_MUTE => Ok(0x00A8),
-            // KC_AUDIO_VOL_UP => Ok(0x00A9),
-            // KC_AUDIO_VOL_DOWN => Ok(0x00AA),
-            // KC_MEDIA_NEXT_TRACK => Ok(0x00AB),
-            // KC_MEDIA_PREV_TRACK => Ok(0x00AC),
-            // KC_MEDIA_STOP => Ok(0x00AD),
-            // KC_MEDIA_PLAY_PAUSE => Ok(0x00AE),
-            // KC_MEDIA_SELECT => Ok(0x00AF),
-            // KC_MEDIA_EJECT => Ok(0x00B0),
-            // KC_MAIL => Ok(0x00B1),
-            // KC_CALCULATOR => Ok(0x00B2),
-            // KC_MY_COMPUTER => Ok(0x00B3),
-            // KC_WWW_SEARCH => Ok(0x00B4),
-            // KC_WWW_STOP => Ok(0x00B8),
-            // KC_WWW_REFRESH => Ok(0x00B9),
-            // KC_WWW_FAVORITES => Ok(0x00BA),
-            // KC_MEDIA_FAST_FORWARD => Ok(0x00BB),
-            // KC_MEDIA_REWIND => Ok(0x00BC),
-            // KC_BRIGHTNESS_UP => Ok(0x00BD),
-            // KC_BRIGHTNESS_DOWN => Ok(0x00BE),
-            // KC_CONTROL_PANEL => Ok(0x00BF),
-            // KC_ASSISTANT => Ok(0x00C0),
-            // KC_MISSION_CONTROL => Ok(0x00C1),
-            // KC_LAUNCHPAD => Ok(0x00C2),
-            KC_LEFT_CTRL => Ok(0xE0),
-            KC_LEFT_SHIFT => Ok(0xE1),
-            KC_LEFT_ALT => Ok(0xE2),
-            KC_LEFT_GUI => Ok(0xE3),
-            KC_RIGHT_CTRL => Ok(0xE4),
-            KC_RIGHT_SHIFT => Ok(0xE5),
-            KC_RIGHT_ALT => Ok(0xE6),
-            KC_RIGHT_GUI => Ok(0xE7),
-            KC_MNEXT => Ok(0xB5),
-            KC_MPREV => Ok(0xB6),
-            KC_MSTOP => Ok(0xB7),
-            KC_MPLAY_PAUSE => Ok(0xCD),
-            _ => Err("Non usb Keycode"),
+            Self::KC_LAYER(layer)
+            | Self::KC_MO(layer)
+            | Self::KC_TG(layer)
+            | Self::KC_OSL(layer)
+            | Self::KC_DF(layer)
+            | Self::KC_TO(layer) => write!(f, "({layer})"),
+            Self::KC_MT(first, second) | Self::KEYS_2(first, second) => {
+                write!(f, "({first}, {second})")
+            }
+            Self::KC_LT(layer, key) => write!(f, "({layer}, {key})"),
+            Self::KC_MACRO(steps) => write!(f, "({} steps)", steps.len()),
+            _ => Ok(()),
         }
     }
 }
 
-impl TryInto<u8> for &Keycode {
-    type Error = &'static str;
-    fn try_into(self) -> Result<u8, Self::Error> {
-        (*self).try_into()
+impl fmt::Debug for Keycode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Category, Keycode, Usage};
+
+    #[test]
+    fn every_key_round_trips_through_its_name_and_usage() {
+        for (index, key) in Keycode::ALL.iter().enumerate() {
+            assert_eq!(Keycode::from_name(key.name()), Some(*key));
+            assert_eq!(key.to_string(), key.name());
+
+            // no two keys send the same usage
+            if let Some(usage) = key.hid_usage() {
+                assert!(
+                    Keycode::ALL[index + 1..]
+                        .iter()
+                        .all(|other| other.hid_usage() != Some(usage)),
+                    "{key} shares its usage"
+                );
+            }
+        }
+
+        assert_eq!(Keycode::from_name("KC_LAYER"), None);
+        assert_eq!(Keycode::from_name("KC_NOT_A_KEY"), None);
+    }
+
+    #[test]
+    fn usages_are_on_their_page() {
+        assert_eq!(Keycode::KC_A.hid_usage(), Some(Usage::Keyboard(0x04)));
+        assert_eq!(Keycode::KC_LEFT_SHIFT.into_keyboard(), Some(0xE1));
+        assert_eq!(Keycode::KC_VOLUP.hid_usage(), Some(Usage::Consumer(0xE9)));
+        assert_eq!(Keycode::KC_SYSTEM_SLEEP.hid_usage(), Some(Usage::System(0x82)));
+        assert_eq!(Keycode::KC_NO.hid_usage(), None);
+        assert_eq!(Keycode::KC_MS_UP.hid_usage(), None);
+        assert_eq!(Keycode::KC_MO(1).hid_usage(), None);
+    }
+
+    #[test]
+    fn categories_come_from_the_table() {
+        assert_eq!(Keycode::KC_BTN1.category(), Some(Category::Mouse));
+        assert_eq!(Keycode::KC_RGB_VAD.category(), Some(Category::Rgb));
+        assert_eq!(Keycode::KC_A.category(), None);
+        assert_eq!(Keycode::KC_MO(1).category(), None);
+        assert_eq!(Keycode::ALL.iter().filter(|key| key.is_mouse()).count(), 13);
+        assert_eq!(Keycode::ALL.iter().filter(|key| key.is_rgb()).count(), 8);
+    }
+
+    #[test]
+    fn parameters_are_displayed() {
+        assert_eq!(Keycode::KC_MO(2).to_string(), "KC_MO(2)");
+        assert_eq!(
+            Keycode::KC_MT(&Keycode::KC_LEFT_SHIFT, &Keycode::KC_A).to_string(),
+            "KC_MT(KC_LEFT_SHIFT, KC_A)"
+        );
     }
 }
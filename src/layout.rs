@@ -0,0 +1,265 @@
+//! Layouts checked at compile time.
+//!
+//! ```
+//! # use keyboard_rs::keycode::Keycode::*;
+//! # use keyboard_rs::layout;
+//! layout! {
+//!     const KEYS: Layout<NUMOFCOL, NUMOFROW, NUMOFLAYES> = [
+//!         [
+//!             [KC_A, KC_B, KC_NO_KEY],
+//!             [KC_D, KC_E, KC_MO(1)],
+//!         ],
+//!         [
+//!             [KC_F, KC_G, KC_NO_KEY],
+//!             [KC_I, KC_J, KC_TRANS],
+//!         ],
+//!     ];
+//! }
+//! ```
+//!
+//! Defines `KEYS` as a `Layout<3, 2, 2>` along with the `NUMOFCOL`, `NUMOFROW` and `NUMOFLAYES`
+//! constants. Positions without a physical key are filled with `KC_NO_KEY`. Rows of different
+//! lengths, layers with a different number of rows, more than [`MAX_LAYERS`] layers and layer keys
+//! referring to a layer that doesn't exist fail to compile.
+
+use crate::keycode::Keycode;
+use crate::tap_dance::Action;
+
+/// The most layers a layout can have.
+pub const MAX_LAYERS: usize = 32;
+
+/// Every layer of a keyboard, indexed as `[layer][row][col]`.
+pub type Layout<const COLS: usize, const ROWS: usize, const LAYERS: usize> =
+    [[[Keycode; COLS]; ROWS]; LAYERS];
+
+/// Defines a [`Layout`] and infers its dimensions, see the [module docs](self).
+///
+/// A bad layout fails to compile, like one with a ragged row
+///
+/// ```compile_fail,E0080
+/// use keyboard_rs::keycode::Keycode::*;
+///
+/// keyboard_rs::layout! {
+///     const KEYS: Layout<COLS, ROWS, LAYERS> = [
+///         [
+///             [KC_A, KC_B, KC_C],
+///             [KC_D, KC_E],
+///         ],
+///     ];
+/// }
+/// ```
+///
+/// or a layer with a missing row
+///
+/// ```compile_fail,E0080
+/// use keyboard_rs::keycode::Keycode::*;
+///
+/// keyboard_rs::layout! {
+///     const KEYS: Layout<COLS, ROWS, LAYERS> = [
+///         [
+///             [KC_A, KC_MO(1)],
+///             [KC_C, KC_D],
+///         ],
+///         [
+///             [KC_E, KC_TRANS],
+///         ],
+///     ];
+/// }
+/// ```
+///
+/// or a layer key past the last layer.
+///
+/// ```compile_fail,E0080
+/// use keyboard_rs::keycode::Keycode::*;
+///
+/// keyboard_rs::layout! {
+///     const KEYS: Layout<COLS, ROWS, LAYERS> = [
+///         [
+///             [KC_A, KC_MO(1)],
+///         ],
+///     ];
+/// }
+/// ```
+#[macro_export]
+macro_rules! layout {
+    (
+        $(#[$attr:meta])*
+        $vis:vis const $name:ident: Layout<$cols:ident, $rows:ident, $layers:ident> = [
+            $([
+                $([$($key:expr),* $(,)?]),* $(,)?
+            ]),* $(,)?
+        ];
+    ) => {
+        // holds the keys as written, in the type namespace so it doesn't clash with `$name`
+        #[doc(hidden)]
+        #[allow(dead_code, non_camel_case_types, clippy::upper_case_acronyms)]
+        struct $name {}
+
+        impl $name {
+            const SLICES: &'static [&'static [&'static [$crate::keycode::Keycode]]] =
+                &[$(&[$(&[$($key),*]),*]),*];
+        }
+
+        $vis const $layers: usize = $name::SLICES.len();
+        $vis const $rows: usize = $crate::layout::rows($name::SLICES);
+        $vis const $cols: usize = $crate::layout::cols($name::SLICES);
+        $(#[$attr])*
+        $vis const $name: $crate::layout::Layout<$cols, $rows, $layers> =
+            $crate::layout::from_slices($name::SLICES);
+    };
+}
+
+#[doc(hidden)]
+#[must_use]
+pub const fn rows(layout: &[&[&[Keycode]]]) -> usize {
+    if layout.is_empty() {
+        panic!("a layout needs at least one layer");
+    }
+
+    layout[0].len()
+}
+
+#[doc(hidden)]
+#[must_use]
+pub const fn cols(layout: &[&[&[Keycode]]]) -> usize {
+    if rows(layout) == 0 {
+        panic!("a layout needs at least one row");
+    }
+
+    layout[0][0].len()
+}
+
+// returns if the keycode activates a layer past the last one
+const fn refers_to_missing_layer(keycode: &Keycode, layers: usize) -> bool {
+    match keycode {
+        Keycode::KC_LAYER(layer)
+        | Keycode::KC_MO(layer)
+        | Keycode::KC_TG(layer)
+        | Keycode::KC_OSL(layer)
+        | Keycode::KC_DF(layer)
+        | Keycode::KC_TO(layer)
+        | Keycode::KC_LT(layer, _) => *layer >= layers,
+        Keycode::KC_MT(hold, tap) | Keycode::KEYS_2(hold, tap) => {
+            refers_to_missing_layer(hold, layers) || refers_to_missing_layer(tap, layers)
+        }
+        Keycode::KC_TD(dance) => {
+            action_refers_to_missing_layer(Some(dance.tap), layers)
+                || action_refers_to_missing_layer(dance.double_tap, layers)
+                || action_refers_to_missing_layer(dance.hold, layers)
+        }
+        _ => false,
+    }
+}
+
+const fn action_refers_to_missing_layer(action: Option<Action>, layers: usize) -> bool {
+    match action {
+        Some(Action::Key(keycode)) => refers_to_missing_layer(&keycode, layers),
+        Some(Action::Callback(_)) | None => false,
+    }
+}
+
+/// Copies a layout into a fixed size array.
+///
+/// Panics if it doesn't have `LAYERS` layers of `ROWS` rows of `COLS` keys, if it has more than
+/// [`MAX_LAYERS`] layers or if a layer key refers to a layer that doesn't exist.
+///
+/// Meant to be called in a const, so a bad layout fails to compile.
+#[must_use]
+pub const fn from_slices<const COLS: usize, const ROWS: usize, const LAYERS: usize>(
+    layout: &[&[&[Keycode]]],
+) -> Layout<COLS, ROWS, LAYERS> {
+    let mut keys = [[[Keycode::KC_NO; COLS]; ROWS]; LAYERS];
+
+    if layout.len() != LAYERS {
+        panic!("the layout doesn't have the expected number of layers");
+    }
+    if LAYERS > MAX_LAYERS {
+        panic!("a layout can have at most 32 layers");
+    }
+
+    let mut layer = 0;
+    while layer < LAYERS {
+        if layout[layer].len() != ROWS {
+            panic!("every layer of a layout needs the same number of rows");
+        }
+
+        let mut row = 0;
+        while row < ROWS {
+            if layout[layer][row].len() != COLS {
+                panic!("every row of a layout needs the same number of keys");
+            }
+
+            let mut col = 0;
+            while col < COLS {
+                let keycode = layout[layer][row][col];
+                if refers_to_missing_layer(&keycode, LAYERS) {
+                    panic!("a layer key refers to a layer that isn't in the layout");
+                }
+
+                keys[layer][row][col] = keycode;
+                col += 1;
+            }
+            row += 1;
+        }
+        layer += 1;
+    }
+
+    keys
+}
+
+#[cfg(test)]
+mod test {
+    use super::{from_slices, MAX_LAYERS};
+    use crate::keycode::{Keycode, Keycode::*};
+    use crate::tap_dance::{Action, TapDance};
+
+    crate::layout! {
+        const KEYS: Layout<COLS, ROWS, LAYERS> = [
+            [
+                [KC_A, KC_B, KC_NO_KEY],
+                [KC_D, KC_E, KC_LT(1, &KC_F)],
+            ],
+            [
+                [KC_G, KC_H, KC_NO_KEY],
+                [KC_MT(&KC_LEFT_SHIFT, &KC_I), KC_TRANS, KC_TRANS],
+            ],
+        ];
+    }
+
+    #[test]
+    fn infers_dimensions() {
+        assert_eq!((COLS, ROWS, LAYERS), (3, 2, 2));
+        assert!(KEYS[1][0][1] == KC_H);
+        assert!(KEYS[0][0][2] == KC_NO_KEY);
+    }
+
+    fn catch(layout: &'static [&'static [&'static [Keycode]]]) -> bool {
+        std::panic::catch_unwind(|| from_slices::<2, 1, 2>(layout)).is_err()
+    }
+
+    #[test]
+    fn rejects_bad_layouts() {
+        assert!(!catch(&[&[&[KC_A, KC_MO(1)]], &[&[KC_B, KC_C]]]));
+        // ragged row
+        assert!(catch(&[&[&[KC_A, KC_B]], &[&[KC_C]]]));
+        // missing row
+        assert!(catch(&[&[&[KC_A, KC_B]], &[]]));
+        // layer past the last one
+        assert!(catch(&[&[&[KC_A, KC_MO(2)]], &[&[KC_B, KC_C]]]));
+        assert!(catch(&[&[&[KC_A, KC_B]], &[&[KC_LT(2, &KC_C), KC_D]]]));
+        const DANCE: TapDance = TapDance {
+            hold: Some(Action::Key(KC_MO(2))),
+            ..TapDance::new(Action::Key(KC_C))
+        };
+        assert!(catch(&[&[&[KC_A, KC_TD(&DANCE)]], &[&[KC_B, KC_C]]]));
+    }
+
+    #[test]
+    fn rejects_too_many_layers() {
+        const LAYER: &[&[Keycode]] = &[&[KC_A]];
+        const LAYERS: &[&[&[Keycode]]] = &[LAYER; MAX_LAYERS + 1];
+
+        assert!(std::panic::catch_unwind(|| from_slices::<1, 1, 33>(LAYERS)).is_err());
+        assert!(std::panic::catch_unwind(|| from_slices::<1, 1, 32>(&LAYERS[1..])).is_ok());
+    }
+}
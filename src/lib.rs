@@ -1,4 +1,5 @@
-#![no_std]
+// make `std` available when testing
+#![cfg_attr(not(any(test, feature = "testing")), no_std)]
 #![feature(generic_const_exprs)]
 #![deny(
     clippy::correctness,
@@ -9,20 +10,36 @@
     clippy::cargo
 )]
 
+pub mod clock;
+pub mod display;
 #[cfg(feature = "encoders")]
 pub mod hardware;
+pub mod indicator;
 mod keyboard;
 pub mod keycode;
+pub mod layout;
+pub mod macros;
+pub mod rgb;
+pub mod tap_dance;
+pub mod config;
 pub mod debounce;
+pub mod error;
+pub mod split;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
 use cortex_m::prelude::_embedded_hal_watchdog_Watchdog;
 use embedded_hal::digital::v2::InputPin;
 use embedded_hal::digital::v2::OutputPin;
-use embedded_hal::timer::CountDown;
+use clock::Clock;
+use config::{Config, Recovery};
+use cortex_m::peripheral::SCB;
 #[cfg(feature = "encoders")]
 use hardware::encoder::Encoder;
-use keyboard::Keyboard;
-use keycode::Keycode;
+pub use keyboard::{matrix, Keyboard, Secondary};
+use layout::Layout;
+// only on the board, so doctests built on the host can link std's panic handler
+#[cfg(target_os = "none")]
 use panic_halt as _;
 use usb_device::class_prelude::UsbBus;
 use usb_device::class_prelude::UsbBusAllocator;
@@ -30,7 +47,7 @@ use usb_device::class_prelude::UsbBusAllocator;
 #[cfg(feature = "rp2040")]
 use fugit::ExtU32;
 #[cfg(feature = "rp2040")]
-use hal::{timer::CountDown as RPCountDown, Timer, Watchdog as RPWatchdog};
+use hal::{Timer, Watchdog as RPWatchdog};
 #[cfg(feature = "rp2040")]
 use rp2040_hal as hal;
 #[cfg(feature = "rp2040")]
@@ -46,20 +63,6 @@ pub struct Board<Dog: _embedded_hal_watchdog_Watchdog, Usb: UsbBus> {
     watchdog: Dog,
 }
 
-#[cfg(feature = "rp2040")]
-impl Board<rp2040_hal::Watchdog, rp2040_hal::usb::UsbBus> {
-    pub fn setup_timers<'a>(
-        timer0: &'a Timer,
-        timer1: &'a Timer,
-    ) -> (RPCountDown<'a>, RPCountDown<'a>) {
-        let mut timer0 = timer0.count_down();
-        timer0.start(10.millis());
-        let mut timer1 = timer1.count_down();
-        timer1.start(1.millis());
-        (timer0, timer1)
-    }
-}
-
 #[cfg(feature = "rp2040")]
 /// .
 ///
@@ -122,35 +125,33 @@ pub fn matrix_scaning<
     EncoderPin: InputPin,
     Output: OutputPin,
     Input: InputPin,
-    Timer: CountDown,
     Dog: _embedded_hal_watchdog_Watchdog,
     Usb: UsbBus,
 >(
     mut board: Board<Dog, Usb>,
     cols: &mut [Output],
     rows: &mut [Input],
-    keys: &[&[&[Keycode]]],
+    keys: &Layout<COLS, ROWS, LAYERS>,
     encoders: [Encoder<EncoderPin>; NUM_OF_ENCODERS],
-    mut timer0: Timer,
-    mut timer1: Timer,
-    mut timer2: Timer,
+    config: Config,
+    clock: &dyn Clock,
 ) -> !
 where
-    [(); COLS * ROWS + { NUM_OF_ENCODERS }]: Sized, Timer::Time: From<fugit::Duration<u32, 1, 1000000>>
+    [(); COLS * ROWS + { NUM_OF_ENCODERS }]: Sized,
 {
     // Set up the USB Communications Class Device driver
 
     let usb_bus = UsbBusAllocator::new(board.usb_bus);
+    let recovery = config.recovery;
 
     let mut keyboard =
-        Keyboard::<COLS, ROWS, NUM_OF_ENCODERS, EncoderPin, Output, Input, Timer, Usb>::new(
+        Keyboard::<COLS, ROWS, NUM_OF_ENCODERS, EncoderPin, _, Usb>::new(
             keys,
             cols,
             rows,
             encoders,
-            &mut timer0,
-            &mut timer1,
-            &mut timer2,
+            config,
+            clock,
             &usb_bus,
         );
 
@@ -158,7 +159,9 @@ where
         // feed watchdog
         board.watchdog.feed();
 
-        keyboard.periodic();
+        if keyboard.periodic().is_err() && recovery == Recovery::Reboot {
+            SCB::sys_reset();
+        }
     }
 }
 
@@ -169,16 +172,15 @@ pub fn matrix_scaning<
     const LAYERS: usize,
     Output: OutputPin,
     Input: InputPin,
-    Timer: CountDown,
     Dog: _embedded_hal_watchdog_Watchdog,
     Usb: UsbBus,
 >(
     mut board: Board<Dog, Usb>,
     cols: &mut [Output],
     rows: &mut [Input],
-    keys: &[&[&[Keycode]]],
-    mut timer0: Timer,
-    mut timer1: Timer,
+    keys: &Layout<COLS, ROWS, LAYERS>,
+    config: Config,
+    clock: &dyn Clock,
 ) -> !
 where
     [(); COLS * ROWS]: Sized,
@@ -187,13 +189,14 @@ where
 
     // let timer = board.timer;
     let usb_bus = UsbBusAllocator::new(board.usb_bus);
+    let recovery = config.recovery;
 
-    let mut keyboard = Keyboard::<COLS, ROWS, Output, Input, Timer, Usb>::new(
+    let mut keyboard = Keyboard::<COLS, ROWS, _, Usb>::new(
         keys,
         cols,
         rows,
-        &mut timer0,
-        &mut timer1,
+        config,
+        clock,
         &usb_bus,
     );
 
@@ -201,6 +204,8 @@ where
         // feed watchdog
         board.watchdog.feed();
 
-        keyboard.periodic();
+        if keyboard.periodic().is_err() && recovery == Recovery::Reboot {
+            SCB::sys_reset();
+        }
     }
 }
@@ -0,0 +1,92 @@
+#[allow(clippy::enum_glob_use)]
+use crate::keycode::Keycode::{self, *};
+
+/// A single step of a `KC_MACRO`.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum MacroStep {
+    /// Presses then releases the key.
+    Tap(Keycode),
+    /// Presses the key until it is released by a later step or the macro ends.
+    Press(Keycode),
+    Release(Keycode),
+    /// Waits for the number of ms before the next step.
+    Delay(u32),
+    /// Types the ascii characters of the string, non ascii characters are skipped.
+    Type(&'static str),
+}
+
+#[rustfmt::skip]
+const LETTERS: [Keycode; 26] = [
+    KC_A, KC_B, KC_C, KC_D, KC_E, KC_F, KC_G, KC_H, KC_I, KC_J, KC_K, KC_L, KC_M,
+    KC_N, KC_O, KC_P, KC_Q, KC_R, KC_S, KC_T, KC_U, KC_V, KC_W, KC_X, KC_Y, KC_Z,
+];
+
+#[rustfmt::skip]
+const DIGITS: [Keycode; 10] = [
+    KC_0, KC_1, KC_2, KC_3, KC_4, KC_5, KC_6, KC_7, KC_8, KC_9,
+];
+
+/// Returns the key that types `ascii` on a US layout and if shift has to be held.
+#[must_use]
+pub const fn from_ascii(ascii: u8) -> Option<(Keycode, bool)> {
+    Some(match ascii {
+        b'a'..=b'z' => (LETTERS[(ascii - b'a') as usize], false),
+        b'A'..=b'Z' => (LETTERS[(ascii - b'A') as usize], true),
+        b'0'..=b'9' => (DIGITS[(ascii - b'0') as usize], false),
+        b'!' => (KC_1, true),
+        b'@' => (KC_2, true),
+        b'#' => (KC_3, true),
+        b'$' => (KC_4, true),
+        b'%' => (KC_5, true),
+        b'^' => (KC_6, true),
+        b'&' => (KC_7, true),
+        b'*' => (KC_8, true),
+        b'(' => (KC_9, true),
+        b')' => (KC_0, true),
+        b'\n' => (KC_ENTER, false),
+        b'\x1b' => (KC_ESCAPE, false),
+        b'\x08' => (KC_BACKSPACE, false),
+        b'\t' => (KC_TAB, false),
+        b' ' => (KC_SPACE, false),
+        b'-' => (KC_MINUS, false),
+        b'_' => (KC_MINUS, true),
+        b'=' => (KC_EQUAL, false),
+        b'+' => (KC_EQUAL, true),
+        b'[' => (KC_LEFT_BRACKET, false),
+        b'{' => (KC_LEFT_BRACKET, true),
+        b']' => (KC_RIGHT_BRACKET, false),
+        b'}' => (KC_RIGHT_BRACKET, true),
+        b'\\' => (KC_BACKSLASH, false),
+        b'|' => (KC_BACKSLASH, true),
+        b';' => (KC_SEMICOLON, false),
+        b':' => (KC_SEMICOLON, true),
+        b'\'' => (KC_QUOTE, false),
+        b'"' => (KC_QUOTE, true),
+        b'`' => (KC_GRAVE, false),
+        b'~' => (KC_GRAVE, true),
+        b',' => (KC_COMMA, false),
+        b'<' => (KC_COMMA, true),
+        b'.' => (KC_DOT, false),
+        b'>' => (KC_DOT, true),
+        b'/' => (KC_SLASH, false),
+        b'?' => (KC_SLASH, true),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::from_ascii;
+    use crate::keycode::Keycode::*;
+
+    #[test]
+    fn ascii_to_keycode() {
+        assert!(from_ascii(b'a') == Some((KC_A, false)));
+        assert!(from_ascii(b'Z') == Some((KC_Z, true)));
+        assert!(from_ascii(b'0') == Some((KC_0, false)));
+        assert!(from_ascii(b'7') == Some((KC_7, false)));
+        assert!(from_ascii(b'?') == Some((KC_SLASH, true)));
+        assert!(from_ascii(b'\n') == Some((KC_ENTER, false)));
+        assert!(from_ascii(0x80).is_none());
+    }
+}
@@ -0,0 +1,196 @@
+//! RGB underglow.
+//!
+//! A strip of addressable LEDs is lit by an [`Animation`] of the colour set in
+//! [`Underglow`](crate::config::Underglow) and changed at runtime with the `KC_RGB_*` keys.
+//! Frames are rendered from [`Keyboard::periodic`](crate::Keyboard::periodic) and handed to a
+//! [`LedDriver`], which writes as much of them as it can without blocking.
+//!
+//! ```ignore
+//! let mut leds = Ws2812::new(pins.gpio16.into_function(), &mut pio, sm0, clocks.system_clock.freq());
+//! let keyboard = Keyboard::new(/* .. */).with_underglow(&mut leds);
+//! ```
+
+use core::convert::Infallible;
+
+#[cfg(feature = "rp2040")]
+mod ws2812;
+
+#[cfg(feature = "rp2040")]
+pub use ws2812::Ws2812;
+
+/// The most LEDs a strip can have.
+pub const MAX_LEDS: usize = 64;
+
+/// A colour as sent to an LED.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const OFF: Self = Self::new(0, 0, 0);
+
+    #[must_use]
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+}
+
+/// A colour as hue, saturation and value, each from 0 to 255.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Hsv {
+    pub h: u8,
+    pub s: u8,
+    pub v: u8,
+}
+
+impl Hsv {
+    #[must_use]
+    pub const fn new(h: u8, s: u8, v: u8) -> Self {
+        Self { h, s, v }
+    }
+
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub const fn to_rgb(self) -> Rgb {
+        if self.s == 0 {
+            return Rgb::new(self.v, self.v, self.v);
+        }
+
+        // six regions of 43 hues, the remainder scaled to 0..=255 within its region
+        let region = self.h / 43;
+        let remainder = (self.h - region * 43) as u16 * 6;
+        let (s, v) = (self.s as u16, self.v as u16);
+
+        let p = ((v * (255 - s)) >> 8) as u8;
+        let q = ((v * (255 - ((s * remainder) >> 8))) >> 8) as u8;
+        let t = ((v * (255 - ((s * (255 - remainder)) >> 8))) >> 8) as u8;
+        let v = self.v;
+
+        match region {
+            0 => Rgb::new(v, t, p),
+            1 => Rgb::new(q, v, p),
+            2 => Rgb::new(p, v, t),
+            3 => Rgb::new(p, q, v),
+            4 => Rgb::new(t, p, v),
+            _ => Rgb::new(v, p, q),
+        }
+    }
+}
+
+/// How the LEDs are lit.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Animation {
+    /// Every LED in the colour.
+    Static,
+    /// The colour fades in and out once per period.
+    Breathing,
+    /// Every hue spread along the strip, cycling once per period.
+    Rainbow,
+    /// Lights up in the colour on every key press and fades out over the period.
+    Reactive,
+}
+
+impl Animation {
+    const ALL: [Self; 4] = [Self::Static, Self::Breathing, Self::Rainbow, Self::Reactive];
+
+    // returns the animation `KC_RGB_MOD` switches to
+    pub(crate) fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|animation| *animation == self);
+        Self::ALL[index.map_or(0, |index| (index + 1) % Self::ALL.len())]
+    }
+
+    // renders the frame at `now` ms into `frame`, `last_press` is when a key was last pressed
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn render(
+        self,
+        color: Hsv,
+        period: u32,
+        now: u32,
+        last_press: Option<u32>,
+        frame: &mut [Rgb],
+    ) {
+        let period = period.max(1);
+        // how far through the period, from 0 to 255
+        let phase = (u64::from(now % period) * 256 / u64::from(period)) as u8;
+
+        match self {
+            Self::Static => frame.fill(color.to_rgb()),
+            Self::Breathing => {
+                // rises for the first half of the period and falls for the second
+                let level = if phase < 128 { phase * 2 } else { (255 - phase) * 2 };
+                frame.fill(Hsv::new(color.h, color.s, scale(color.v, level)).to_rgb());
+            }
+            Self::Rainbow => {
+                let len = frame.len();
+                for (index, led) in frame.iter_mut().enumerate() {
+                    let offset = (index * 256 / len) as u8;
+                    *led = Hsv::new(phase.wrapping_add(offset), color.s, color.v).to_rgb();
+                }
+            }
+            Self::Reactive => {
+                let elapsed = last_press.map_or(period, |pressed| now.wrapping_sub(pressed));
+                // in u64 so long periods don't overflow
+                let level = u64::from(period.saturating_sub(elapsed)) * 255 / u64::from(period);
+                frame.fill(Hsv::new(color.h, color.s, scale(color.v, level as u8)).to_rgb());
+            }
+        }
+    }
+}
+
+// scales `value` by `level` out of 255
+#[allow(clippy::cast_possible_truncation)]
+const fn scale(value: u8, level: u8) -> u8 {
+    (value as u16 * level as u16 / 255) as u8
+}
+
+/// Writes frames to a strip of LEDs.
+pub trait LedDriver {
+    /// Writes the colour of each LED along the strip without blocking.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`nb::Error::WouldBlock`] when only part of the frame could be written, it is
+    /// called again with the same frame until the rest is written.
+    fn write(&mut self, frame: &[Rgb]) -> nb::Result<(), Infallible>;
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Animation, Hsv, Rgb};
+
+    #[test]
+    fn hsv_primaries() {
+        assert_eq!(Hsv::new(0, 255, 255).to_rgb(), Rgb::new(255, 0, 0));
+        assert_eq!(Hsv::new(85, 255, 255).to_rgb().g, 255);
+        assert_eq!(Hsv::new(170, 255, 255).to_rgb().b, 255);
+        assert_eq!(Hsv::new(12, 0, 100).to_rgb(), Rgb::new(100, 100, 100));
+    }
+
+    #[test]
+    fn animations() {
+        let color = Hsv::new(0, 255, 200);
+        let mut frame = [Rgb::OFF; 4];
+
+        Animation::Breathing.render(color, 1000, 0, None, &mut frame);
+        assert_eq!(frame[0], Rgb::OFF);
+        Animation::Breathing.render(color, 1000, 500, None, &mut frame);
+        assert!(frame.iter().all(|led| led.r > 190));
+
+        Animation::Rainbow.render(color, 1000, 0, None, &mut frame);
+        assert!(frame[0] != frame[2]);
+
+        Animation::Reactive.render(color, 1000, 2000, None, &mut frame);
+        assert_eq!(frame[0], Rgb::OFF);
+        Animation::Reactive.render(color, 1000, 2000, Some(1900), &mut frame);
+        assert!(frame[0].r > 150);
+        Animation::Reactive.render(color, 1000, 3000, Some(1900), &mut frame);
+        assert_eq!(frame[0], Rgb::OFF);
+        Animation::Reactive.render(color, u32::MAX, 2000, Some(1900), &mut frame);
+        assert!(frame[0].r > 190);
+
+        assert_eq!(Animation::Reactive.next(), Animation::Static);
+    }
+}
@@ -0,0 +1,99 @@
+use core::convert::Infallible;
+
+use fugit::HertzU32;
+use rp2040_hal::gpio::{AnyPin, Pin};
+use rp2040_hal::pio::{
+    Buffers, PIOBuilder, PIOExt, PinDir, Running, ShiftDirection, StateMachine, StateMachineIndex,
+    Tx, UninitStateMachine, PIO,
+};
+
+use super::{LedDriver, Rgb};
+
+// cycles of each part of a bit, a 1 is high for T1 + T2 and a 0 for T1
+const T1: u8 = 2;
+const T2: u8 = 5;
+const T3: u8 = 3;
+// bits sent per second
+const BIT_RATE: u32 = 800_000;
+
+/// A WS2812 strip driven by a PIO state machine.
+///
+/// Colours are pushed to the FIFO of the state machine, [`write`](LedDriver::write) returns as
+/// soon as it is full and picks up where it left off on the next call.
+pub struct Ws2812<P: PIOExt, SM: StateMachineIndex> {
+    tx: Tx<(P, SM)>,
+    _sm: StateMachine<(P, SM), Running>,
+    // the next LED of the frame being written
+    next: usize,
+}
+
+impl<P: PIOExt, SM: StateMachineIndex> Ws2812<P, SM> {
+    /// Installs the program on `pio` and starts `sm` driving `pin`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there isn't enough instruction memory left on `pio`.
+    pub fn new<LedPin: AnyPin<Function = P::PinFunction>>(
+        pin: LedPin,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        system_clock: HertzU32,
+    ) -> Self {
+        let side_set = pio::SideSet::new(false, 1, false);
+        let mut assembler = pio::Assembler::new_with_side_set(side_set);
+        let mut wrap_target = assembler.label();
+        let mut wrap_source = assembler.label();
+        let mut do_zero = assembler.label();
+
+        assembler.bind(&mut wrap_target);
+        assembler.out_with_delay_and_side_set(pio::OutDestination::X, 1, T3 - 1, 0);
+        assembler.jmp_with_delay_and_side_set(pio::JmpCondition::XIsZero, &mut do_zero, T1 - 1, 1);
+        assembler.jmp_with_delay_and_side_set(pio::JmpCondition::Always, &mut wrap_target, T2 - 1, 1);
+        assembler.bind(&mut do_zero);
+        assembler.nop_with_delay_and_side_set(T2 - 1, 0);
+        assembler.bind(&mut wrap_source);
+        let program = assembler.assemble_with_wrap(wrap_source, wrap_target);
+
+        let installed = pio.install(&program).unwrap();
+
+        // the divisor as a 16.8 fixed point number
+        let cycles_per_bit = u32::from(T1 + T2 + T3);
+        let divisor = u64::from(system_clock.to_Hz()) * 256 / u64::from(BIT_RATE * cycles_per_bit);
+        #[allow(clippy::cast_possible_truncation)]
+        let (int, frac) = ((divisor >> 8) as u16, divisor as u8);
+
+        let pin: Pin<LedPin::Id, LedPin::Function, LedPin::Pull> = pin.into();
+        let pin_id = pin.id().num;
+        let (mut sm, _, tx) = PIOBuilder::from_installed_program(installed)
+            .buffers(Buffers::OnlyTx)
+            .side_set_pin_base(pin_id)
+            .out_shift_direction(ShiftDirection::Left)
+            .autopull(true)
+            .pull_threshold(24)
+            .clock_divisor_fixed_point(int, frac)
+            .build(sm);
+        sm.set_pindirs([(pin_id, PinDir::Output)]);
+
+        Self {
+            tx,
+            _sm: sm.start(),
+            next: 0,
+        }
+    }
+}
+
+impl<P: PIOExt, SM: StateMachineIndex> LedDriver for Ws2812<P, SM> {
+    fn write(&mut self, frame: &[Rgb]) -> nb::Result<(), Infallible> {
+        while let Some(led) = frame.get(self.next) {
+            // sent as green, red then blue from the top bit, autopull takes the top 24 bits
+            let word = u32::from(led.g) << 24 | u32::from(led.r) << 16 | u32::from(led.b) << 8;
+            if !self.tx.write(word) {
+                return Err(nb::Error::WouldBlock);
+            }
+            self.next += 1;
+        }
+
+        self.next = 0;
+        Ok(())
+    }
+}
@@ -0,0 +1,452 @@
+//! Split keyboards made of two halves linked over a serial port.
+//!
+//! The secondary half scans its own matrix and sends every debounced change to the primary half,
+//! which merges them into its layout and owns USB. The primary half sends its active layer back
+//! so the secondary half can show it.
+//!
+//! Every message is sent as a fixed size frame: a start byte, the message kind, two payload bytes
+//! and a checksum. Frames with a bad checksum are dropped and the decoder resyncs on the next
+//! start byte. The secondary half also sends every key it holds now and then, so a key whose
+//! press or release was lost doesn't stay that way.
+
+use embedded_hal::digital::v2::InputPin;
+use embedded_hal::serial::{Read, Write};
+
+const START: u8 = 0x7E;
+const FRAME_LEN: usize = 5;
+// bytes of frames that can be queued before messages are dropped
+const QUEUE_LEN: usize = 16 * FRAME_LEN;
+// times a full queue is flushed to make room for a message before it's dropped
+const SEND_RETRIES: usize = 4;
+
+const PRESS: u8 = 0x01;
+const RELEASE: u8 = 0x02;
+const LAYER: u8 = 0x03;
+// the low four bits are the chunk of the row
+const KEYS: u8 = 0x10;
+
+/// Columns of a row sent in one [`Message::Keys`].
+pub const KEYS_PER_CHUNK: usize = 8;
+
+// the keys of the secondary half the primary half keeps track of to resync them, keys past these
+// are still pressed and released but never resynced
+const TRACKED_ROWS: usize = 16;
+const TRACKED_CHUNKS: usize = 4;
+
+/// Which half of a split keyboard is running.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Role {
+    /// Connected to USB, merges both halves and sends the reports.
+    Primary,
+    /// Scans its matrix and sends the changes to the primary half.
+    Secondary,
+}
+
+impl Role {
+    /// Detects the role from a pin that reads high when USB power is present.
+    pub fn detect(vbus: &impl InputPin) -> Self {
+        if vbus.is_high().unwrap_or(false) {
+            Self::Primary
+        } else {
+            Self::Secondary
+        }
+    }
+}
+
+/// A message sent between the halves.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Message {
+    /// A key of the secondary half was pressed.
+    Press { row: u8, col: u8 },
+    /// A key of the secondary half was released.
+    Release { row: u8, col: u8 },
+    /// The active layer of the primary half.
+    Layer(u8),
+    /// The keys of the secondary half held down in columns `chunk * KEYS_PER_CHUNK` onwards of a
+    /// row, one bit per column, sent now and then to recover from a lost press or release.
+    ///
+    /// `chunk` must be below 16.
+    Keys { row: u8, chunk: u8, held: u8 },
+}
+
+impl Message {
+    /// Returns the frame the message is sent as.
+    #[must_use]
+    pub const fn encode(&self) -> [u8; FRAME_LEN] {
+        let (kind, a, b) = match *self {
+            Self::Press { row, col } => (PRESS, row, col),
+            Self::Release { row, col } => (RELEASE, row, col),
+            Self::Layer(layer) => (LAYER, layer, 0),
+            Self::Keys { row, chunk, held } => (KEYS | (chunk & 0x0F), row, held),
+        };
+
+        [START, kind, a, b, checksum(kind, a, b)]
+    }
+
+    const fn decode(frame: &[u8; FRAME_LEN]) -> Option<Self> {
+        let [start, kind, a, b, check] = *frame;
+
+        if start != START || check != checksum(kind, a, b) {
+            return None;
+        }
+
+        match kind {
+            PRESS => Some(Self::Press { row: a, col: b }),
+            RELEASE => Some(Self::Release { row: a, col: b }),
+            LAYER => Some(Self::Layer(a)),
+            _ if kind & 0xF0 == KEYS => Some(Self::Keys {
+                row: a,
+                chunk: kind & 0x0F,
+                held: b,
+            }),
+            _ => None,
+        }
+    }
+}
+
+const fn checksum(kind: u8, a: u8, b: u8) -> u8 {
+    !kind.wrapping_add(a).wrapping_add(b)
+}
+
+/// Turns received bytes back into messages.
+pub struct Decoder {
+    frame: [u8; FRAME_LEN],
+    len: usize,
+}
+
+impl Decoder {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            frame: [0; FRAME_LEN],
+            len: 0,
+        }
+    }
+
+    /// Feeds a received byte and returns the message it completes.
+    pub fn push(&mut self, byte: u8) -> Option<Message> {
+        if self.len == 0 && byte != START {
+            return None;
+        }
+
+        self.frame[self.len] = byte;
+        self.len += 1;
+
+        if self.len < FRAME_LEN {
+            return None;
+        }
+
+        let message = Message::decode(&self.frame);
+        if message.is_some() {
+            self.len = 0;
+        } else {
+            // resync on the next start byte in the dropped frame
+            let next = self.frame[1..]
+                .iter()
+                .position(|byte| *byte == START)
+                .map_or(FRAME_LEN, |index| index + 1);
+            self.frame.copy_within(next.., 0);
+            self.len = FRAME_LEN - next;
+        }
+        message
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Framed messages over a serial port.
+pub struct Link<Serial> {
+    serial: Serial,
+    decoder: Decoder,
+    // bytes waiting to be written, oldest first
+    queue: [u8; QUEUE_LEN],
+    queued: usize,
+}
+
+impl<Serial: Read<u8> + Write<u8>> Link<Serial> {
+    pub const fn new(serial: Serial) -> Self {
+        Self {
+            serial,
+            decoder: Decoder::new(),
+            queue: [0; QUEUE_LEN],
+            queued: 0,
+        }
+    }
+
+    /// Queues a message, flushing a full queue a few times to make room for it.
+    ///
+    /// Returns `false` if the queue is still full, like when the other half stopped reading, and
+    /// the message is dropped.
+    pub fn send(&mut self, message: Message) -> bool {
+        for _ in 0..SEND_RETRIES {
+            if self.queued + FRAME_LEN <= QUEUE_LEN {
+                break;
+            }
+            self.flush();
+        }
+
+        if self.queued + FRAME_LEN > QUEUE_LEN {
+            return false;
+        }
+
+        self.queue[self.queued..self.queued + FRAME_LEN].copy_from_slice(&message.encode());
+        self.queued += FRAME_LEN;
+        true
+    }
+
+    /// Writes as many queued bytes as the serial port accepts without blocking.
+    pub fn flush(&mut self) {
+        let mut written = 0;
+
+        while written < self.queued {
+            match self.serial.write(self.queue[written]) {
+                Ok(()) => written += 1,
+                Err(nb::Error::WouldBlock) => break,
+                // the byte is lost, the other half drops the frame
+                Err(nb::Error::Other(_)) => written += 1,
+            }
+        }
+
+        self.queue.copy_within(written..self.queued, 0);
+        self.queued -= written;
+    }
+
+    /// Returns the next received message, if one has arrived.
+    pub fn receive(&mut self) -> Option<Message> {
+        loop {
+            match self.serial.read() {
+                Ok(byte) => {
+                    if let Some(message) = self.decoder.push(byte) {
+                        return Some(message);
+                    }
+                }
+                Err(nb::Error::WouldBlock) => return None,
+                // framing or overrun errors, the decoder drops the broken frame
+                Err(nb::Error::Other(_)) => {}
+            }
+        }
+    }
+}
+
+/// The primary half's end of the link.
+pub struct Primary<Serial> {
+    link: Link<Serial>,
+    row_offset: usize,
+    col_offset: usize,
+    // the last layer sent to the secondary half
+    layer: Option<usize>,
+    // the keys of the secondary half held down, one bit per column of a chunk
+    held: [[u8; TRACKED_CHUNKS]; TRACKED_ROWS],
+    // a received `Message::Keys` whose changes haven't all been returned yet
+    resync: Option<(usize, usize, u8)>,
+}
+
+impl<Serial: Read<u8> + Write<u8>> Primary<Serial> {
+    /// Keys of the secondary half are placed at `(row + row_offset, col + col_offset)` in the
+    /// layout of the primary half.
+    pub const fn new(serial: Serial, row_offset: usize, col_offset: usize) -> Self {
+        Self {
+            link: Link::new(serial),
+            row_offset,
+            col_offset,
+            layer: None,
+            held: [[0; TRACKED_CHUNKS]; TRACKED_ROWS],
+            resync: None,
+        }
+    }
+
+    // returns the next key change of the secondary half as `(row, col, pressed)` in the layout,
+    // a press or release of a key already in that state is skipped
+    pub(crate) fn receive(&mut self) -> Option<(usize, usize, bool)> {
+        loop {
+            let (row, col, pressed) = match self.resync {
+                Some((row, chunk, held)) => match self.next_resync_change(row, chunk, held) {
+                    Some(change) => change,
+                    None => {
+                        self.resync = None;
+                        continue;
+                    }
+                },
+                None => match self.link.receive()? {
+                    Message::Press { row, col } => (usize::from(row), usize::from(col), true),
+                    Message::Release { row, col } => (usize::from(row), usize::from(col), false),
+                    Message::Keys { row, chunk, held } => {
+                        let (row, chunk) = (usize::from(row), usize::from(chunk));
+                        if row < TRACKED_ROWS && chunk < TRACKED_CHUNKS {
+                            self.resync = Some((row, chunk, held));
+                        }
+                        continue;
+                    }
+                    Message::Layer(_) => continue,
+                },
+            };
+
+            if !self.set_held(row, col, pressed) {
+                continue;
+            }
+
+            return Some((row + self.row_offset, col + self.col_offset, pressed));
+        }
+    }
+
+    // the first key of a resynced chunk held differently than the primary half thinks
+    const fn next_resync_change(
+        &self,
+        row: usize,
+        chunk: usize,
+        held: u8,
+    ) -> Option<(usize, usize, bool)> {
+        let changed = self.held[row][chunk] ^ held;
+        if changed == 0 {
+            return None;
+        }
+
+        let bit = changed.trailing_zeros() as usize;
+        Some((row, chunk * KEYS_PER_CHUNK + bit, held & (1 << bit) != 0))
+    }
+
+    // records the state of a key, returning if it changed
+    fn set_held(&mut self, row: usize, col: usize, pressed: bool) -> bool {
+        let (chunk, bit) = (col / KEYS_PER_CHUNK, col % KEYS_PER_CHUNK);
+        let Some(held) = self.held.get_mut(row).and_then(|row| row.get_mut(chunk)) else {
+            // not tracked, so every change is passed on
+            return true;
+        };
+
+        let was_pressed = *held & (1 << bit) != 0;
+        if pressed {
+            *held |= 1 << bit;
+        } else {
+            *held &= !(1 << bit);
+        }
+        was_pressed != pressed
+    }
+
+    // sends the active layer if it changed
+    pub(crate) fn sync_layer(&mut self, layer: usize) {
+        if self.layer != Some(layer)
+            && self
+                .link
+                .send(Message::Layer(u8::try_from(layer).unwrap_or(u8::MAX)))
+        {
+            self.layer = Some(layer);
+        }
+
+        self.link.flush();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use embedded_hal::serial::Write;
+
+    use super::{Decoder, Link, Message, Primary, FRAME_LEN, QUEUE_LEN};
+    use crate::testing::MockSerial;
+
+    fn decode_all(decoder: &mut Decoder, bytes: &[u8]) -> Vec<Message> {
+        bytes.iter().filter_map(|byte| decoder.push(*byte)).collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let messages = [
+            Message::Press { row: 3, col: 5 },
+            Message::Release { row: 0, col: 0x7E },
+            Message::Layer(2),
+            Message::Keys {
+                row: 1,
+                chunk: 15,
+                held: 0b1000_0001,
+            },
+        ];
+        let bytes: Vec<u8> = messages.iter().flat_map(Message::encode).collect();
+
+        assert_eq!(decode_all(&mut Decoder::new(), &bytes), messages);
+    }
+
+    #[test]
+    fn drops_corrupt_frames_and_resyncs() {
+        let mut corrupt = Message::Press { row: 1, col: 2 }.encode();
+        corrupt[3] ^= 0xFF;
+
+        let mut bytes = vec![0x00, 0x42];
+        bytes.extend(corrupt);
+        bytes.extend(Message::Release { row: 1, col: 2 }.encode());
+        // a frame cut short by a start byte
+        bytes.extend(&Message::Layer(1).encode()[..2]);
+        bytes.extend(Message::Layer(3).encode());
+
+        assert_eq!(
+            decode_all(&mut Decoder::new(), &bytes),
+            [Message::Release { row: 1, col: 2 }, Message::Layer(3)]
+        );
+    }
+
+    #[test]
+    fn link_over_serial_pair() {
+        let (a, b) = MockSerial::pair();
+        let mut a = Link::new(a);
+        let mut b = Link::new(b);
+
+        a.send(Message::Press { row: 1, col: 2 });
+        a.send(Message::Layer(4));
+        assert!(b.receive().is_none());
+
+        a.flush();
+        assert_eq!(b.receive(), Some(Message::Press { row: 1, col: 2 }));
+        assert_eq!(b.receive(), Some(Message::Layer(4)));
+        assert!(b.receive().is_none());
+    }
+
+    #[test]
+    fn stalled_link_drops_messages() {
+        let (a, _b) = MockSerial::pair();
+        a.stall(true);
+        let mut link = Link::new(a);
+
+        for _ in 0..QUEUE_LEN / FRAME_LEN {
+            assert!(link.send(Message::Layer(1)));
+        }
+        assert!(!link.send(Message::Layer(2)));
+    }
+
+    #[test]
+    fn lost_frames_are_resynced() {
+        let (mut secondary, primary_serial) = MockSerial::pair();
+        let mut primary = Primary::new(primary_serial, 1, 2);
+        let mut send = |message: Message| {
+            for byte in message.encode() {
+                secondary.write(byte).unwrap();
+            }
+        };
+
+        send(Message::Press { row: 0, col: 9 });
+        send(Message::Press { row: 0, col: 9 });
+        assert_eq!(primary.receive(), Some((1, 11, true)));
+        assert_eq!(primary.receive(), None);
+
+        // the release of col 9 and the press of col 10 are lost
+        send(Message::Keys {
+            row: 0,
+            chunk: 1,
+            held: 0b100,
+        });
+        assert_eq!(primary.receive(), Some((1, 11, false)));
+        assert_eq!(primary.receive(), Some((1, 12, true)));
+        assert_eq!(primary.receive(), None);
+
+        // nothing was lost
+        send(Message::Keys {
+            row: 0,
+            chunk: 1,
+            held: 0b100,
+        });
+        send(Message::Release { row: 0, col: 10 });
+        assert_eq!(primary.receive(), Some((1, 12, false)));
+        assert_eq!(primary.receive(), None);
+    }
+}
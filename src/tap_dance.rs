@@ -0,0 +1,74 @@
+//! Keys that do something different when tapped once, tapped twice or held.
+
+use crate::keycode::Keycode;
+
+/// What a `KC_TD` key does once the taps are counted, or a leader sequence does once typed.
+#[derive(Copy, Clone, Debug)]
+pub enum Action {
+    /// Sends the key, held down until the `KC_TD` key is released or tapped by a sequence. Layer
+    /// keys move between layers like they do in the layout, so `KC_MO` is active while held.
+    Key(Keycode),
+    /// Calls the function.
+    Callback(fn()),
+}
+
+// callbacks are compared by address, so the same function may not always be equal to itself
+impl PartialEq for Action {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Key(keycode), Self::Key(other)) => keycode == other,
+            (Self::Callback(callback), Self::Callback(other)) => {
+                core::ptr::fn_addr_eq(*callback, *other)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Action {}
+
+/// The actions of a `KC_TD` key.
+///
+/// The taps are counted until the tapping term passes without another tap, or another key is
+/// pressed. A key that is only tapped, or with the most taps it has actions for, acts straight
+/// away.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct TapDance {
+    /// Tapped once.
+    pub tap: Action,
+    /// Tapped twice.
+    pub double_tap: Option<Action>,
+    /// Held past the tapping term, or held while another key is pressed.
+    pub hold: Option<Action>,
+}
+
+impl TapDance {
+    #[must_use]
+    pub const fn new(tap: Action) -> Self {
+        Self {
+            tap,
+            double_tap: None,
+            hold: None,
+        }
+    }
+
+    /// Returns the action for the number of taps, with the key `held` down.
+    #[must_use]
+    pub const fn action(&self, taps: u8, held: bool) -> Action {
+        match (taps, held, self.hold, self.double_tap) {
+            (1, true, Some(hold), _) => hold,
+            (2.., _, _, Some(double_tap)) => double_tap,
+            _ => self.tap,
+        }
+    }
+
+    /// Returns the most taps the key has actions for.
+    #[must_use]
+    pub const fn max_taps(&self) -> u8 {
+        if self.double_tap.is_some() {
+            2
+        } else {
+            1
+        }
+    }
+}
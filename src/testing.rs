@@ -0,0 +1,885 @@
+//! Host side fakes for driving a [`Keyboard`](crate::Keyboard) from `cargo test`.
+//!
+//! [`MockMatrix`] models a diode matrix: a key pressed at `(row, col)` pulls its input to the
+//! active level while the output on the other side of the key is driven to the active level.
+//! [`MockClock`] is a [`Clock`](crate::clock::Clock) advanced by hand, and [`MockUsbBus`] records
+//! every report written to an in endpoint so it can be inspected through a [`UsbCapture`].
+//! [`MockSerial::pair`] links the two halves of a split keyboard in memory.
+
+use core::cell::{Cell, RefCell};
+use core::convert::Infallible;
+use core::fmt::Debug;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use embedded_hal::serial::{Read, Write};
+use usb_device::bus::PollResult;
+use usb_device::class_prelude::{EndpointAddress, EndpointType, UsbBus};
+use usb_device::{UsbDirection, UsbError};
+
+use crate::clock::Clock;
+use crate::config::ActiveLevel;
+use crate::display::{Status, StatusDisplay};
+use crate::keycode::Keycode;
+use crate::matrix::{Events, FlexPin, Scanner};
+use crate::rgb::{LedDriver, Rgb};
+
+/// Physical key state of a fake matrix, shared by its pins.
+pub struct MockMatrix<const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize> {
+    pressed: Cell<[[bool; NUM_OF_COLS]; NUM_OF_ROWS]>,
+    // lines currently driven to the active level
+    cols_driven: Cell<[bool; NUM_OF_COLS]>,
+    rows_driven: Cell<[bool; NUM_OF_ROWS]>,
+    active_low: bool,
+    // input pins return an error when read
+    inputs_fail: Cell<bool>,
+    // output pins return an error when set
+    outputs_fail: Cell<bool>,
+}
+
+impl<const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize> MockMatrix<NUM_OF_COLS, NUM_OF_ROWS> {
+    /// Returns a matrix with outputs driven high and inputs pulled down.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            pressed: Cell::new([[false; NUM_OF_COLS]; NUM_OF_ROWS]),
+            cols_driven: Cell::new([false; NUM_OF_COLS]),
+            rows_driven: Cell::new([false; NUM_OF_ROWS]),
+            active_low: false,
+            inputs_fail: Cell::new(false),
+            outputs_fail: Cell::new(false),
+        }
+    }
+
+    /// Returns a matrix with outputs driven low and inputs pulled up.
+    #[must_use]
+    pub const fn active_low() -> Self {
+        Self {
+            active_low: true,
+            ..Self::new()
+        }
+    }
+
+    pub fn press(&self, row: usize, col: usize) {
+        self.set(row, col, true);
+    }
+
+    pub fn release(&self, row: usize, col: usize) {
+        self.set(row, col, false);
+    }
+
+    /// Makes every input pin return [`MockPinError`] until called again with `false`.
+    pub fn fail_inputs(&self, fail: bool) {
+        self.inputs_fail.set(fail);
+    }
+
+    /// Makes every output pin return [`MockPinError`] until called again with `false`, after
+    /// setting its level like a pin that fails to report it was set.
+    pub fn fail_outputs(&self, fail: bool) {
+        self.outputs_fail.set(fail);
+    }
+
+    fn set(&self, row: usize, col: usize, pressed: bool) {
+        let mut keys = self.pressed.get();
+        keys[row][col] = pressed;
+        self.pressed.set(keys);
+    }
+
+    /// Returns one output pin per column.
+    #[must_use]
+    pub fn output_pins(&self) -> [MockOutputPin<'_, NUM_OF_COLS, NUM_OF_ROWS>; NUM_OF_COLS] {
+        core::array::from_fn(|col| MockOutputPin {
+            matrix: self,
+            line: Line::Col(col),
+        })
+    }
+
+    /// Returns one input pin per row.
+    #[must_use]
+    pub fn input_pins(&self) -> [MockInputPin<'_, NUM_OF_COLS, NUM_OF_ROWS>; NUM_OF_ROWS] {
+        core::array::from_fn(|row| MockInputPin {
+            matrix: self,
+            line: Line::Row(row),
+        })
+    }
+
+    /// Returns one output pin per row, for matrices scanned from the rows.
+    #[must_use]
+    pub fn row_output_pins(&self) -> [MockOutputPin<'_, NUM_OF_COLS, NUM_OF_ROWS>; NUM_OF_ROWS] {
+        core::array::from_fn(|row| MockOutputPin {
+            matrix: self,
+            line: Line::Row(row),
+        })
+    }
+
+    /// Returns one input pin per column, for matrices scanned from the rows.
+    #[must_use]
+    pub fn col_input_pins(&self) -> [MockInputPin<'_, NUM_OF_COLS, NUM_OF_ROWS>; NUM_OF_COLS] {
+        core::array::from_fn(|col| MockInputPin {
+            matrix: self,
+            line: Line::Col(col),
+        })
+    }
+
+    // returns if a pressed key connects the line to a driven line
+    fn is_connected(&self, line: Line) -> bool {
+        let pressed = self.pressed.get();
+
+        match line {
+            Line::Row(row) => {
+                let driven = self.cols_driven.get();
+                (0..NUM_OF_COLS).any(|col| pressed[row][col] && driven[col])
+            }
+            Line::Col(col) => {
+                let driven = self.rows_driven.get();
+                (0..NUM_OF_ROWS).any(|row| pressed[row][col] && driven[row])
+            }
+        }
+    }
+}
+
+impl<const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize> Default
+    for MockMatrix<NUM_OF_COLS, NUM_OF_ROWS>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Line {
+    Row(usize),
+    Col(usize),
+}
+
+/// Output pin of a [`MockMatrix`].
+pub struct MockOutputPin<'a, const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize> {
+    matrix: &'a MockMatrix<NUM_OF_COLS, NUM_OF_ROWS>,
+    line: Line,
+}
+
+impl<const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize> OutputPin
+    for MockOutputPin<'_, NUM_OF_COLS, NUM_OF_ROWS>
+{
+    type Error = MockPinError;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.set(self.matrix.active_low)
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.set(!self.matrix.active_low)
+    }
+}
+
+impl<const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize>
+    MockOutputPin<'_, NUM_OF_COLS, NUM_OF_ROWS>
+{
+    fn set(&self, driven: bool) -> Result<(), MockPinError> {
+        match self.line {
+            Line::Row(row) => {
+                let mut rows = self.matrix.rows_driven.get();
+                rows[row] = driven;
+                self.matrix.rows_driven.set(rows);
+            }
+            Line::Col(col) => {
+                let mut cols = self.matrix.cols_driven.get();
+                cols[col] = driven;
+                self.matrix.cols_driven.set(cols);
+            }
+        }
+
+        if self.matrix.outputs_fail.get() {
+            return Err(MockPinError);
+        }
+        Ok(())
+    }
+}
+
+/// Input pin of a [`MockMatrix`].
+pub struct MockInputPin<'a, const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize> {
+    matrix: &'a MockMatrix<NUM_OF_COLS, NUM_OF_ROWS>,
+    line: Line,
+}
+
+impl<const NUM_OF_COLS: usize, const NUM_OF_ROWS: usize> InputPin
+    for MockInputPin<'_, NUM_OF_COLS, NUM_OF_ROWS>
+{
+    type Error = MockPinError;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        if self.matrix.inputs_fail.get() {
+            return Err(MockPinError);
+        }
+
+        Ok(self.matrix.is_connected(self.line) != self.matrix.active_low)
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        self.is_high().map(|high| !high)
+    }
+}
+
+/// Bidirectional pins joined by keys, each with a diode from the pin driving it to the pin
+/// reading it, for duplex and round-robin matrices.
+///
+/// A pin that floats reads the active level if a pressed key joins it to a pin driving the
+/// active level, and the inactive level otherwise.
+pub struct MockFlexMatrix<const NUM_OF_PINS: usize> {
+    // keys pressed, indexed by the pin driving them and then the pin reading them
+    pressed: Cell<[[bool; NUM_OF_PINS]; NUM_OF_PINS]>,
+    // the level each pin drives, `None` while it floats
+    driven: Cell<[Option<bool>; NUM_OF_PINS]>,
+    active_low: bool,
+    // pins return an error when driven
+    drives_fail: Cell<bool>,
+}
+
+impl<const NUM_OF_PINS: usize> MockFlexMatrix<NUM_OF_PINS> {
+    /// Returns a matrix with its pins pulled down.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            pressed: Cell::new([[false; NUM_OF_PINS]; NUM_OF_PINS]),
+            driven: Cell::new([None; NUM_OF_PINS]),
+            active_low: false,
+            drives_fail: Cell::new(false),
+        }
+    }
+
+    /// Returns a matrix with its pins pulled up.
+    #[must_use]
+    pub const fn active_low() -> Self {
+        Self {
+            active_low: true,
+            ..Self::new()
+        }
+    }
+
+    /// Returns a matrix pulled down and one pulled up, each with the active level it's scanned
+    /// with, to run a test at both levels.
+    #[must_use]
+    pub const fn at_both_levels() -> [(Self, ActiveLevel); 2] {
+        [
+            (Self::new(), ActiveLevel::High),
+            (Self::active_low(), ActiveLevel::Low),
+        ]
+    }
+
+    /// Presses the key going from pin `driver` to pin `reader`.
+    pub fn press(&self, driver: usize, reader: usize) {
+        self.set(driver, reader, true);
+    }
+
+    pub fn release(&self, driver: usize, reader: usize) {
+        self.set(driver, reader, false);
+    }
+
+    /// Makes every pin return [`MockPinError`] when driven until called again with `false`,
+    /// after driving the level like a pin that fails to report it was set.
+    pub fn fail_drives(&self, fail: bool) {
+        self.drives_fail.set(fail);
+    }
+
+    /// Returns if any pin drives a level.
+    #[must_use]
+    pub fn is_driven(&self) -> bool {
+        self.driven.get().iter().any(Option::is_some)
+    }
+
+    fn set(&self, driver: usize, reader: usize, pressed: bool) {
+        let mut keys = self.pressed.get();
+        keys[driver][reader] = pressed;
+        self.pressed.set(keys);
+    }
+
+    #[must_use]
+    pub fn pins(&self) -> [MockFlexPin<'_, NUM_OF_PINS>; NUM_OF_PINS] {
+        core::array::from_fn(|pin| MockFlexPin { matrix: self, pin })
+    }
+
+    /// Presses the keys from pin `driver` to pin `reader`, scans `scanner` once and returns the
+    /// `(row, col)` of every key it reads as pressed, in order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the scan fails.
+    pub fn scan_pressed<Scan: Scanner>(
+        &self,
+        scanner: &mut Scan,
+        presses: &[(usize, usize)],
+    ) -> Vec<(usize, usize)>
+    where
+        Scan::OutputError: Debug,
+        Scan::InputError: Debug,
+    {
+        for (driver, reader) in presses {
+            self.press(*driver, *reader);
+        }
+
+        let mut pressed = Pressed(Vec::new());
+        scanner.scan::<()>(&mut pressed).unwrap();
+        pressed.0.sort_unstable();
+        pressed.0
+    }
+}
+
+// the keys pressed by a scan
+struct Pressed(Vec<(usize, usize)>);
+
+impl Events for Pressed {
+    fn tick(&mut self, _now: u32) {}
+
+    fn press(&mut self, row: usize, col: usize, _now: u32) {
+        self.0.push((row, col));
+    }
+
+    fn release(&mut self, row: usize, col: usize, _now: u32) {
+        self.0.retain(|key| *key != (row, col));
+    }
+}
+
+impl<const NUM_OF_PINS: usize> Default for MockFlexMatrix<NUM_OF_PINS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Pin of a [`MockFlexMatrix`].
+pub struct MockFlexPin<'a, const NUM_OF_PINS: usize> {
+    matrix: &'a MockFlexMatrix<NUM_OF_PINS>,
+    pin: usize,
+}
+
+impl<const NUM_OF_PINS: usize> MockFlexPin<'_, NUM_OF_PINS> {
+    fn set(&self, level: Option<bool>) {
+        let mut driven = self.matrix.driven.get();
+        driven[self.pin] = level;
+        self.matrix.driven.set(driven);
+    }
+}
+
+impl<const NUM_OF_PINS: usize> FlexPin for MockFlexPin<'_, NUM_OF_PINS> {
+    type Error = MockPinError;
+
+    fn drive(&mut self, high: bool) -> Result<(), Self::Error> {
+        self.set(Some(high));
+        if self.matrix.drives_fail.get() {
+            return Err(MockPinError);
+        }
+        Ok(())
+    }
+
+    fn float(&mut self) -> Result<(), Self::Error> {
+        self.set(None);
+        Ok(())
+    }
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        let driven = self.matrix.driven.get();
+        if let Some(level) = driven[self.pin] {
+            return Ok(level);
+        }
+
+        let active = !self.matrix.active_low;
+        let pressed = self.matrix.pressed.get();
+        let connected =
+            (0..NUM_OF_PINS).any(|driver| pressed[driver][self.pin] && driven[driver] == Some(active));
+
+        Ok(connected == active)
+    }
+}
+
+/// Pin reading and driving a level shared with the test, high while the cell is `true`.
+pub struct MockPin<'a> {
+    level: &'a Cell<bool>,
+}
+
+impl<'a> MockPin<'a> {
+    #[must_use]
+    pub const fn new(level: &'a Cell<bool>) -> Self {
+        Self { level }
+    }
+}
+
+impl InputPin for MockPin<'_> {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(self.level.get())
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.level.get())
+    }
+}
+
+impl OutputPin for MockPin<'_> {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.level.set(false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.level.set(true);
+        Ok(())
+    }
+}
+
+/// Fake LED strip adding every frame written in full to a list shared with the test.
+///
+/// Takes at most `per_write` LEDs per write, like a driver whose FIFO fills up.
+pub struct MockLedDriver<'a> {
+    frames: &'a RefCell<Vec<Vec<Rgb>>>,
+    per_write: usize,
+    // the next LED of the frame being written
+    next: usize,
+}
+
+impl<'a> MockLedDriver<'a> {
+    #[must_use]
+    pub const fn new(frames: &'a RefCell<Vec<Vec<Rgb>>>, per_write: usize) -> Self {
+        Self {
+            frames,
+            per_write,
+            next: 0,
+        }
+    }
+}
+
+impl LedDriver for MockLedDriver<'_> {
+    fn write(&mut self, frame: &[Rgb]) -> nb::Result<(), Infallible> {
+        self.next = (self.next + self.per_write).min(frame.len());
+        if self.next < frame.len() {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        self.next = 0;
+        self.frames.borrow_mut().push(frame.to_vec());
+        Ok(())
+    }
+}
+
+/// Fake status display adding every status shown to a list shared with the test.
+pub struct MockDisplay<'a> {
+    shown: &'a RefCell<Vec<Status>>,
+}
+
+impl<'a> MockDisplay<'a> {
+    #[must_use]
+    pub const fn new(shown: &'a RefCell<Vec<Status>>) -> Self {
+        Self { shown }
+    }
+}
+
+impl StatusDisplay for MockDisplay<'_> {
+    fn show(&mut self, status: &Status) {
+        self.shown.borrow_mut().push(*status);
+    }
+}
+
+/// Error returned by a [`MockInputPin`] after [`MockMatrix::fail_inputs`] and a
+/// [`MockOutputPin`] after [`MockMatrix::fail_outputs`] or a [`MockFlexPin`] after
+/// [`MockFlexMatrix::fail_drives`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MockPinError;
+
+/// Fake [`Clock`] advanced by hand.
+pub struct MockClock {
+    now: Cell<u64>,
+}
+
+impl MockClock {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { now: Cell::new(0) }
+    }
+
+    pub fn advance_ms(&self, ms: u32) {
+        self.advance_us(u64::from(ms) * 1000);
+    }
+
+    pub fn advance_us(&self, us: u64) {
+        self.now.set(self.now.get() + us);
+    }
+
+    #[must_use]
+    pub const fn now_us(&self) -> u64 {
+        self.now.get()
+    }
+
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    #[allow(clippy::cast_possible_truncation)]
+    fn now(&self) -> u32 {
+        (self.now.get() / 1000) as u32
+    }
+}
+
+#[derive(Default)]
+struct BusState {
+    next_endpoint: [usize; 2],
+    writes: Vec<(EndpointAddress, Vec<u8>)>,
+    // bitmask of the out endpoints other than the control endpoint
+    out_endpoints: u16,
+    // reports sent by the host, read from the out endpoints oldest first
+    output_reports: VecDeque<Vec<u8>>,
+}
+
+/// In memory [`UsbBus`] that never enumerates, records every in endpoint write and hands the
+/// output reports queued with [`UsbCapture::send_output_report`] to the out endpoints.
+pub struct MockUsbBus {
+    state: Arc<Mutex<BusState>>,
+}
+
+impl MockUsbBus {
+    /// Returns the bus along with a handle to the reports written to it.
+    #[must_use]
+    pub fn new() -> (Self, UsbCapture) {
+        let state = Arc::new(Mutex::new(BusState {
+            next_endpoint: [1, 1],
+            writes: Vec::new(),
+            out_endpoints: 0,
+            output_reports: VecDeque::new(),
+        }));
+
+        (
+            Self {
+                state: state.clone(),
+            },
+            UsbCapture { state },
+        )
+    }
+
+    fn state(&self) -> MutexGuard<'_, BusState> {
+        self.state.lock().unwrap()
+    }
+}
+
+impl UsbBus for MockUsbBus {
+    fn alloc_ep(
+        &mut self,
+        ep_dir: UsbDirection,
+        ep_addr: Option<EndpointAddress>,
+        ep_type: EndpointType,
+        _max_packet_size: u16,
+        _interval: u8,
+    ) -> usb_device::Result<EndpointAddress> {
+        if let Some(ep_addr) = ep_addr {
+            return Ok(ep_addr);
+        }
+
+        if ep_type == EndpointType::Control {
+            return Ok(EndpointAddress::from_parts(0, ep_dir));
+        }
+
+        let direction = usize::from(ep_dir == UsbDirection::In);
+        let index = self.state().next_endpoint[direction];
+        self.state().next_endpoint[direction] += 1;
+        if ep_dir == UsbDirection::Out {
+            self.state().out_endpoints |= 1 << index;
+        }
+
+        Ok(EndpointAddress::from_parts(index, ep_dir))
+    }
+
+    fn enable(&mut self) {}
+
+    fn reset(&self) {}
+
+    fn set_device_address(&self, _addr: u8) {}
+
+    fn write(&self, ep_addr: EndpointAddress, buf: &[u8]) -> usb_device::Result<usize> {
+        if ep_addr.index() != 0 {
+            self.state().writes.push((ep_addr, buf.to_vec()));
+        }
+        Ok(buf.len())
+    }
+
+    fn read(&self, ep_addr: EndpointAddress, buf: &mut [u8]) -> usb_device::Result<usize> {
+        if ep_addr.index() == 0 {
+            return Err(UsbError::WouldBlock);
+        }
+
+        let mut state = self.state();
+        let report = state.output_reports.front().ok_or(UsbError::WouldBlock)?;
+        if report.len() > buf.len() {
+            return Err(UsbError::BufferOverflow);
+        }
+
+        buf[..report.len()].copy_from_slice(report);
+        Ok(state.output_reports.pop_front().map_or(0, |report| report.len()))
+    }
+
+    fn set_stalled(&self, _ep_addr: EndpointAddress, _stalled: bool) {}
+
+    fn is_stalled(&self, _ep_addr: EndpointAddress) -> bool {
+        false
+    }
+
+    fn suspend(&self) {}
+
+    fn resume(&self) {}
+
+    fn poll(&self) -> PollResult {
+        let state = self.state();
+        if state.output_reports.is_empty() {
+            return PollResult::None;
+        }
+
+        PollResult::Data {
+            ep_out: state.out_endpoints,
+            ep_in_complete: 0,
+            ep_setup: 0,
+        }
+    }
+}
+
+/// Handle to the reports written to a [`MockUsbBus`].
+pub struct UsbCapture {
+    state: Arc<Mutex<BusState>>,
+}
+
+impl UsbCapture {
+    /// Returns every report written to an in endpoint, oldest first.
+    #[must_use]
+    pub fn writes(&self) -> Vec<(EndpointAddress, Vec<u8>)> {
+        self.state.lock().unwrap().writes.clone()
+    }
+
+    /// Returns every NKRO keyboard report written, oldest first.
+    #[must_use]
+    pub fn keyboard_reports(&self) -> Vec<KeyboardReport> {
+        self.writes()
+            .into_iter()
+            .filter_map(|(_, report)| KeyboardReport::try_from(report.as_slice()).ok())
+            .collect()
+    }
+
+    #[must_use]
+    pub fn last_keyboard_report(&self) -> Option<KeyboardReport> {
+        self.keyboard_reports().pop()
+    }
+
+    /// Returns every wheel mouse report written, oldest first.
+    #[must_use]
+    pub fn mouse_reports(&self) -> Vec<MouseReport> {
+        self.writes()
+            .into_iter()
+            .filter_map(|(_, report)| MouseReport::try_from(report.as_slice()).ok())
+            .collect()
+    }
+
+    /// Returns every consumer control report written, oldest first.
+    #[must_use]
+    pub fn consumer_reports(&self) -> Vec<ConsumerReport> {
+        self.writes()
+            .into_iter()
+            .filter_map(|(_, report)| ConsumerReport::try_from(report.as_slice()).ok())
+            .collect()
+    }
+
+    /// Returns every system control report written, oldest first.
+    #[must_use]
+    pub fn system_reports(&self) -> Vec<SystemReport> {
+        self.writes()
+            .into_iter()
+            .filter_map(|(_, report)| SystemReport::try_from(report.as_slice()).ok())
+            .collect()
+    }
+
+    /// Forgets every report written so far.
+    pub fn clear(&self) {
+        self.state.lock().unwrap().writes.clear();
+    }
+
+    /// Queues an output report from the host, like the keyboard LEDs, read on the next poll.
+    pub fn send_output_report(&self, report: &[u8]) {
+        self.state
+            .lock()
+            .unwrap()
+            .output_reports
+            .push_back(report.to_vec());
+    }
+}
+
+/// A captured NKRO boot keyboard report.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KeyboardReport {
+    bytes: [u8; Self::LEN],
+}
+
+impl KeyboardReport {
+    const LEN: usize = 25;
+    const NKRO_OFFSET: usize = 8;
+
+    /// Returns if the usage of `keycode` is set in the report.
+    #[must_use]
+    pub fn contains(&self, keycode: Keycode) -> bool {
+        let Some(usage) = keycode.into_keyboard() else {
+            return false;
+        };
+
+        if (0xE0..=0xE7).contains(&usage) {
+            self.bytes[0] & (1 << (usage - 0xE0)) != 0
+        } else {
+            let index = Self::NKRO_OFFSET + usize::from(usage / 8);
+            index < Self::LEN && self.bytes[index] & (1 << (usage % 8)) != 0
+        }
+    }
+
+    /// Returns if no keys or modifiers are set in the report.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.bytes[0] == 0
+            && self.bytes[Self::NKRO_OFFSET..]
+                .iter()
+                .enumerate()
+                // usages 1 to 3 are error codes, not keys
+                .all(|(index, byte)| if index == 0 { byte & !0b1111 == 0 } else { *byte == 0 })
+    }
+}
+
+impl TryFrom<&[u8]> for KeyboardReport {
+    type Error = ();
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Ok(Self {
+            bytes: bytes.try_into().map_err(|_| ())?,
+        })
+    }
+}
+
+/// A captured wheel mouse report.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MouseReport {
+    pub buttons: u8,
+    pub x: i8,
+    pub y: i8,
+    pub vertical_wheel: i8,
+    pub horizontal_wheel: i8,
+}
+
+impl TryFrom<&[u8]> for MouseReport {
+    type Error = ();
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let [buttons, x, y, vertical_wheel, horizontal_wheel] = bytes.try_into().map_err(|_| ())?;
+
+        Ok(Self {
+            buttons,
+            x: x as i8,
+            y: y as i8,
+            vertical_wheel: vertical_wheel as i8,
+            horizontal_wheel: horizontal_wheel as i8,
+        })
+    }
+}
+
+/// A captured consumer control report.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ConsumerReport {
+    /// Usages on the consumer page, 0 for an empty slot.
+    pub codes: [u16; 4],
+}
+
+impl TryFrom<&[u8]> for ConsumerReport {
+    type Error = ();
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let bytes: [u8; 8] = bytes.try_into().map_err(|_| ())?;
+        let mut codes = [0; 4];
+        for (code, bytes) in codes.iter_mut().zip(bytes.chunks_exact(2)) {
+            *code = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+
+        Ok(Self { codes })
+    }
+}
+
+/// A captured system control report.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SystemReport {
+    /// Usage on the generic desktop page, 0 when nothing is pressed.
+    pub usage: u8,
+}
+
+impl TryFrom<&[u8]> for SystemReport {
+    type Error = ();
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let [usage] = bytes.try_into().map_err(|_| ())?;
+
+        Ok(Self { usage })
+    }
+}
+
+/// One end of an in memory serial link.
+pub struct MockSerial {
+    rx: Arc<Mutex<VecDeque<u8>>>,
+    tx: Arc<Mutex<VecDeque<u8>>>,
+    // writes would block, like a peer that stopped reading
+    stalled: Cell<bool>,
+}
+
+impl MockSerial {
+    /// Returns both ends of a link, bytes written to one end are read from the other.
+    #[must_use]
+    pub fn pair() -> (Self, Self) {
+        let a = Arc::new(Mutex::new(VecDeque::new()));
+        let b = Arc::new(Mutex::new(VecDeque::new()));
+
+        (
+            Self {
+                rx: a.clone(),
+                tx: b.clone(),
+                stalled: Cell::new(false),
+            },
+            Self {
+                rx: b,
+                tx: a,
+                stalled: Cell::new(false),
+            },
+        )
+    }
+
+    /// Makes writes to this end block until called again with `false`.
+    pub fn stall(&self, stalled: bool) {
+        self.stalled.set(stalled);
+    }
+}
+
+impl Read<u8> for MockSerial {
+    type Error = Infallible;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.rx.lock().unwrap().pop_front().ok_or(nb::Error::WouldBlock)
+    }
+}
+
+impl Write<u8> for MockSerial {
+    type Error = Infallible;
+
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        if self.stalled.get() {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        self.tx.lock().unwrap().push_back(word);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        Ok(())
+    }
+}